@@ -1,37 +1,35 @@
 #![allow (non_camel_case_types, non_snake_case, non_upper_case_globals)]
 
-use itertools::Itertools;
 use tracing::{info, warn};
 
-use std::collections::{HashMap, HashSet};
-use std::sync::{LazyLock, OnceLock, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::config::Config;
+use crate::config::{AutoOverlayCondSpec, AutoOverlayRuleAction, Config, LearnedRuleTarget};
 use crate::dusky::WinDusky;
-use crate::effects::{ColorEffect, ColorEffects};
+use crate::effects::{ColorEffect, ColorEffects, EffectIntensity};
 use crate::luminance::calculate_avg_luminance;
+use crate::occlusion::calc_viz_bounds;
 use crate::tray::*;
 use crate::types::*;
 use crate::win_utils::*;
 
+pub mod cache;
+pub mod explain;
+pub mod remembered;
+pub mod resample;
+pub mod rules;
+pub mod titles;
+use cache::{EvalCache, WindowIdentity};
+use remembered::{AppIdentity, RememberMode, RememberedChoice, RememberedOverrides};
+use resample::Hysteresis;
+use rules::{Condition, Rule, RuleAction, RuleSet, RulesEval, WindowFacts};
+use rules::{LEGACY_PRIORITY__CLASS, LEGACY_PRIORITY__EXE, LEGACY_PRIORITY__LUMINANCE, LEGACY_PRIORITY__TITLE};
+use titles::TitlePattern;
 
 
-#[derive (Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
-pub enum RulesKey {
-    Rule_ClassId (String),
-    Rule_Exe (String),
-}
-
-
-#[derive (Debug, Clone)]
-pub struct RulesValue {
-    pub enabled   : bool,
-    pub effect    : Option <ColorEffect>,
-    pub excl_exes : Option <HashSet <String>>,
-}
-
 
 #[derive (Debug, Default, Copy, Clone)]
 pub struct RulesResult {
@@ -43,20 +41,30 @@ pub struct RulesResult {
     pub overridden : bool,
     // ^^ we'll set these for manually un-toggled overlays, and treat as hwnd-exclusions from then on
 
+    pub by_title : bool,
+    // ^^ whether the deciding rule checked the window title (as those get re-checked when the title changes)
+
+    pub lum : Option <u8>,
+    // ^^ the luminance measured during eval (if it was needed), so re-checks dont have to capture the window again
+
+    pub lum_retry : bool,
+    // ^^ set when the luminance capture failed (so the decision went w/o it), to have the window re-evaluated on next fgnd
+
     pub elev_excl : bool,
     // ^^ we calc this for hwnds if we're not-elevated, so we can print out warnings on impossible overlay attempts
-}
 
-impl From<&RulesValue> for RulesResult {
-    fn from (rv: &RulesValue) -> Self {
-        RulesResult { enabled:rv.enabled, effect: rv.effect, ..RulesResult::default() }
-    }
+    pub lum_flipped : Option <Instant>,
+    // ^^ when the luminance decision was last made (on first eval, or when re-sampling flipped it) .. so flips can be kept
+    // a min time apart, including the first one
 }
 
 static effect_none : LazyLock<RulesResult> = LazyLock::new (RulesResult::default);
 
 static effect_overriden : LazyLock<RulesResult> = LazyLock::new (|| RulesResult { overridden: true, ..RulesResult::default() });
 
+const EVAL_CACHE_CAPACITY : usize = 512;
+// ^^ way more windows than anyone would have open at once, so evictions should mostly just be for windows we missed closing
+
 
 
 
@@ -70,25 +78,30 @@ pub struct AutoOverlay {
     pub auto_overlay_enabled : Flag,
     // ^^ whether luminance/rules based auto-overlay is to be enabled .. otherwise, only manual toggles will be available
 
-    pub auto_overlay_lum__thresh : u8,
-    // ^^ if enabled, we'll capture and calculate avg luminance of hwnd when first seen (and trigger auto overlay if over threshold)
-    // .. A value of 0 means luminance based auto-overlay is to be disabled
-
     pub auto_overlay_lum__use_bitblt : bool,
     // ^^ whether the confs specify to use BitBlt (the altternate method) instead of the default PrintWindow
 
     pub auto_overlay_lum__delay_ms : u32,
     // ^^ since many windows even for dark-mode apps come up white before they get painted, we'll add a configurable delay
 
-    auto_overlay_lum__excl_exes : HashSet <String>,
-    // ^^ we'll load exclusions to luminance based auto-overlay rule here
-    // .. and since this is only loaded at init time, we'll use AtomicRefCell instead of RwLock for efficiency
+    auto_overlay_lum__resample_secs : u32,
+    lum_hysteresis : Hysteresis,
+    // ^^ how often (if at all) to re-sample luminance for windows decided by it, and how to damp flips from that
 
-    rules : HashMap <RulesKey, RulesValue>,
-    // ^^ other rules based on exe or window class names can be loaded from config (to trigger auto overlays)
+    rules : RwLock <RuleSet <ColorEffect>>,
+    // ^^ all the rules (compound ones, and those compiled from the simpler luminance/title/class/exe confs), by priority
+    // .. (behind a lock so they can be reloaded when rules get learned from windows)
 
-    eval_cache : RwLock <HashMap <Hwnd, RulesResult>>,
+    eval_cache : Mutex <EvalCache <Hwnd, WindowIdentity, RulesResult>>,
     // ^^ the results from evaluation of rules and/or luminance will be cached for efficiency
+    // .. (entries are checked against window identity to catch hwnd reuse, and the least recently used get evicted)
+
+    remember_mode : RememberMode,
+    // ^^ whether user overrides (un-toggles and effect picks) also get remembered per app, beyond the hwnd they were made on
+
+    remembered : RwLock <RememberedOverrides>,
+    remembered_file : Option <PathBuf>,
+    // ^^ the remembered per-app overrides, and the state file they're persisted to (so they survive restarts)
 
 }
 
@@ -106,48 +119,150 @@ impl AutoOverlay {
 
         let elevated = check_cur_proc_elevated().unwrap_or_default();
 
-        let auto_overlay_lum__thresh     = conf.get_auto_overlay_luminance__threshold();
         let auto_overlay_lum__delay_ms   = conf.get_auto_overlay_luminance__delay_ms();
         let auto_overlay_lum__use_bitblt = conf.get_auto_overlay_luminance__use_alternate();
 
-        let mut auto_overlay_lum__excl_exes : HashSet <String> = HashSet::new();
-        conf.get_auto_overlay_luminance__exclusion_exes() .into_iter() .for_each (|s| { auto_overlay_lum__excl_exes .insert(s); } );
-        info! ("lum auto-ov excl exes: {:?}", &auto_overlay_lum__excl_exes);
+        let auto_overlay_lum__resample_secs = conf.get_auto_overlay_luminance__resample_secs();
+        let lum_hysteresis = Hysteresis::new (
+            conf.get_auto_overlay_luminance__threshold(), conf.get_auto_overlay_luminance__off_threshold(),
+            Duration::from_secs (conf.get_auto_overlay_luminance__min_flip_secs() as u64),
+        );
 
-        let mut rules : HashMap <RulesKey, RulesValue> = HashMap::new();
-        for exe in conf.get_auto_overlay_exes() {
-            //tracing::debug! ("loading auto-overlay exe rule : {:?}", exe);
-            let effect = exe.effect .as_ref() .map (|s| effects.find_by_name(s));
-            let _ = rules .insert (
-                RulesKey::Rule_Exe (exe.exe),
-                RulesValue { enabled: true, effect, excl_exes: None }
-            );
-        }
-        for class in conf.get_auto_overlay_window_classes() {
-            //tracing::debug! ("loading auto-overlay exe rule : {:?}", class);
-            let excl_exes =  (!class.exclusion_exes.is_empty()) .then_some (class.exclusion_exes.into_iter().collect::<HashSet<String>>());
-            let effect = class.effect .as_ref() .map (|s| effects.find_by_name(s));
-            let _ = rules .insert (
-                RulesKey::Rule_ClassId (class.class),
-                RulesValue { enabled: true, effect, excl_exes }
-            );
-        }
-        info! ("The following auto-overlay rules were loaded :");
-        rules .iter() .sorted_by_key (|t| t.0) .enumerate() .for_each (|(i,t)| info!("{:?}.{:?}", i+1, t));
+        let rules = Self::compile_rules (conf, effects);
+        let auto_overlay_enabled = Flag::new (!rules.is_empty());
+        let rules = RwLock::new (rules);
 
-        let auto_overlay_enabled = Flag::new (auto_overlay_lum__thresh > 0  ||  !rules.is_empty());
+        let eval_cache = Mutex::new (EvalCache::new (EVAL_CACHE_CAPACITY));
 
-        let eval_cache = RwLock::new (HashMap::default());
+        let remember_mode = conf.get_auto_overlay_remember_overrides();
+        let remembered_file = conf.get_overrides_state_file();
+        let remembered = match (remember_mode, remembered_file.as_deref()) {
+            (RememberMode::None, _) | (_, None) => RememberedOverrides::default(),
+            (_, Some (path)) => Self::load_remembered (path),
+        };
+        info! ("Remembering user overrides : {:?} .. (loaded {:?} remembered overrides)", remember_mode, remembered.entries().len());
+        let remembered = RwLock::new (remembered);
 
         AUTO_OVERLAY.get_or_init ( move ||
             AutoOverlay {
-                elevated, auto_overlay_enabled, auto_overlay_lum__thresh, auto_overlay_lum__excl_exes,
-                auto_overlay_lum__use_bitblt, auto_overlay_lum__delay_ms, rules, eval_cache,
+                elevated, auto_overlay_enabled, auto_overlay_lum__use_bitblt, auto_overlay_lum__delay_ms,
+                auto_overlay_lum__resample_secs, lum_hysteresis, rules, eval_cache, remember_mode, remembered, remembered_file,
             }
         )
 
     }
 
+    pub fn compile_rules (conf: &Config, effects:&ColorEffects) -> RuleSet <ColorEffect> {
+        let rules = RuleSet::new (Self::load_rules (conf, effects));
+        info! ("The following auto-overlay rules were loaded (in priority order) :");
+        rules.rules() .iter() .enumerate() .for_each (|(i,r)| info!("{:?}. [{:?}] {:?} : {} -> {:?}", i+1, r.priority, r.name, r.when, r.action));
+        rules
+    }
+
+    /// Re-loads rules from the (in-memory) config, and drops cached evals (other than user overrides) so windows get
+    /// re-evaluated against the new rules when they next come to foreground
+    pub fn reload_rules (&self, conf: &Config, effects:&ColorEffects) {
+        let rules = Self::compile_rules (conf, effects);
+        let had_none = std::mem::replace (&mut *self.rules.write().unwrap(), rules) .is_empty();
+        if had_none && !self.auto_overlay_enabled.is_set() {
+            // auto-overlay starts disabled when there are no rules, so we'll enable it now that there are
+            self.toggle_auto_overlay_enabled();
+        }
+        self.eval_cache.lock().unwrap() .retain (|r| r.overridden);
+    }
+
+    /// Turns an hwnd's current overlay (or the lack of one) into a config rule for its exe or window class (per confs),
+    /// which then takes effect right away .. returns a description of the learned rule
+    pub fn learn_rule (&self, hwnd: Hwnd, effect: Option <ColorEffect>, conf: &Config, effects:&ColorEffects) -> Result <String, String> {
+        let facts = self.get_window_facts (hwnd, None) .ok_or ("couldn't get process info for the window")?;
+        let target = if conf.check_flag__learn_rule_by_class() {
+            LearnedRuleTarget::Class (facts.class.clone())
+        } else { LearnedRuleTarget::Exe (facts.exe.clone()) };
+
+        conf.save_learned_rule (&target, effect.map (|e| e.name()))?;
+
+        // the learned rule now captures the user's choice for this app, so any remembered override would just shadow it
+        self.forget_remembered_for (&facts, false);
+        self.reload_rules (conf, effects);
+
+        Ok ( match effect {
+            Some (e) if !e.1.is_full() => format! ("{target:?} -> overlay w {:?} (rules can't specify intensity, so at full intensity)", e.name()),
+            Some (e) => format! ("{target:?} -> overlay w {:?}", e.name()),
+            None => format! ("{target:?} -> exclude"),
+        } )
+    }
+
+    /// Loads the compound rules from config, along w rules compiled from the simpler luminance, title, class and exe confs
+    fn load_rules (conf: &Config, effects:&ColorEffects) -> Vec <Rule <ColorEffect>> {
+
+        let overlay = |effect: &Option<String>| RuleAction::Overlay (effect .as_ref() .map (|s| effects.find_by_name(s)));
+        let mut rules = vec![];
+
+        for rule in conf.get_auto_overlay_rules() {
+            let when = match compile_condition (&rule.when) {
+                Ok (when) => when,
+                Err (e) => { warn! ("{e} .. skipping auto-overlay rule {:?}", rule.name); continue }
+            };
+            let action = match rule.action {
+                AutoOverlayRuleAction::Overlay => overlay (&rule.effect),
+                AutoOverlayRuleAction::Exclude => RuleAction::Exclude,
+                AutoOverlayRuleAction::Nothing => RuleAction::Nothing,
+            };
+            let name = if rule.name.is_empty() { format! ("rule #{}", rules.len() + 1) } else { rule.name };
+            rules .push ( Rule { name, priority: rule.priority, when, action } );
+        }
+
+        let lum_thresh = conf.get_auto_overlay_luminance__threshold();
+        if lum_thresh > 0 {
+            // luminance over threshold, but we disable [0, 255] values as that typically means the window hasnt painted itself etc
+            let lum = Condition::Luminance { min: lum_thresh .saturating_add (1), max: u8::MAX - 1 };
+            let excl_exes = conf.get_auto_overlay_luminance__exclusion_exes();
+            info! ("lum auto-ov excl exes: {:?}", &excl_exes);
+            let when = if excl_exes.is_empty() { lum } else { Condition::All (vec! [ Condition::not (Condition::exes (&excl_exes)), lum ]) };
+            rules .push ( Rule { name: "luminance threshold".into(), priority: LEGACY_PRIORITY__LUMINANCE, when, action: RuleAction::Overlay (None) } );
+        }
+
+        for title in conf.get_auto_overlay_window_titles() {
+            let pattern = if title.is_regex { TitlePattern::from_regex (&title.title) } else { TitlePattern::from_glob (&title.title) };
+            let pattern = match pattern {
+                Ok (p) => Condition::Title (p),
+                Err (e) => { warn! ("{e} .. skipping that auto-overlay title rule"); continue }
+            };
+            let when = if title.exes.is_empty() { pattern } else { Condition::All (vec! [ Condition::exes (&title.exes), pattern ]) };
+            let name = format! ("title : {}", title.title);
+            rules .push ( Rule { name, priority: LEGACY_PRIORITY__TITLE, when, action: overlay (&title.effect) } );
+        }
+
+        for class in conf.get_auto_overlay_window_classes() {
+            // a class w excluded exes becomes two rules .. overlay for the rest, and then exclude those exes
+            let class_cond = Condition::classes ([&class.class]);
+            let when = if class.exclusion_exes.is_empty() { class_cond.clone() } else {
+                Condition::All (vec! [ class_cond.clone(), Condition::not (Condition::exes (&class.exclusion_exes)) ])
+            };
+            rules .push ( Rule { name: format! ("class : {}", class.class), priority: LEGACY_PRIORITY__CLASS, when, action: overlay (&class.effect) } );
+            if !class.exclusion_exes.is_empty() {
+                let name = format! ("class : {} (excluded exes)", class.class);
+                rules .push ( Rule { name, priority: LEGACY_PRIORITY__CLASS, when: class_cond, action: RuleAction::Exclude } );
+            }
+        }
+
+        for exe in conf.get_auto_overlay_exes() {
+            let when = Condition::exes ([&exe.exe]);
+            rules .push ( Rule { name: format! ("exe : {}", exe.exe), priority: LEGACY_PRIORITY__EXE, when, action: overlay (&exe.effect) } );
+        }
+
+        rules
+    }
+
+    fn load_remembered (path: &Path) -> RememberedOverrides {
+        if !path.exists() { return RememberedOverrides::default() }
+        let loaded = std::fs::read_to_string (path) .map_err (|e| e.to_string()) .and_then (|s| RememberedOverrides::parse (&s));
+        loaded .unwrap_or_else (|e| {
+            warn! ("Ignoring remembered overrides file {path:?} : {e} .. (it'll get overwritten if new overrides are remembered)");
+            RememberedOverrides::default()
+        } )
+    }
+
     pub fn toggle_auto_overlay_enabled (&self) -> bool {
         let enabled = !self.auto_overlay_enabled.toggle();
         update_tray__auto_overlay_enable (enabled);
@@ -157,48 +272,274 @@ impl AutoOverlay {
 
     pub fn register_user_unapplied (&self, hwnd:Hwnd) {
         info! ("Registering user un-toggle of overlay: {:?} .. (Override added!)", hwnd);
-        let mut eval_cache = self.eval_cache.write().unwrap();
-        if let Some(result) = eval_cache .get_mut (&hwnd) {
+        let ident = window_identity (hwnd);
+        let mut eval_cache = self.eval_cache.lock().unwrap();
+        let mut rule_enabled = false;
+        if let Some(result) = eval_cache .get_mut (hwnd, &ident) {
+            rule_enabled = result.enabled;
             result.enabled = false; result.overridden = true;
         } else {
-            eval_cache .insert (hwnd, *effect_overriden);
+            eval_cache .insert (hwnd, ident, *effect_overriden);
         }
-        let n_overrides = eval_cache .iter() .filter (|(_,r)| r.overridden) .count();
+        let n_overrides = eval_cache .values() .filter (|r| r.overridden) .count();
         update_tray__overrides_count(n_overrides);
+        drop (eval_cache);
+        if rule_enabled { self.remember_override (hwnd, RememberedChoice::Off); }
+        // ^^ only overriding the rules is worth remembering (un-toggling a manually applied overlay just undoes that)
+    }
+    pub fn register_user_applied (&self, hwnd:Hwnd) {
+        // toggling an overlay (back) on undoes any user override on the hwnd, and for an app that was remembered as off, forgets that
+        let mut eval_cache = self.eval_cache.lock().unwrap();
+        if let Some(result) = eval_cache .get_mut (hwnd, &window_identity (hwnd)) .filter (|r| r.overridden) {
+            result.overridden = false;
+            update_tray__overrides_count (eval_cache .values() .filter (|r| r.overridden) .count());
+        }
+        drop (eval_cache);
+        let Some (facts) = self.get_window_facts (hwnd, None) else { return };
+        self.forget_remembered_for (&facts, true);
+    }
+    fn forget_remembered_for (&self, facts: &WindowFacts, only_off: bool) {
+        let forgotten = {
+            let mut remembered = self.remembered.write().unwrap();
+            let entry = remembered .find (&facts.exe, &facts.class, &facts.title) .filter (|e| !only_off || e.choice == RememberedChoice::Off);
+            let id = entry .map (|e| e.app.id());
+            id .is_some_and (|id| remembered.forget (&id))
+        };
+        if forgotten {
+            info! ("Forgot the remembered override for {:?} ({:?})", facts.exe, facts.class);
+            self.save_remembered();
+        }
     }
     pub fn clear_user_overrides (&self) {
-        let mut eval_cache = self.eval_cache.write().unwrap();
-        let n_overrides = eval_cache .iter() .filter (|(_,r)| r.overridden) .count();
+        let mut eval_cache = self.eval_cache.lock().unwrap();
+        let n_overrides = eval_cache .values() .filter (|r| r.overridden) .count();
         info! ("Clearing all {:?} user-initiated rules overrides (and {:?} hwnd eval results)!", n_overrides, eval_cache.len());
+        info! ("(eval cache stats so far : {})", eval_cache.stats());
         eval_cache .clear();
         update_tray__overrides_count(0);
+        update_tray__eval_cache (eval_cache.len(), eval_cache.capacity(), eval_cache.stats());
+    }
+
+    /// Drops the cached result for a destroyed window (so it doesnt linger, or get picked up by a later window w the same hwnd)
+    pub fn forget_hwnd (&self, hwnd: Hwnd) {
+        let mut eval_cache = self.eval_cache.lock().unwrap();
+        let Some (result) = eval_cache.remove (hwnd) else { return };
+        if result.overridden {
+            update_tray__overrides_count (eval_cache .values() .filter (|r| r.overridden) .count());
+        }
+        update_tray__eval_cache (eval_cache.len(), eval_cache.capacity(), eval_cache.stats());
     }
 
 
     pub fn check_rule_cached (&self, hwnd: Hwnd) -> Option <RulesResult> {
-        let eval_cache = self.eval_cache.read().unwrap();
-        eval_cache .get (&hwnd) .copied()
+        let ident = window_identity (hwnd);
+        self.eval_cache.lock().unwrap() .get (hwnd, &ident) .copied()
     }
     pub fn update_cached_rule_result_effect (&self, hwnd: Hwnd, effect:ColorEffect) {
-        if let Some(result) = self.eval_cache.write().unwrap().get_mut(&hwnd) {
+        if let Some(result) = self.eval_cache.lock().unwrap().get_mut(hwnd, &window_identity (hwnd)) {
             //tracing::debug!("found cached result for {:?} .. {:?}", hwnd, &result);
             result.effect.replace(effect);
         }
+        self.remember_override (hwnd, RememberedChoice::Effect { name: effect.name().to_string(), intensity: effect.1.0 });
+    }
+
+
+    fn remember_override (&self, hwnd: Hwnd, choice: RememberedChoice) {
+        if self.remember_mode == RememberMode::None { return }
+        let Some (info) = get_proc_info (hwnd) else { return };
+        let class = get_win_class_by_hwnd (hwnd);
+        let app = match self.remember_mode {
+            RememberMode::AppTitle => AppIdentity::with_exact_title (&info.exe, &class, &get_win_title (hwnd)),
+            _ => AppIdentity::new (&info.exe, &class, None),
+        };
+        info! ("Remembering user override for {} : {:?}", app.label(), choice);
+        self.remembered.write().unwrap() .remember (app, choice);
+        self.save_remembered();
+    }
+
+    pub fn forget_remembered_override (&self, id: &str) {
+        if self.remembered.write().unwrap() .forget (id) {
+            info! ("Forgot remembered user override for {:?}", id);
+            self.save_remembered();
+        }
+    }
+
+    fn save_remembered (&self) {
+        if let Some (path) = self.remembered_file.as_ref() {
+            if let Err (e) = std::fs::write (path, self.remembered.read().unwrap() .serialize()) {
+                warn! ("Failed to save remembered overrides to {path:?} : {e}");
+            }
+        }
+        self.report_remembered_overrides();
+    }
+
+    pub fn report_remembered_overrides (&self) {
+        let remembered = self.remembered.read().unwrap();
+        update_tray__remembered_overrides (remembered.entries() .iter() .map (|e| (e.app.id(), e.label())) .collect());
+    }
+
+    fn find_remembered (&self, facts: &WindowFacts) -> Option <RememberedChoice> {
+        if self.remember_mode == RememberMode::None { return None }
+        self.remembered.read().unwrap() .find (&facts.exe, &facts.class, &facts.title) .map (|e| e.choice.clone())
+    }
+
+    /// The remembered effect for an hwnd's app (if any, and if that effect is still around in the confs)
+    pub fn find_remembered_effect (&self, hwnd: Hwnd) -> Option <ColorEffect> {
+        match self.find_remembered (&self.get_window_facts (hwnd, None)?)? {
+            RememberedChoice::Effect { name, intensity } => Self::remembered_effect (&name, intensity),
+            RememberedChoice::Off => None,
+        }
+    }
+    fn remembered_effect (name: &str, intensity: u8) -> Option <ColorEffect> {
+        let idx = ColorEffects::instance().cycle_order .iter() .position (|(s,_)| s == name)?;
+        Some ( ColorEffect::new (idx) .with_intensity (EffectIntensity (intensity .clamp (EffectIntensity::MIN, EffectIntensity::FULL.0))) )
+    }
+
+    /// Applies any remembered user override for the window's app on top of a rules result
+    fn apply_remembered (&self, facts: &WindowFacts, result: RulesResult) -> RulesResult {
+        match self.find_remembered (facts) {
+            Some (RememberedChoice::Off) => RulesResult { enabled: false, effect: None, overridden: true, ..result },
+            Some (RememberedChoice::Effect { name, intensity }) if result.enabled => {
+                RulesResult { effect: Self::remembered_effect (&name, intensity) .or (result.effect), ..result }
+            }
+            _ => result,
+        }
     }
 
     pub fn re_check_rule (&self, hwnd: Hwnd) -> RulesResult {
+        let result = self.eval_rules (hwnd);
+        self.cache_rule_result (hwnd, result)
+    }
 
-        let mut result = self.eval_rules (hwnd);
+    fn cache_rule_result (&self, hwnd: Hwnd, result: RulesResult) -> RulesResult {
+        let ident = window_identity (hwnd);
+        Self::cache_rule_result_locked (&mut self.eval_cache.lock().unwrap(), hwnd, ident, result)
+    }
 
-        let mut eval_cache = self.eval_cache.write().unwrap();
+    fn cache_rule_result_locked (
+        eval_cache: &mut EvalCache <Hwnd, WindowIdentity, RulesResult>, hwnd: Hwnd, ident: WindowIdentity, mut result: RulesResult
+    ) -> RulesResult {
         if result.enabled && result.effect.is_none() {
             let effect = Some (ColorEffects::instance().default);
             result = RulesResult { overridden: false, effect, ..result }
         }
-        eval_cache .insert (hwnd, result);
+        eval_cache .insert (hwnd, ident, result);
+        update_tray__eval_cache (eval_cache.len(), eval_cache.capacity(), eval_cache.stats());
         result
     }
 
+    /// Re-checks rules for an hwnd whose title changed .. returns the updated result if that changes whether it should have
+    /// an overlay (user overrides are left alone, as are windows whose decision would need a fresh luminance capture)
+    pub fn re_check_title_rules (&self, hwnd: Hwnd) -> Option <RulesResult> {
+        if !self.auto_overlay_enabled.is_set() { return None }
+
+        let cached = self.check_rule_cached (hwnd)?;
+        // ^^ we only re-check hwnds we've evaluated before (others get a full eval when they first come to fgnd anyway)
+        let rules = self.rules.read().unwrap();
+        if cached.overridden || cached.elev_excl || !rules.uses_title() { return None }
+
+        let facts = self.get_window_facts (hwnd, cached.lum)?;
+        let result = match rules.eval (&facts) {
+            RulesEval::NeedsLuminance => return None,
+            RulesEval::Decided (Rule { action: RuleAction::Nothing, .. }) => return None,
+            eval => self.apply_remembered (&facts, Self::eval_result (eval, cached.lum)),
+        };
+        if !result.by_title && !cached.by_title { return None }
+        // ^^ w only the title changed, decisions not involving titles cant have changed
+        if result.enabled == cached.enabled && result.by_title == cached.by_title { return None }
+
+        let result = RulesResult { lum_flipped: cached.lum_flipped, ..result };
+        info! ("Title change for {:?} re-evaluated auto-overlay rules to : {:?}", hwnd, result);
+        Some (self.cache_rule_result (hwnd, result))
+    }
+
+    /// Starts the periodic luminance re-sampling (if configured), which re-evaluates windows whose decision hinged on it
+    pub fn start_lum_resampling (&'static self, wd: &'static WinDusky) {
+        let secs = self.auto_overlay_lum__resample_secs;
+        if secs == 0 { return }
+        thread::spawn (move || loop {
+            thread::sleep (Duration::from_secs (secs as u64));
+            if self.auto_overlay_enabled.is_set() && !wd.check_fs_mode() {
+                self.resample_luminance (wd);
+            }
+        } );
+        info! ("Started luminance re-sampling every {secs}s .. (w {:?})", self.lum_hysteresis);
+    }
+
+    fn resample_luminance (&self, wd: &WinDusky) {
+        // we'll only re-sample windows whose decision hinged on luminance, and that the user hasnt overridden ..
+        // .. and if the user toggled an overlay on themselves (so it doesnt match the cached decision), we'll leave that be too
+        let candidates : Vec <(Hwnd, RulesResult)> = self.eval_cache.lock().unwrap() .iter()
+            .filter (|(_, r)| r.lum.is_some() && !r.overridden && !r.elev_excl)
+            .map (|(hwnd, r)| (hwnd, *r)) .collect();
+        let candidates : Vec <(Hwnd, RulesResult)> = candidates .into_iter()
+            .filter (|(hwnd, r)| wd.has_overlay (hwnd) == r.enabled)
+            .filter (|(hwnd, _)| check_window_visible (*hwnd) && !check_window_cloaked (*hwnd) && !check_window_minimized (*hwnd))
+            .collect();
+        if candidates.is_empty() { return }
+
+        // there's no point capturing windows that cant be seen, and that'd likely capture the wrong pixels w BitBlt anyway
+        let Ok (viz) = calc_viz_bounds (wd, candidates .iter() .map (|(hwnd, _)| *hwnd)) else { return };
+        let visible = viz .into_iter() .filter (|(_, bounds)| bounds.is_some()) .map (|(hwnd, _)| hwnd) .collect::<Vec<_>>();
+
+        for (hwnd, cached) in candidates .into_iter() .filter (|(hwnd, _)| visible.contains (hwnd)) {
+            let Some (lum) = calculate_avg_luminance (hwnd, self.auto_overlay_lum__use_bitblt) else { continue };
+            // ^^ unlike first evals, a failed capture here just means we'll try again next time
+            let Some (facts) = self.get_window_facts (hwnd, Some (lum)) else { continue };
+
+            let rules = self.rules.read().unwrap();
+            let decide = |lum: u8| match rules.eval (&WindowFacts { luminance: Some (lum), ..facts.clone() }) {
+                RulesEval::Decided (Rule { action: RuleAction::Overlay (_), .. }) => Some (true),
+                RulesEval::Decided (Rule { action: RuleAction::Exclude, .. }) | RulesEval::NoMatch => Some (false),
+                RulesEval::Decided (Rule { action: RuleAction::Nothing, .. }) | RulesEval::NeedsLuminance => None,
+            };
+            let since_flip = cached.lum_flipped .map (|t| t.elapsed());
+            let flip = resample::should_flip (&self.lum_hysteresis, cached.enabled, lum, since_flip, decide);
+            let result = flip .then (|| self.apply_remembered (&facts, Self::eval_result (rules.eval (&facts), Some (lum))));
+            drop (rules);
+
+            // the user could have toggled the overlay (or the window got re-evaluated) while we were capturing, so we'll only
+            // update the cached entry if it's still the one we sampled for .. (and post w the lock held, to keep it that way)
+            let (ident, has_overlay) = (window_identity (hwnd), wd.has_overlay (&hwnd));
+            // ^^ (checked before locking the cache, as clearing overlays clears user overrides w the overlays lock held)
+            let mut eval_cache = self.eval_cache.lock().unwrap();
+            let Some (entry) = eval_cache .get_mut (hwnd, &ident) else { continue };
+            if (entry.enabled, entry.overridden, entry.lum) != (cached.enabled, cached.overridden, cached.lum) { continue }
+            let Some (result) = result else {
+                entry.lum = Some (lum);
+                // ^^ w/o a flip we just keep the latest luminance (so the next flip is logged against it)
+                continue
+            };
+            let result = RulesResult { lum_flipped: Some (Instant::now()), ..result };
+            let result = Self::cache_rule_result_locked (&mut eval_cache, hwnd, ident, result);
+            info! ("Luminance re-sampling flipped auto-overlay for {:?} (luminance {:?} -> {:?}) : {:?}", hwnd, cached.lum, lum, result);
+
+            if result.enabled && !has_overlay {
+                wd.post_req__overlay_create (hwnd, result.effect.unwrap_or (wd.effects.default));
+            } else if !result.enabled && has_overlay {
+                wd.post_req__overlay_fade_out (hwnd);
+            }
+        }
+    }
+
+    fn get_window_facts (&self, hwnd:Hwnd, luminance: Option<u8>) -> Option <WindowFacts> {
+        let info = get_proc_info (hwnd)?;
+        Some ( WindowFacts { exe: info.exe, class: get_win_class_by_hwnd (hwnd), title: get_win_title (hwnd), luminance } )
+    }
+
+    fn eval_result (eval: RulesEval <ColorEffect>, lum: Option<u8>) -> RulesResult {
+        match eval {
+            RulesEval::Decided (rule) => {
+                let (enabled, effect) = match rule.action {
+                    RuleAction::Overlay (effect) => (true, effect),
+                    RuleAction::Exclude | RuleAction::Nothing => (false, None),
+                };
+                RulesResult { enabled, effect, by_title: rule.when.uses_title(), lum, ..RulesResult::default() }
+            }
+            _ => RulesResult { lum, ..*effect_none },
+        }
+    }
+
     fn eval_rules (&self, hwnd:Hwnd) -> RulesResult {
 
         //tracing::debug! ("Evaluating rules for new {:?}", hwnd);
@@ -209,37 +550,78 @@ impl AutoOverlay {
         let Some(info) = get_proc_info(hwnd) else {
             return *effect_none
         };
-
         let elev_excl = !self.elevated && info.elev;
 
-        if !self.auto_overlay_lum__excl_exes .contains (&info.exe) {
-            if self.auto_overlay_lum__thresh > 0 {
-                if let Some (lum) = calculate_avg_luminance (hwnd, self.auto_overlay_lum__use_bitblt) {
-                    //tracing::debug! ("got luminance {:?} for {:?}", lum, hwnd);
-                    if lum != u8::MIN  &&  lum != u8::MAX  &&  lum > self.auto_overlay_lum__thresh {
-                        // ^^ we disable [0, 255] values as that typically means the window hasnt painted itself etc
-                        // ^^ and since we do multiple evals for first-seen hwnds, we'll just come back to this later
-                        info! ("Found avg luminance of {:?} for {:?} .. will auto-apply an overlay!", lum, hwnd);
-                        return RulesResult { enabled:true, elev_excl, ..RulesResult::default() }
-                    }
-                }
-            }
+        let mut facts = WindowFacts {
+            exe: info.exe, class: get_win_class_by_hwnd (hwnd), title: get_win_title (hwnd), luminance: None
+        };
+        if let Some (RememberedChoice::Off) = self.find_remembered (&facts) {
+            // the user had toggled this app's overlay off before (and that was remembered), so no need to go further
+            return RulesResult { overridden: true, elev_excl, ..*effect_none }
         }
+        let rules = self.rules.read().unwrap();
+        let mut eval = rules.eval (&facts);
+
+        let mut lum = None;
+        if let RulesEval::NeedsLuminance = eval {
+            // some rule hinges on luminance, so we'll have to capture the window for it
+            lum = calculate_avg_luminance (hwnd, self.auto_overlay_lum__use_bitblt);
+            //tracing::debug! ("got luminance {:?} for {:?}", lum, hwnd);
+            facts.luminance = Some (lum .unwrap_or (u8::MIN));
+            // ^^ a failed capture counts as zero, which luminance conditions never match (same as for unpainted windows)
+            // .. but we wont cache that as its luminance, and will instead capture again when it next comes to fgnd
+            eval = rules.eval (&facts);
+        }
+        let lum_retry = facts.luminance.is_some() && lum.is_none();
+        if let RulesEval::Decided (Rule { name, action: RuleAction::Overlay (_), .. }) = &eval {
+            info! ("Auto-overlay rule {:?} matched {:?} (luminance: {:?}) .. will auto-apply an overlay!", name, hwnd, facts.luminance);
+        }
+        let lum_flipped = lum.map (|_| Instant::now());
+        RulesResult { elev_excl, lum_retry, lum_flipped, ..self.apply_remembered (&facts, Self::eval_result (eval, lum)) }
+    }
 
-        let class = get_win_class_by_hwnd (hwnd);
 
-        if let Some(result) = self.rules .get (& RulesKey::Rule_ClassId (class)) {
-            if result.excl_exes.as_ref().is_some_and (|h| h.contains(&info.exe)) {
-                return *effect_none
+    /// Explains (as lines of text) how auto-overlay rules decide for an hwnd, along w anything else that affects whether
+    /// it gets an overlay (cached results, overrides, elevation etc) .. (this can capture the window, so it can be slow)
+    pub fn explain_window (&self, hwnd: Hwnd) -> Vec <String> {
+        let mut lines = vec! [ format! ("Explaining auto-overlay rules for {:?} :", hwnd) ];
+        if !self.auto_overlay_enabled.is_set() {
+            lines.push ("auto-overlay is disabled (so only manual toggles apply)".to_string());
+        }
+        let Some (info) = get_proc_info (hwnd) else {
+            lines.push ("couldn't get process info for the window, so no rules can apply to it".to_string());
+            return lines
+        };
+        if !self.elevated && info.elev {
+            lines.push ("the window is elevated but WinDusky isn't, so overlays can't be applied to it".to_string());
+        }
+        match self.check_rule_cached (hwnd) {
+            Some (cached) => {
+                let effect = cached.effect .map (|e| e.label()) .unwrap_or ("none".into());
+                lines.push (format! ("cached result (used when it next comes to fgnd) : overlay {}, effect {effect}, lum {:?}", cached.enabled, cached.lum));
+                if cached.overridden {
+                    lines.push ("the window has a user override (toggled off), so rules don't apply to it until overrides are cleared".to_string());
+                }
+                if let Some (flipped) = cached.lum_flipped {
+                    lines.push (format! ("the luminance decision was last made (or flipped by re-sampling) {:.0?} ago", flipped.elapsed()));
+                }
             }
-            return RulesResult { elev_excl, ..result.into() };
+            None => lines.push ("no cached result (not evaluated yet), so rules get evaluated when it next comes to fgnd".to_string()),
         }
-
-        if let Some(result) = self.rules .get (& RulesKey::Rule_Exe (info.exe)) {
-            return RulesResult { elev_excl, ..result.into() };
+        let cache_stats = { let c = self.eval_cache.lock().unwrap(); format! ("{}/{} entries, {}", c.len(), c.capacity(), c.stats()) };
+        lines.push (format! ("eval cache : {cache_stats}"));
+        let mut facts = WindowFacts { exe: info.exe, class: get_win_class_by_hwnd (hwnd), title: get_win_title (hwnd), luminance: None };
+        if let Some (entry) = self.remembered.read().unwrap() .find (&facts.exe, &facts.class, &facts.title) .filter (|_| self.remember_mode != RememberMode::None) {
+            lines.push (format! ("remembered override for the app (applies over the rules) : {}", entry.label()));
         }
-
-        *effect_none
+        let rules = self.rules.read().unwrap();
+        if let RulesEval::NeedsLuminance = rules.eval (&facts) {
+            let lum = calculate_avg_luminance (hwnd, self.auto_overlay_lum__use_bitblt);
+            lines.push (format! ("captured the window's luminance (as a rule hinges on it) : {}", lum .map_or ("failed (counts as 0)".into(), |l| l.to_string())));
+            facts.luminance = Some (lum .unwrap_or (u8::MIN));
+        }
+        lines.extend (explain::explain_rules (&rules, &facts, |e| e.label()));
+        lines
     }
 
 
@@ -253,7 +635,7 @@ impl AutoOverlay {
         // next we'll check if we have have evaluated auto-overlay rules for this previously
         let result = self.check_rule_cached (hwnd);
 
-        if let Some ( RulesResult { enabled: false, .. } ) = result {
+        if let Some ( RulesResult { enabled: false, lum_retry: false, .. } ) = result {
             return;
         }
         else if let Some ( RulesResult { enabled: true, effect, ..} ) = result {
@@ -262,7 +644,7 @@ impl AutoOverlay {
         }
 
 
-        // so looks like this is first ever fgnd for this, so we'd like to eval from scratch ..
+        // so looks like this is first ever fgnd for this (or its luminance capture failed last time), so we'd like to eval from scratch ..
         // .. but eval for luminance requies screen cap, so we'll spawn thread to do all that
         thread::spawn ( move || {
             //tracing::debug! ("Processing Auto-Overlay for new {:?}", hwnd);
@@ -315,3 +697,30 @@ impl AutoOverlay {
 
 
 
+
+
+
+/// What cached eval results get checked against, to tell a reused hwnd from the window a result was for
+fn window_identity (hwnd: Hwnd) -> WindowIdentity {
+    let (thread_id, pid) = get_thread_and_pid_by_hwnd (hwnd);
+    WindowIdentity { pid, thread_id, class: get_win_class_by_hwnd (hwnd) }
+}
+
+
+/// Compiles a rule condition from its config spec .. all the specified parts must hold (and an empty spec always holds)
+fn compile_condition (spec: &AutoOverlayCondSpec) -> Result <Condition, String> {
+    let mut conds = vec![];
+    if !spec.exes.is_empty()    { conds.push (Condition::exes (&spec.exes)) }
+    if !spec.classes.is_empty() { conds.push (Condition::classes (&spec.classes)) }
+    if let Some ((title, is_regex)) = spec.title.as_ref() {
+        conds.push (Condition::Title (if *is_regex { TitlePattern::from_regex (title)? } else { TitlePattern::from_glob (title)? }));
+    }
+    if let Some ((min, max)) = spec.luminance {
+        let to_u8 = |f: f32| (u8::MAX as f32 * f.clamp (0.0, 1.0)) as u8;
+        conds.push (Condition::Luminance { min: to_u8 (min), max: to_u8 (max) });
+    }
+    for not in spec.not.iter() {
+        conds.push (Condition::not (compile_condition (not)?));
+    }
+    Ok (if conds.len() == 1 { conds.remove(0) } else { Condition::All (conds) })
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+
+
+// A bounded cache for per-window auto-overlay eval results .. keyed by hwnd, but w each entry also holding the identity
+// of the window it was made for (see WindowIdentity), since hwnds get reused once windows are gone, and a new window
+// shouldnt inherit a dead one's result. Entries for destroyed windows get dropped as those events come in, and beyond
+// that, the least recently used entries get evicted once the cache is at capacity.
+// (Eviction just scans for the oldest entry .. w at most a few hundred entries, that's cheaper than keeping a list in order)
+
+
+
+/// What we check a cached entry against, to tell a reused hwnd from the window the entry was made for
+#[derive (Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowIdentity {
+    pub pid       : u32,
+    pub thread_id : u32,
+    // ^^ of the thread that created the window
+    pub class     : String,
+}
+
+
+#[derive (Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits      : u64,
+    pub misses    : u64,
+    pub stale     : u64,
+    // ^^ misses where there was an entry, but for a different window that had the same hwnd
+    pub evictions : u64,
+}
+
+impl std::fmt::Display for CacheStats {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write! (f, "{} hits, {} misses ({} stale), {} evictions", self.hits, self.misses, self.stale, self.evictions)
+    }
+}
+
+
+#[derive (Debug)]
+struct Entry <I, V> {
+    ident     : I,
+    value     : V,
+    last_used : u64,
+}
+
+#[derive (Debug)]
+pub struct EvalCache <K, I, V> {
+    entries  : HashMap <K, Entry <I, V>>,
+    capacity : usize,
+    tick     : u64,
+    stats    : CacheStats,
+}
+
+impl <K: Hash + Eq + Copy, I: PartialEq, V> EvalCache <K, I, V> {
+
+    pub fn new (capacity: usize) -> EvalCache <K, I, V> {
+        EvalCache { entries: HashMap::default(), capacity: capacity.max (1), tick: 0, stats: CacheStats::default() }
+    }
+
+    pub fn len (&self) -> usize { self.entries.len() }
+
+    pub fn capacity (&self) -> usize { self.capacity }
+
+    pub fn stats (&self) -> CacheStats { self.stats }
+
+    /// Drops the entry for the key if it was made for some other window identity, returning whether it did
+    fn drop_if_stale (&mut self, key: K, ident: &I) -> bool {
+        if self.entries .get (&key) .is_some_and (|e| e.ident != *ident) {
+            self.entries.remove (&key);
+            return true
+        }
+        false
+    }
+
+    fn touch (&mut self, key: K) -> Option <&mut Entry <I, V>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries .get_mut (&key)?;
+        entry.last_used = tick;
+        Some (entry)
+    }
+
+    /// Looks up the entry for the key (if it's for the same window identity), counting hits and misses
+    pub fn get (&mut self, key: K, ident: &I) -> Option <&V> {
+        if self.drop_if_stale (key, ident) { self.stats.stale += 1 }
+        if self.entries.contains_key (&key) { self.stats.hits += 1 } else { self.stats.misses += 1 }
+        self.touch (key) .map (|e| &e.value)
+    }
+
+    /// Like get, but for updating the entry (and w/o counting towards the hit/miss stats)
+    pub fn get_mut (&mut self, key: K, ident: &I) -> Option <&mut V> {
+        self.drop_if_stale (key, ident);
+        self.touch (key) .map (|e| &mut e.value)
+    }
+
+    pub fn insert (&mut self, key: K, ident: I, value: V) {
+        if !self.entries.contains_key (&key) && self.entries.len() >= self.capacity {
+            let oldest = self.entries .iter() .min_by_key (|(_, e)| e.last_used) .map (|(k, _)| *k);
+            if let Some (oldest) = oldest {
+                self.entries.remove (&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+        self.tick += 1;
+        self.entries.insert (key, Entry { ident, value, last_used: self.tick });
+    }
+
+    pub fn remove (&mut self, key: K) -> Option <V> {
+        self.entries.remove (&key) .map (|e| e.value)
+    }
+
+    pub fn retain (&mut self, mut keep: impl FnMut (&V) -> bool) {
+        self.entries .retain (|_, e| keep (&e.value));
+    }
+
+    pub fn clear (&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn values (&self) -> impl Iterator <Item = &V> {
+        self.entries .values() .map (|e| &e.value)
+    }
+
+    pub fn iter (&self) -> impl Iterator <Item = (K, &V)> {
+        self.entries .iter() .map (|(k, e)| (*k, &e.value))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction () {
+        let mut cache : EvalCache <u32, u32, &str> = EvalCache::new (2);
+        cache.insert (1, 0, "a");
+        cache.insert (2, 0, "b");
+        assert_eq! (cache.get (1, &0), Some (&"a"));
+        // ^^ so now 2 is the least recently used
+        cache.insert (3, 0, "c");
+        assert_eq! (cache.get (2, &0), None);
+        assert_eq! ((cache.len(), cache.get (3, &0)), (2, Some (&"c")));
+        // re-inserting an existing key replaces it w/o evicting anything
+        cache.insert (1, 0, "a2");
+        assert_eq! (cache.stats(), CacheStats { hits: 2, misses: 1, stale: 0, evictions: 1 });
+        assert_eq! ((cache.len(), cache.get (1, &0)), (2, Some (&"a2")));
+    }
+
+    #[test]
+    fn test_reused_keys () {
+        let mut cache : EvalCache <u32, WindowIdentity, bool> = EvalCache::new (8);
+        let ident = |pid: u32| WindowIdentity { pid, thread_id: 7, class: "Notepad".into() };
+        cache.insert (42, ident (100), true);
+        assert_eq! (cache.get (42, &ident (100)), Some (&true));
+
+        // a different window w the same hwnd doesnt see (or update) the old window's result, and it gets dropped
+        assert_eq! (cache.get_mut (42, &ident (200)), None);
+        assert_eq! (cache.get (42, &ident (100)), None);
+        cache.insert (42, ident (200), false);
+        assert_eq! (cache.get (42, &ident (100)), None);
+        assert_eq! (cache.stats().stale, 1);
+        assert_eq! ((cache.remove (42), cache.len()), (None, 0));
+        // ^^ (the stale lookup above already dropped it)
+    }
+
+}
@@ -0,0 +1,115 @@
+use crate::auto::rules::{Condition, RuleAction, RuleSet, RulesEval, WindowFacts};
+
+
+
+// Explanations of auto-overlay rule decisions .. walks the rules in priority order like eval does, but reports how each
+// checked rule fared (and how each part of its condition evaluated), and what finally decided things.
+// This is kept independent of live windows, so the same explanation can come from the explain hotkey (w the window's
+// actual facts, and its luminance captured if needed), or from the cli (w facts given on the command line).
+
+
+
+fn tri (v: Option <bool>) -> &'static str {
+    match v { Some (true) => "yes", Some (false) => "no", None => "unknown (luminance not measured)" }
+}
+
+/// The top-level parts of a condition (ie the terms of an AND), each w how it evaluated
+fn condition_parts (cond: &Condition, facts: &WindowFacts) -> Vec <(String, Option <bool>)> {
+    let parts : Vec <&Condition> = match cond {
+        Condition::All (conds) if !conds.is_empty() => conds.iter().collect(),
+        _ => vec! [cond],
+    };
+    parts .into_iter() .map (|c| (c.to_string(), c.eval (facts))) .collect()
+}
+
+pub fn describe_facts (facts: &WindowFacts) -> String {
+    let lum = match facts.luminance {
+        Some (lum) => format! ("{lum} ({:.2})", lum as f32 / u8::MAX as f32),
+        None => "not measured".to_string(),
+    };
+    format! ("window : exe {:?}, class {:?}, title {:?}, luminance {lum}", facts.exe, facts.class, facts.title)
+}
+
+pub fn describe_action <E> (action: &RuleAction <E>, effect_label: impl Fn (&E) -> String) -> String {
+    match action {
+        RuleAction::Overlay (Some (effect)) => format! ("overlay (effect : {})", effect_label (effect)),
+        RuleAction::Overlay (None) => "overlay (default effect)".to_string(),
+        RuleAction::Exclude => "exclude (no overlay)".to_string(),
+        RuleAction::Nothing => "nothing (leaves the window as is)".to_string(),
+    }
+}
+
+/// Explains the eval of a rule-set for the given facts, as lines of text
+pub fn explain_rules <E> (rules: &RuleSet <E>, facts: &WindowFacts, effect_label: impl Fn (&E) -> String) -> Vec <String> {
+    let mut lines = vec! [ describe_facts (facts) ];
+    if rules.is_empty() {
+        lines.push ("no auto-overlay rules are configured".to_string());
+        return lines
+    }
+    let eval = rules.eval (facts);
+    let n_checked = match &eval {
+        RulesEval::Decided (rule) => rules.rules() .iter() .position (|r| std::ptr::eq (r, *rule)) .map_or (rules.rules().len(), |i| i + 1),
+        RulesEval::NeedsLuminance => rules.rules() .iter() .position (|r| r.when.eval (facts) .is_none()) .map_or (0, |i| i + 1),
+        RulesEval::NoMatch => rules.rules().len(),
+    };
+    for rule in rules.rules() .iter() .take (n_checked) {
+        let verdict = match rule.when.eval (facts) { Some (true) => "MATCH", Some (false) => "no match", None => "undecided" };
+        let parts = condition_parts (&rule.when, facts) .into_iter() .map (|(desc, v)| format! ("{desc} : {}", tri (v))) .collect::<Vec<_>>();
+        lines.push (format! ("  [{}] {:?} : {verdict} .. {}", rule.priority, rule.name, parts.join (", ")));
+    }
+    let n_skipped = rules.rules().len() - n_checked;
+    if n_skipped > 0 {
+        lines.push (format! ("  ({n_skipped} lower priority rule(s) not checked)"));
+    }
+    lines.push ( match eval {
+        RulesEval::Decided (rule) => format! ("=> decided by {:?} : {}", rule.name, describe_action (&rule.action, effect_label)),
+        RulesEval::NoMatch => "=> no rule matched : no overlay".to_string(),
+        RulesEval::NeedsLuminance => "=> undecided .. the highest matching candidate hinges on luminance, which isn't measured".to_string(),
+    } );
+    lines
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auto::rules::{Rule, LEGACY_PRIORITY__EXE, LEGACY_PRIORITY__LUMINANCE};
+
+    fn facts (exe: &str, luminance: Option <u8>) -> WindowFacts {
+        WindowFacts { exe: exe.into(), class: "Notepad".into(), title: "x.txt".into(), luminance }
+    }
+
+    #[test]
+    fn test_explanations () {
+        let lum = Condition::All (vec! [ Condition::not (Condition::exes (["chrome.exe"])), Condition::Luminance { min: 179, max: 254 } ]);
+        let rules = RuleSet::new (vec! [
+            Rule { name: "exe".into(), priority: LEGACY_PRIORITY__EXE, when: Condition::exes (["notepad.exe"]), action: RuleAction::Overlay (Some (3)) },
+            Rule { name: "lum".into(), priority: LEGACY_PRIORITY__LUMINANCE, when: lum, action: RuleAction::Overlay (None) },
+        ] );
+        let label = |e: &u32| format! ("effect #{e}");
+
+        let lines = explain_rules (&rules, &facts ("notepad.exe", Some (40)), label);
+        assert_eq! (lines, [
+            r#"window : exe "notepad.exe", class "Notepad", title "x.txt", luminance 40 (0.16)"#,
+            r#"  [400] "lum" : no match .. NOT (exe in ["chrome.exe"]) : yes, luminance in 179..=254 : no"#,
+            r#"  [100] "exe" : MATCH .. exe in ["notepad.exe"] : yes"#,
+            r#"=> decided by "exe" : overlay (effect : effect #3)"#,
+        ]);
+
+        // w/o luminance, we stop at the rule that hinges on it
+        let lines = explain_rules (&rules, &facts ("notepad.exe", None), label);
+        assert! (lines[1] .contains ("undecided") && lines[2] .contains ("1 lower priority rule(s) not checked"));
+        assert! (lines[3] .starts_with ("=> undecided"));
+
+        // and a bright window is decided by the luminance rule, before the exe rule gets checked
+        let lines = explain_rules (&rules, &facts ("notepad.exe", Some (220)), label);
+        assert_eq! (lines.last() .map (|s| s.as_str()), Some (r#"=> decided by "lum" : overlay (default effect)"#));
+
+        let lines = explain_rules (&rules, &facts ("chrome.exe", Some (220)), label);
+        assert_eq! (lines.last() .map (|s| s.as_str()), Some ("=> no rule matched : no overlay"));
+    }
+
+}
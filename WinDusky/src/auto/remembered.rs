@@ -0,0 +1,242 @@
+use crate::auto::titles::TitlePattern;
+
+
+
+// Remembered user overrides .. when the user toggles an auto-overlay off, or picks a different effect for it, we can
+// remember that per app identity (exe and window class, and optionally a title pattern), rather than just for that hwnd.
+// These then apply to new windows of the same app too, and get persisted to a state file so they survive restarts.
+//
+// The state file has a line per entry :  exe | class | choice | title-glob  .. where the choice is 'off', or the effect
+// as e.g. 'effect 70% Smart Inversion V3'. (The title is last, and can be left empty to match any title.) Entries made
+// w title-matching use the exact title at the time, in quotes (so any '*' or '?' in it are taken literally), but the
+// file can be hand-edited to widen those into globs (w/o the quotes).
+
+
+
+const SEP : &str = " | ";
+
+const FILE_HEADER : &str = "\
+# WinDusky remembered overlay overrides .. one per line as :  exe | class | off (or effect <intensity>% <name>) | title-glob (or \"exact title\")
+# (these can be forgotten via the tray menu, or edited here while WinDusky isn't running)
+";
+
+
+/// How (and whether) user overrides get remembered beyond the hwnd they were made on
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RememberMode {
+    None,
+    App,
+    // ^^ per exe and window class
+    AppTitle,
+    // ^^ per exe and window class, and the (exact) window title
+}
+
+impl RememberMode {
+    pub fn parse (s: &str) -> Option <RememberMode> {
+        match s.trim() .to_lowercase() .as_str() {
+            "none" | "" => Some (RememberMode::None),
+            "app"       => Some (RememberMode::App),
+            "app+title" => Some (RememberMode::AppTitle),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive (Debug, Clone, PartialEq, Eq)]
+pub enum RememberedChoice {
+    Off,
+    Effect { name: String, intensity: u8 },
+}
+
+
+#[derive (Debug, Clone)]
+pub struct AppIdentity {
+    pub exe   : String,
+    // ^^ stored lowercased
+    pub class : String,
+    pub title : Option <TitlePattern>,
+}
+
+impl AppIdentity {
+
+    /// An identity w an (optional) title spec, as stored .. either a glob, or an exact title in quotes
+    pub fn new (exe: &str, class: &str, title: Option <&str>) -> AppIdentity {
+        let title = title .and_then (|t| match t .strip_prefix ('"') .and_then (|t| t.strip_suffix ('"')) {
+            Some (exact) => TitlePattern::exact (exact, t) .ok(),
+            None => TitlePattern::from_glob (t) .ok(),
+        } );
+        // ^^ (these always compile, as everything but the wildcards is escaped)
+        AppIdentity { exe: exe.to_lowercase(), class: class.to_string(), title }
+    }
+
+    /// An identity that matches just the given (exact) window title
+    pub fn with_exact_title (exe: &str, class: &str, title: &str) -> AppIdentity {
+        Self::new (exe, class, Some (&format! ("\"{title}\"")))
+    }
+
+    pub fn matches (&self, exe: &str, class: &str, title: &str) -> bool {
+        self.exe == exe.to_lowercase()  &&  self.class == class  &&  self.title .as_ref() .is_none_or (|t| t.matches (title))
+    }
+
+    /// A stable string id for the identity (e.g. for tray menu entries)
+    pub fn id (&self) -> String {
+        format! ("{}{SEP}{}{SEP}{}", self.exe, self.class, self.title .as_ref() .map (|t| t.spec.as_str()) .unwrap_or_default())
+    }
+
+    pub fn label (&self) -> String {
+        match self.title.as_ref() {
+            Some (t) => format! ("{} ({}) : {:?}", self.exe, self.class, t.spec),
+            None     => format! ("{} ({})", self.exe, self.class),
+        }
+    }
+}
+
+
+#[derive (Debug, Clone)]
+pub struct RememberedOverride {
+    pub app    : AppIdentity,
+    pub choice : RememberedChoice,
+}
+
+impl RememberedOverride {
+    pub fn label (&self) -> String {
+        match &self.choice {
+            RememberedChoice::Off => format! ("{} : off", self.app.label()),
+            RememberedChoice::Effect { name, intensity: 100 } => format! ("{} : {name}", self.app.label()),
+            RememberedChoice::Effect { name, intensity } => format! ("{} : {name} @ {intensity}%", self.app.label()),
+        }
+    }
+}
+
+
+#[derive (Debug, Clone, Default)]
+pub struct RememberedOverrides {
+    entries : Vec <RememberedOverride>,
+}
+
+impl RememberedOverrides {
+
+    pub fn entries (&self) -> &[RememberedOverride] { &self.entries }
+
+    /// The entry for a window, preferring ones w a title pattern (as the more specific) over those w/o
+    pub fn find (&self, exe: &str, class: &str, title: &str) -> Option <&RememberedOverride> {
+        let mut matching = self.entries .iter() .filter (|e| e.app.matches (exe, class, title));
+        let first = matching.next()?;
+        if first.app.title.is_some() { return Some (first) }
+        Some (matching .find (|e| e.app.title.is_some()) .unwrap_or (first))
+    }
+
+    /// Adds (or replaces) the entry for the app identity
+    pub fn remember (&mut self, app: AppIdentity, choice: RememberedChoice) {
+        let id = app.id();
+        match self.entries .iter_mut() .find (|e| e.app.id() == id) {
+            Some (entry) => entry.choice = choice,
+            None => self.entries .push ( RememberedOverride { app, choice } ),
+        }
+    }
+
+    /// Forgets the entry w the given identity id, returning whether there was one
+    pub fn forget (&mut self, id: &str) -> bool {
+        let n = self.entries.len();
+        self.entries .retain (|e| e.app.id() != id);
+        self.entries.len() != n
+    }
+
+    pub fn serialize (&self) -> String {
+        let mut out = FILE_HEADER.to_string();
+        for e in self.entries.iter() {
+            let choice = match &e.choice {
+                RememberedChoice::Off => "off".to_string(),
+                RememberedChoice::Effect { name, intensity } => format! ("effect {intensity}% {name}"),
+            };
+            let title = e.app.title .as_ref() .map (|t| t.spec.as_str()) .unwrap_or_default();
+            out += &format! ("{}{SEP}{}{SEP}{choice}{SEP}{title}\n", e.app.exe, e.app.class);
+        }
+        out
+    }
+
+    pub fn parse (s: &str) -> Result <RememberedOverrides, String> {
+        let entries = s .lines() .map (str::trim_start) .filter (|l| !l.trim().is_empty() && !l.starts_with ('#')) .map (|line| {
+            let parts : Vec<&str> = line .splitn (4, SEP.trim()) .map (str::trim) .collect();
+            let [exe, class, choice, title] = parts[..] else {
+                return Err (format! ("Malformed remembered override line : {line:.60}"))
+            };
+            let choice = match choice .strip_prefix ("effect ") {
+                None if choice == "off" => RememberedChoice::Off,
+                Some (eff) => {
+                    let (pct, name) = eff .split_once ("% ") .ok_or (format! ("Malformed remembered effect : {eff:?}"))?;
+                    let intensity = pct.trim() .parse::<u8>() .map_err (|e| format! ("Bad remembered effect intensity {pct:?} : {e}"))?;
+                    RememberedChoice::Effect { name: name.trim().to_string(), intensity }
+                }
+                None => return Err (format! ("Unknown remembered override choice {choice:?}")),
+            };
+            let title = (!title.is_empty()) .then_some (title);
+            Ok ( RememberedOverride { app: AppIdentity::new (exe, class, title), choice } )
+        } ) .collect::<Result <Vec<_>, String>>()?;
+        Ok ( RememberedOverrides { entries } )
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect (name: &str, intensity: u8) -> RememberedChoice {
+        RememberedChoice::Effect { name: name.into(), intensity }
+    }
+
+    #[test]
+    fn test_remember_and_find () {
+        let mut rem = RememberedOverrides::default();
+        rem.remember (AppIdentity::new ("Notepad.exe", "Notepad", None), RememberedChoice::Off);
+        rem.remember (AppIdentity::new ("chrome.exe", "Chrome_WidgetWin_1", None), effect ("Smart Inversion V3", 100));
+        rem.remember (AppIdentity::new ("chrome.exe", "Chrome_WidgetWin_1", Some ("*YouTube*")), RememberedChoice::Off);
+
+        assert_eq! (rem.find ("notepad.exe", "Notepad", "x.txt") .map (|e| &e.choice), Some (&RememberedChoice::Off));
+        assert! (rem.find ("notepad.exe", "#32770", "Save As") .is_none());
+        // the title-specific entry wins where it matches (regardless of order), and the app-wide one applies elsewhere
+        assert_eq! (rem.find ("chrome.exe", "Chrome_WidgetWin_1", "Cats - YouTube") .map (|e| &e.choice), Some (&RememberedChoice::Off));
+        assert_eq! (rem.find ("chrome.exe", "Chrome_WidgetWin_1", "News") .map (|e| &e.choice), Some (&effect ("Smart Inversion V3", 100)));
+
+        // re-remembering replaces, and forgetting goes by identity
+        rem.remember (AppIdentity::new ("notepad.exe", "Notepad", None), effect ("Negative Sepia", 70));
+        assert_eq! (rem.entries().len(), 3);
+        assert! (rem.forget (&AppIdentity::new ("chrome.exe", "Chrome_WidgetWin_1", Some ("*YouTube*")).id()));
+        assert! (!rem.forget ("nope"));
+        assert_eq! (rem.find ("chrome.exe", "Chrome_WidgetWin_1", "Cats - YouTube") .map (|e| &e.choice), Some (&effect ("Smart Inversion V3", 100)));
+    }
+
+    #[test]
+    fn test_state_round_trip () {
+        let mut rem = RememberedOverrides::default();
+        rem.remember (AppIdentity::new ("notepad.exe", "Notepad", None), RememberedChoice::Off);
+        rem.remember (AppIdentity::new ("chrome.exe", "Chrome_WidgetWin_1", Some ("Inbox | Mail - *")), effect ("Negative Sepia", 70));
+        rem.remember (AppIdentity::with_exact_title ("code.exe", "Chrome_WidgetWin_1", "*scratch? [draft]"), RememberedChoice::Off);
+
+        let parsed = RememberedOverrides::parse (&rem.serialize()) .unwrap();
+        let summary = |r: &RememberedOverrides| r.entries() .iter() .map (|e| (e.app.id(), e.choice.clone())) .collect::<Vec<_>>();
+        assert_eq! (summary (&parsed), summary (&rem));
+        assert! (parsed.find ("chrome.exe", "Chrome_WidgetWin_1", "Inbox | Mail - Work") .is_some());
+        // exact titles stay exact (their wildcard chars dont act as such)
+        assert! (parsed.find ("code.exe", "Chrome_WidgetWin_1", "*scratch? [draft]") .is_some());
+        assert! (parsed.find ("code.exe", "Chrome_WidgetWin_1", "my scratch! [draft]") .is_none());
+
+        assert! (RememberedOverrides::parse ("notepad.exe | Notepad") .is_err());
+        assert! (RememberedOverrides::parse ("notepad.exe | Notepad | maybe | ") .is_err());
+        assert! (RememberedOverrides::parse ("notepad.exe | Notepad | effect lots% Sepia | ") .is_err());
+        assert! (RememberedOverrides::parse (FILE_HEADER) .unwrap() .entries() .is_empty());
+    }
+
+    #[test]
+    fn test_remember_modes () {
+        assert_eq! (RememberMode::parse ("App+Title"), Some (RememberMode::AppTitle));
+        assert_eq! (RememberMode::parse (""), Some (RememberMode::None));
+        assert_eq! (RememberMode::parse ("always"), None);
+    }
+
+}
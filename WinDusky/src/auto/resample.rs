@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+
+
+// Periodic luminance re-sampling .. windows normally get their luminance sampled just once (when first evaluated), so
+// an app that comes up on a dark splash screen and then turns white (or a viewer that switches documents) would keep the
+// wrong decision. When re-sampling is enabled, windows whose decision hinged on luminance get re-captured periodically,
+// and re-evaluated against the rules w the new luminance.
+//
+// To keep overlays from flickering on windows hovering around the threshold, flips are damped two ways :
+//  - hysteresis : an overlaid window only loses its overlay once its luminance is below the (lower) off threshold ..
+//    generalized for any luminance conditions by requiring the off decision to hold w the luminance nudged by the gap
+//    between the on and off thresholds, in either direction
+//  - a minimum time between flips for any window
+// (A window w/o an overlay turns on at the usual threshold, as that's what it'd have gotten on its first eval anyway)
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Hysteresis {
+    pub gap : u8,
+    // ^^ how far below the on threshold the off threshold is (in luminance units)
+    pub min_flip : Duration,
+    // ^^ the min time between flips of a window's overlay state
+}
+
+impl Hysteresis {
+    pub fn new (on_thresh: u8, off_thresh: u8, min_flip: Duration) -> Hysteresis {
+        Hysteresis { gap: on_thresh .saturating_sub (off_thresh), min_flip }
+    }
+}
+
+
+/// Whether a re-sampled window should flip its overlay state .. the decide fn evals rules at a given luminance, giving
+/// whether the window should have an overlay (or None if the rules would leave it as is)
+pub fn should_flip (
+    hyst: &Hysteresis, overlaid: bool, lum: u8, since_flip: Option <Duration>, decide: impl Fn (u8) -> Option <bool>
+) -> bool {
+    if since_flip .is_some_and (|d| d < hyst.min_flip) { return false }
+
+    if decide (lum) != Some (!overlaid) { return false }
+    if !overlaid { return true }
+
+    // turning off needs the decision to hold even w the luminance nudged by the hysteresis gap (either way)
+    [lum .saturating_sub (hyst.gap), lum .saturating_add (hyst.gap)] .into_iter() .all (|l| decide (l) == Some (false))
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hysteresis () {
+        // like the luminance threshold rule, w 0.7 on and 0.6 off
+        let hyst = Hysteresis::new (178, 153, Duration::from_secs (10));
+        let decide = |lum: u8| Some (lum > 178);
+
+        // turning on happens at the on threshold
+        assert! ( should_flip (&hyst, false, 200, None, decide));
+        assert! (!should_flip (&hyst, false, 170, None, decide));
+
+        // but once on, it takes dropping below the off threshold to turn off
+        assert! (!should_flip (&hyst, true, 170, None, decide));
+        assert! (!should_flip (&hyst, true, 154, None, decide));
+        assert! ( should_flip (&hyst, true, 150, None, decide));
+        assert! (!should_flip (&hyst, true, 200, None, decide));
+
+        // and no flips until the min time since the last one has passed
+        assert! (!should_flip (&hyst, true, 40, Some (Duration::from_secs (3)), decide));
+        assert! ( should_flip (&hyst, true, 40, Some (Duration::from_secs (12)), decide));
+
+        // rules that'd leave the window as is never flip it
+        assert! (!should_flip (&hyst, true, 40, None, |_| None));
+    }
+
+}
@@ -0,0 +1,292 @@
+use std::collections::HashSet;
+
+use crate::auto::titles::TitlePattern;
+
+
+
+// The auto-overlay rules engine .. pure logic over the facts we gather about a window, so it can be tested w/o Windows.
+//
+// Each rule has a condition (exe, class, title, and luminance-range checks, combined w AND/NOT), a priority, and an
+// action. Rules are checked from highest priority down (in config order for equal priorities), and the first one whose
+// condition holds decides. The simpler legacy settings (luminance threshold, title, class and exe rules) get compiled
+// into rules too, at fixed priorities (see LEGACY_PRIORITY__*), so they can be interleaved w the compound ones.
+//
+// Luminance needs a window capture, which is slow-ish, so we only take it when a decision actually hinges on it. For
+// that, conditions evaluate three-way : a luminance check w no luminance measured yet is 'unknown', and if the highest
+// matching candidate is unknown, eval asks for luminance (rather than letting a lower priority rule decide).
+
+
+
+/// Priorities the legacy rule settings get compiled at (compound rules can go above, below, or between these)
+pub const LEGACY_PRIORITY__LUMINANCE : i64 = 400;
+pub const LEGACY_PRIORITY__TITLE     : i64 = 300;
+pub const LEGACY_PRIORITY__CLASS     : i64 = 200;
+pub const LEGACY_PRIORITY__EXE       : i64 = 100;
+
+/// Priority for exclusions 'learned' from a window (above all the legacy settings, so they reliably keep the app clear)
+pub const LEARNED_EXCLUSION_PRIORITY : i64 = 500;
+
+
+/// What we know about a window when evaluating rules
+#[derive (Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowFacts {
+    pub exe       : String,
+    pub class     : String,
+    pub title     : String,
+    pub luminance : Option <u8>,
+    // ^^ None if not measured (yet)
+}
+
+
+#[derive (Debug, Clone)]
+pub enum Condition {
+    Exe   (HashSet <String>),
+    // ^^ matches any of the exes (case-insensitively, so these are stored lowercased)
+    Class (HashSet <String>),
+    // ^^ matches any of the window classes (exactly)
+    Title (TitlePattern),
+    Luminance { min: u8, max: u8 },
+    // ^^ inclusive .. (luminance of exactly 0 or 255 never matches, as that typically means an unpainted window)
+    All (Vec <Condition>),
+    Not (Box <Condition>),
+}
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RuleAction <E> {
+    Overlay (Option <E>),
+    // ^^ w the given effect, or the default effect if none
+    Exclude,
+    // ^^ no overlay (and any auto-applied one gets removed when re-checked)
+    Nothing,
+    // ^^ leaves things as they are .. e.g. to shield some windows from lower priority rules
+}
+
+
+#[derive (Debug, Clone)]
+pub struct Rule <E> {
+    pub name     : String,
+    pub priority : i64,
+    pub when     : Condition,
+    pub action   : RuleAction <E>,
+}
+
+
+#[derive (Debug, Clone)]
+pub enum RulesEval <'a, E> {
+    Decided (&'a Rule <E>),
+    NoMatch,
+    NeedsLuminance,
+}
+
+
+
+impl Condition {
+
+    pub fn exes <S: AsRef<str>> (exes: impl IntoIterator <Item = S>) -> Condition {
+        Condition::Exe (exes .into_iter() .map (|s| s.as_ref() .to_lowercase()) .collect())
+    }
+    pub fn classes <S: AsRef<str>> (classes: impl IntoIterator <Item = S>) -> Condition {
+        Condition::Class (classes .into_iter() .map (|s| s.as_ref() .to_string()) .collect())
+    }
+    pub fn not (cond: Condition) -> Condition {
+        Condition::Not (Box::new (cond))
+    }
+
+    /// Three-way eval .. None if it depends on luminance, which isnt measured yet
+    pub fn eval (&self, facts: &WindowFacts) -> Option <bool> {
+        match self {
+            Condition::Exe (exes)       => Some (exes.contains (&facts.exe.to_lowercase())),
+            Condition::Class (classes)  => Some (classes.contains (&facts.class)),
+            Condition::Title (pattern)  => Some (pattern.matches (&facts.title)),
+            Condition::Luminance { min, max } => {
+                facts.luminance .map (|lum| lum != u8::MIN && lum != u8::MAX && lum >= *min && lum <= *max)
+            }
+            Condition::All (conds) => {
+                let mut result = Some (true);
+                for c in conds {
+                    match c.eval (facts) {
+                        Some (false) => return Some (false),
+                        // ^^ a definite false decides it, even if other parts are unknown
+                        None => result = None,
+                        Some (true) => { }
+                    }
+                }
+                result
+            }
+            Condition::Not (c) => c.eval (facts) .map (|b| !b),
+        }
+    }
+
+    pub fn uses_title (&self) -> bool {
+        match self {
+            Condition::Title (_) => true,
+            Condition::All (conds) => conds .iter() .any (Condition::uses_title),
+            Condition::Not (c) => c.uses_title(),
+            _ => false,
+        }
+    }
+}
+
+
+
+impl std::fmt::Display for Condition {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn sorted (set: &HashSet<String>) -> Vec <&String> { let mut v : Vec<&String> = set.iter().collect(); v.sort(); v }
+        match self {
+            Condition::Exe (exes)       => write! (f, "exe in {:?}", sorted (exes)),
+            Condition::Class (classes)  => write! (f, "class in {:?}", sorted (classes)),
+            Condition::Title (pattern)  => write! (f, "title ~ {:?}", pattern.spec),
+            Condition::Luminance { min, max } => write! (f, "luminance in {min}..={max}"),
+            Condition::All (conds) if conds.is_empty() => write! (f, "always"),
+            Condition::All (conds) => {
+                for (i, c) in conds.iter().enumerate() {
+                    if i > 0 { write! (f, " AND ")? }
+                    if let Condition::All (_) = c { write! (f, "({c})")? } else { write! (f, "{c}")? }
+                }
+                Ok(())
+            }
+            Condition::Not (c) => write! (f, "NOT ({c})"),
+        }
+    }
+}
+
+
+
+#[derive (Debug, Clone)]
+pub struct RuleSet <E> {
+    rules : Vec <Rule <E>>,
+    // ^^ kept sorted by descending priority (w config order kept for ties)
+}
+
+impl <E> Default for RuleSet <E> {
+    fn default() -> Self { RuleSet { rules: vec![] } }
+}
+
+impl <E> RuleSet <E> {
+
+    pub fn new (mut rules: Vec <Rule <E>>) -> RuleSet <E> {
+        rules .sort_by_key (|r| std::cmp::Reverse (r.priority));
+        // ^^ (sort is stable, so equal priorities stay in given order)
+        RuleSet { rules }
+    }
+
+    pub fn rules (&self) -> &[Rule <E>] { &self.rules }
+
+    pub fn is_empty (&self) -> bool { self.rules.is_empty() }
+
+    pub fn uses_title (&self) -> bool {
+        self.rules .iter() .any (|r| r.when.uses_title())
+    }
+
+    /// Finds the highest priority rule whose condition holds for the given facts
+    pub fn eval (&self, facts: &WindowFacts) -> RulesEval <'_, E> {
+        for rule in self.rules.iter() {
+            match rule.when.eval (facts) {
+                Some (true)  => return RulesEval::Decided (rule),
+                Some (false) => { }
+                None => return RulesEval::NeedsLuminance,
+                // ^^ if this one might match, lower priority rules cant decide yet
+            }
+        }
+        RulesEval::NoMatch
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts (exe: &str, class: &str, title: &str, luminance: Option <u8>) -> WindowFacts {
+        WindowFacts { exe: exe.into(), class: class.into(), title: title.into(), luminance }
+    }
+    fn rule (name: &str, priority: i64, when: Condition, action: RuleAction <u32>) -> Rule <u32> {
+        Rule { name: name.into(), priority, when, action }
+    }
+    fn decided <'a> (eval: RulesEval <'a, u32>) -> Option <&'a str> {
+        match eval { RulesEval::Decided (r) => Some (&r.name), _ => None }
+    }
+    fn title (glob: &str) -> Condition {
+        Condition::Title (TitlePattern::from_glob (glob) .unwrap())
+    }
+
+    #[test]
+    fn test_conditions () {
+        let f = facts ("Chrome.exe", "Chrome_WidgetWin_1", "Docs - Google Chrome", Some (200));
+        assert_eq! (Condition::exes (["chrome.exe", "firefox.exe"]) .eval (&f), Some (true));
+        assert_eq! (Condition::classes (["chrome_widgetwin_1"]) .eval (&f), Some (false));
+        assert_eq! (Condition::Luminance { min: 150, max: 254 } .eval (&f), Some (true));
+        assert_eq! (Condition::Luminance { min: 0, max: 255 } .eval (&facts ("", "", "", Some (255))), Some (false));
+
+        let cond = Condition::All (vec! [ Condition::exes (["chrome.exe"]), Condition::not (title ("*YouTube*")) ]);
+        assert_eq! (cond.eval (&f), Some (true));
+        assert_eq! (cond.eval (&facts ("chrome.exe", "", "Cats - YouTube", None)), Some (false));
+
+        // luminance checks are unknown until measured, unless something else already decides
+        let cond = Condition::All (vec! [ Condition::exes (["chrome.exe"]), Condition::Luminance { min: 150, max: 254 } ]);
+        assert_eq! (cond.eval (&facts ("chrome.exe", "", "", None)), None);
+        assert_eq! (cond.eval (&facts ("notepad.exe", "", "", None)), Some (false));
+        assert_eq! (Condition::not (cond.clone()) .eval (&facts ("chrome.exe", "", "", None)), None);
+        assert! (!cond.uses_title());
+
+        let cond = Condition::All (vec! [ Condition::exes (["b.exe", "A.exe"]), Condition::not (title ("*Docs*")) ]);
+        assert_eq! (cond.to_string(), r#"exe in ["a.exe", "b.exe"] AND NOT (title ~ "*Docs*")"#);
+    }
+
+    #[test]
+    fn test_priorities_and_actions () {
+        let rules = RuleSet::new (vec! [
+            rule ("exe",      LEGACY_PRIORITY__EXE,   Condition::exes (["mmc.exe", "chrome.exe"]), RuleAction::Overlay (None)),
+            rule ("no-games", 900,                    Condition::exes (["game.exe"]),              RuleAction::Exclude),
+            rule ("docs",     LEGACY_PRIORITY__TITLE, title ("*Google Docs*"),                     RuleAction::Overlay (Some (7))),
+            rule ("shield",   LEGACY_PRIORITY__TITLE, title ("*Docs*"),                            RuleAction::Nothing),
+            rule ("dialogs",  LEGACY_PRIORITY__CLASS, Condition::classes (["#32770"]),             RuleAction::Overlay (None)),
+        ] );
+        assert_eq! (rules.rules() .iter() .map (|r| r.name.as_str()) .collect::<Vec<_>>(), ["no-games", "docs", "shield", "dialogs", "exe"]);
+
+        assert_eq! (decided (rules.eval (&facts ("chrome.exe", "x", "Report - Google Docs", None))), Some ("docs"));
+        // ^^ equal priorities go in the given order
+        assert_eq! (decided (rules.eval (&facts ("chrome.exe", "x", "My Docs", None))), Some ("shield"));
+        assert_eq! (decided (rules.eval (&facts ("chrome.exe", "x", "News", None))), Some ("exe"));
+        assert_eq! (decided (rules.eval (&facts ("game.exe", "#32770", "Google Docs", None))), Some ("no-games"));
+        assert! (matches! (rules.eval (&facts ("notepad.exe", "x", "", None)), RulesEval::NoMatch));
+    }
+
+    #[test]
+    fn test_legacy_title_rules () {
+        // title rules from the simpler confs, as load_rules compiles them .. optionally gated on exes, and first match wins
+        let title_rule = |name: &str, glob: &str, exes: &[&str]| {
+            let when = if exes.is_empty() { title (glob) } else { Condition::All (vec! [ Condition::exes (exes), title (glob) ]) };
+            rule (name, LEGACY_PRIORITY__TITLE, when, RuleAction::Overlay (None))
+        };
+        let rules = RuleSet::new (vec! [
+            title_rule ("chrome docs", "*Docs*", &["chrome.exe"]),  title_rule ("docs", "*Docs*", &[]),  title_rule ("wiki", "*Wiki*", &[]),
+        ] );
+        assert_eq! (decided (rules.eval (&facts ("chrome.exe", "x", "My Docs - Chrome", None))), Some ("chrome docs"));
+        assert_eq! (decided (rules.eval (&facts ("msedge.exe", "x", "My Docs - Edge", None))), Some ("docs"));
+        assert_eq! (decided (rules.eval (&facts ("msedge.exe", "x", "Wiki", None))), Some ("wiki"));
+        assert! (matches! (rules.eval (&facts ("chrome.exe", "x", "News", None)), RulesEval::NoMatch));
+    }
+
+    #[test]
+    fn test_luminance_only_when_needed () {
+        let lum = Condition::All (vec! [ Condition::not (Condition::exes (["chrome.exe"])), Condition::Luminance { min: 179, max: 254 } ]);
+        let rules = RuleSet::new (vec! [
+            rule ("exe", LEGACY_PRIORITY__EXE,       Condition::exes (["notepad.exe", "chrome.exe"]), RuleAction::Overlay (None)),
+            rule ("lum", LEGACY_PRIORITY__LUMINANCE, lum, RuleAction::Overlay (None)),
+            rule ("top", 999,                        Condition::classes (["Top"]), RuleAction::Exclude),
+        ] );
+        // a higher priority rule decides w/o needing luminance, as does the luminance rule being ruled out otherwise
+        assert_eq! (decided (rules.eval (&facts ("notepad.exe", "Top", "", None))), Some ("top"));
+        assert_eq! (decided (rules.eval (&facts ("chrome.exe", "x", "", None))), Some ("exe"));
+        // but otherwise we need it measured before lower priority rules can decide
+        assert! (matches! (rules.eval (&facts ("notepad.exe", "x", "", None)), RulesEval::NeedsLuminance));
+        assert_eq! (decided (rules.eval (&facts ("notepad.exe", "x", "", Some (220)))), Some ("lum"));
+        assert_eq! (decided (rules.eval (&facts ("notepad.exe", "x", "", Some (40)))), Some ("exe"));
+    }
+
+}
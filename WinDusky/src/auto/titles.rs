@@ -0,0 +1,91 @@
+use regex::{Regex, RegexBuilder};
+
+
+
+// Window-title patterns for auto-overlay rules .. either globs (where '*' matches anything and '?' any one char), or
+// full regexes. Globs match the whole title, and regexes anywhere in it (unless anchored), both case-insensitively.
+// These are mainly for browsers, where one window can show both light and dark pages, and the title (which follows
+// the active tab) is the best cheap hint we get of what's showing .. so title rules get re-checked on title changes.
+
+
+
+#[derive (Debug, Clone)]
+pub struct TitlePattern {
+    pub spec  : String,
+    // ^^ as specified in the config (for logging)
+    regex : Regex,
+}
+
+impl TitlePattern {
+
+    pub fn from_glob (glob: &str) -> Result <TitlePattern, String> {
+        Self::build (glob, &glob_to_regex (glob))
+    }
+
+    pub fn from_regex (re: &str) -> Result <TitlePattern, String> {
+        Self::build (re, re)
+    }
+
+    /// Matches just the given title (case-insensitively, like globs) .. w wildcard chars in it taken literally
+    pub fn exact (title: &str, spec: &str) -> Result <TitlePattern, String> {
+        Self::build (spec, &format! ("^{}$", regex::escape (title)))
+    }
+
+    fn build (spec: &str, re: &str) -> Result <TitlePattern, String> {
+        let regex = RegexBuilder::new (re) .case_insensitive (true) .build()
+            .map_err (|e| format! ("Invalid window title pattern {spec:?} : {e}"))?;
+        Ok ( TitlePattern { spec: spec.to_string(), regex } )
+    }
+
+    pub fn matches (&self, title: &str) -> bool {
+        self.regex.is_match (title)
+    }
+}
+
+
+/// Converts a glob to an (anchored) regex .. '*' matches any run of chars, '?' any single char, and all else is literal
+pub fn glob_to_regex (glob: &str) -> String {
+    let mut re = String::from ("^");
+    for ch in glob.chars() {
+        match ch {
+            '*' => re += ".*",
+            '?' => re += ".",
+            _   => re += &regex::escape (ch.encode_utf8 (&mut [0; 4])),
+        }
+    }
+    re += "$";
+    re
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globs () {
+        let p = TitlePattern::from_glob ("* - Google Docs - *") .unwrap();
+        assert! (p.matches ("Budget 2025 - Google Docs - Google Chrome"));
+        assert! (p.matches ("budget - google docs - Firefox"));
+        // ^^ case-insensitive
+        assert! (!p.matches ("Google Docs"));
+        assert! (!p.matches ("Budget - Google Sheets - Google Chrome"));
+
+        // regex-special chars in globs are literal, and '?' is any one char
+        let p = TitlePattern::from_glob ("(1?) Inbox*") .unwrap();
+        assert! (p.matches ("(12) Inbox - mail") && !p.matches ("(123) Inbox") && !p.matches ("12 Inbox"));
+    }
+
+    #[test]
+    fn test_regexes () {
+        let p = TitlePattern::from_regex (r"GitHub|Stack Overflow") .unwrap();
+        assert! (p.matches ("Questions - Stack Overflow - Mozilla Firefox"));
+        assert! (p.matches ("yakrider/WinDusky · github"));
+        assert! (!p.matches ("YouTube"));
+        assert! (TitlePattern::from_regex ("(unclosed") .is_err());
+    }
+
+}
@@ -0,0 +1,65 @@
+// A standalone build of the 'preview' cli command .. it only pulls in the (platform independent) effects code, so unlike
+// the main WinDusky binary, it builds and runs anywhere (e.g. on linux CI, to render previews of effect changes for review)
+// e.g. :  cargo run --bin dusky-preview -- screenshot.png --conf WinDusky.conf.toml --effect "Smart Inversion V3"
+
+#![allow (dead_code)]
+// ^^ only part of the effects code gets used here
+
+use toml_edit::DocumentMut;
+
+
+#[path = "../effects/compose.rs"] mod compose;
+#[path = "../effects/cvd.rs"]     mod cvd;
+#[path = "../effects/fit.rs"]     mod fit;
+#[path = "../effects/matrix.rs"]  mod matrix;
+#[path = "../effects/preview.rs"] mod preview;
+#[path = "../effects/spec.rs"]    mod spec;
+
+mod effects {
+    // ^^ mirrors the crate::effects paths of the main binary, so the modules above can be shared as is
+    pub(crate) use super::{compose, cvd, fit, matrix, spec};
+}
+
+#[path = "../gamma/blackbody.rs"] mod blackbody;
+#[path = "../gamma/ramp.rs"]      mod ramp;
+
+mod gamma {
+    // ^^ likewise for the (pure) gamma ramp code, which the ramp fit in effects::fit is checked against
+    #[allow (unused_imports)]
+    pub(crate) use super::{blackbody, ramp};
+    // ^^ (ramp only gets used from the fit tests)
+}
+
+
+
+const USAGE : &str = "\
+Usage: dusky-preview <image> [options]
+
+  Render an image through the color-effects defined in a WinDusky conf file, and save the results as PNGs
+      -e, --effect <name>             Effect to render (can be repeated) .. renders all defined effects if not specified
+      -o, --out <path>                Output file (for the contact sheet), or directory (with --individual)
+      -i, --individual                Write one PNG per effect instead of a single contact sheet
+      -c, --conf <conf-file>          The conf file to load effects from (defaults to WinDusky.conf.toml in the current dir)
+";
+
+
+fn main() {
+    let args : Vec<String> = std::env::args() .skip(1) .collect();
+    if args.is_empty() || args .iter() .any (|a| a == "-h" || a == "--help") {
+        print! ("{USAGE}");
+        std::process::exit (if args.is_empty() { 2 } else { 0 });
+    }
+    let pargs = match preview::parse_preview_args (&args) {
+        Ok (pargs) => pargs,
+        Err (e) => { eprint! ("{e}\n\n{USAGE}"); std::process::exit (2) }
+    };
+    let conf_path = pargs.conf.clone() .unwrap_or ("WinDusky.conf.toml".into());
+    let doc = match std::fs::read_to_string (&conf_path) .map_err (|e| e.to_string()) .and_then (|s| s.parse::<DocumentMut>() .map_err (|e| e.to_string())) {
+        Ok (doc) => doc,
+        Err (e) => { eprintln! ("Failed to load conf file {conf_path:?} : {e}"); std::process::exit (2) }
+    };
+    let (specs, malformed) = spec::parse_color_effects (&doc);
+    malformed .iter() .for_each (|name| eprintln! ("Ignoring malformed [[effects]] entry {name:?}"));
+
+    std::process::exit (preview::run_preview (pargs, &specs));
+}
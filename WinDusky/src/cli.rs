@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+use crate::auto::explain;
+use crate::auto::rules::WindowFacts;
+use crate::auto::AutoOverlay;
+use crate::config::Config;
+use crate::effects::lint::{self, LintLevel};
+use crate::effects::preview;
+use crate::effects::ColorEffects;
+use crate::gamma::{self, calfile, GammaRamp, SCREEN_DC_KEY};
+use crate::presets::PRESET_NORMAL;
+
+
+
+// Command-line entry points .. these run headless (no hotkeys, hooks, overlays or tray) and exit once done
+// e.g. :  WinDusky check-effects [path\to\WinDusky.conf.toml]
+//         WinDusky preview screenshot.png --effect "Smart Inversion V3"
+//         WinDusky export-gamma-ramp dark.cal --preset Dark
+//         WinDusky explain-rules --exe chrome.exe --title "Inbox - Gmail" --luminance 0.8
+
+
+
+const USAGE : &str = "\
+Usage: WinDusky [command]
+
+  (no command)                      Start WinDusky normally
+  check-effects [conf-file]         Check the color-effects defined in the config (or the given conf file) for problems
+  preview <image> [options]         Render an image through configured color-effects, and save the results as PNGs
+      -e, --effect <name>             Effect to render (can be repeated) .. renders all defined effects if not specified
+      -o, --out <path>                Output file (for the contact sheet), or directory (with --individual)
+      -i, --individual                Write one PNG per effect instead of a single contact sheet
+      -c, --conf <conf-file>          Use the given conf file instead of the active config
+  export-gamma-ramp <file> [options]
+                                    Save a gamma ramp as an ArgyllCMS .cal file (if the file ends in .cal), or else as CSV
+      -p, --preset <name>             Export the ramp computed for a gamma preset (layered on any configured base ramp),
+                                        instead of the ramp currently set
+      -r, --raw                       With --preset, export the computed ramp w/o layering it on the base ramp
+      -m, --monitor <device>          Export the current ramp of the given monitor (e.g. \\\\.\\DISPLAY1), instead of the screen's
+      -c, --conf <conf-file>          Use the given conf file instead of the active config
+  explain-rules [options]           Explain how the configured auto-overlay rules decide for a window w the given details
+      -e, --exe <exe>                 The window's exe (e.g. chrome.exe)
+      -k, --class <class>             The window's class (e.g. Chrome_WidgetWin_1)
+      -t, --title <title>             The window's title
+      -l, --luminance <0.0 to 1.0>    The window's luminance .. if not given, luminance based rules are left undecided
+      -c, --conf <conf-file>          Use the given conf file instead of the active config
+  help                              Print this message
+";
+
+
+
+/// Runs the cli command if one was specified, returning the process exit code (or None if we should start up normally)
+pub fn run_cli_if_requested () -> Option <i32> {
+
+    let args : Vec<String> = std::env::args() .skip(1) .collect();
+    let cmd = args.first()?;
+
+    // we're built as a windows-subsystem app (no console), so we'll attach to the console we were launched from (if any)
+    unsafe { let _ = AttachConsole (ATTACH_PARENT_PROCESS); }
+
+    let code = match cmd.as_str() {
+        "check-effects"          => check_effects (args.get(1)),
+        "preview"                => preview (&args[1..]),
+        "export-gamma-ramp"      => export_gamma_ramp (&args[1..]),
+        "explain-rules"          => explain_rules (&args[1..]),
+        "help" | "--help" | "-h" => { print! ("{USAGE}"); 0 }
+        _ => { eprint! ("Unknown command {cmd:?}\n\n{USAGE}"); 2 }
+    };
+    Some (code)
+}
+
+
+
+/// Loads the config either from the given path or from the usual conf location
+fn load_conf (path: Option<&String>) -> Result <&'static Config, String> {
+    match path {
+        Some (p) => Config::from_file (&PathBuf::from(p)) .map (|c| &*Box::leak (Box::new (c))),
+        None     => Ok (Config::instance()),
+    }
+}
+
+
+
+fn check_effects (path: Option<&String>) -> i32 {
+
+    let conf = match load_conf (path) {
+        Ok (conf) => conf,
+        Err (e) => { eprintln! ("{e}"); return 2 }
+    };
+    let (specs, malformed) = conf.get_color_effects();
+    let issues = lint::lint_effects (&specs, &malformed, &conf.get_effects_cycle_order());
+
+    println! ("Checked {} color-effect definitions :", specs.len() + malformed.len());
+    for issue in issues.iter() {
+        println! ("  {issue}");
+    }
+    let n_problems = issues .iter() .filter (|i| i.level > LintLevel::Note) .count();
+    println! ("{} problem(s), {} note(s)", n_problems, issues.len() - n_problems);
+
+    if n_problems > 0 { 1 } else { 0 }
+}
+
+
+
+fn preview (args: &[String]) -> i32 {
+
+    let pargs = match preview::parse_preview_args (args) {
+        Ok (pargs) => pargs,
+        Err (e) => { eprint! ("{e}\n\n{USAGE}"); return 2 }
+    };
+    let conf = match load_conf (pargs.conf.as_ref()) {
+        Ok (conf) => conf,
+        Err (e) => { eprintln! ("{e}"); return 2 }
+    };
+    preview::run_preview (pargs, &conf.get_color_effects().0)
+}
+
+
+
+#[derive (Debug, Default)]
+struct ExportRampArgs {
+    out     : PathBuf,
+    preset  : Option <String>,
+    raw     : bool,
+    monitor : Option <String>,
+    conf    : Option <String>,
+}
+
+fn parse_export_ramp_args (args: &[String]) -> Result <ExportRampArgs, String> {
+    let mut eargs = ExportRampArgs::default();
+    let mut out = None;
+    let mut iter = args.iter();
+    while let Some (arg) = iter.next() {
+        let mut value = || iter.next() .cloned() .ok_or (format! ("Missing value for {arg:?}"));
+        match arg.as_str() {
+            "-p" | "--preset"  => eargs.preset = Some (value()?),
+            "-m" | "--monitor" => eargs.monitor = Some (value()?),
+            "-c" | "--conf"    => eargs.conf = Some (value()?),
+            "-r" | "--raw"     => eargs.raw = true,
+            _ if arg.starts_with('-') => return Err (format! ("Unknown export-gamma-ramp option {arg:?}")),
+            _ if out.is_none() => out = Some (PathBuf::from (arg)),
+            _ => return Err (format! ("Unexpected export-gamma-ramp argument {arg:?}")),
+        }
+    }
+    eargs.out = out .ok_or ("No output file specified for export-gamma-ramp")?;
+    if eargs.preset.is_some() && eargs.monitor.is_some() { return Err ("Use either --preset or --monitor, not both".into()) }
+    if eargs.raw && eargs.preset.is_none() { return Err ("--raw only applies along with --preset".into()) }
+    Ok (eargs)
+}
+
+
+fn export_gamma_ramp (args: &[String]) -> i32 {
+
+    let eargs = match parse_export_ramp_args (args) {
+        Ok (eargs) => eargs,
+        Err (e) => { eprint! ("{e}\n\n{USAGE}"); return 2 }
+    };
+    let ramp : GammaRamp = if let Some (name) = eargs.preset.as_ref() {
+        let conf = match load_conf (eargs.conf.as_ref()) {
+            Ok (conf) => conf,
+            Err (e) => { eprintln! ("{e}"); return 2 }
+        };
+        let presets = conf.get_gamma_presets();
+        let Some (spec) = presets .iter() .chain ([&*PRESET_NORMAL]) .find (|p| p.preset == *name) else {
+            eprintln! ("Gamma preset {name:?} is not defined .. available presets are :");
+            presets .iter() .for_each (|p| eprintln! ("  {}", p.preset));
+            return 2
+        };
+        if eargs.raw {
+            gamma::calc_gbct_ramp (&spec.gbc, spec.color_temp, spec.tint)
+        } else {
+            if let Err (e) = gamma::load_base_ramp (conf.get_gamma_base_ramp_file().as_deref()) {
+                eprintln! ("{e}");
+                return 1
+            }
+            gamma::calc_layered_ramp (&spec.gbc, spec.color_temp, spec.tint)
+        }
+    } else {
+        let key = eargs.monitor.as_deref() .unwrap_or (SCREEN_DC_KEY);
+        match gamma::get_ramp_for_key (key) {
+            Some (ramp) => ramp,
+            None => { eprintln! ("Failed to get the current gamma ramp for {:?}", eargs.monitor.as_deref() .unwrap_or ("the screen")); return 1 }
+        }
+    };
+
+    let out = if calfile::is_cal_file (&eargs.out) { calfile::serialize_cal (&ramp) } else { calfile::serialize_csv (&ramp) };
+    if let Err (e) = std::fs::write (&eargs.out, out) {
+        eprintln! ("Failed to write {:?} : {e}", eargs.out);
+        return 1
+    }
+    println! ("Wrote gamma ramp to {:?}", eargs.out);
+    0
+}
+
+
+
+#[derive (Debug, Default)]
+struct ExplainArgs {
+    facts : WindowFacts,
+    conf  : Option <String>,
+}
+
+fn parse_explain_args (args: &[String]) -> Result <ExplainArgs, String> {
+    let mut xargs = ExplainArgs::default();
+    let mut iter = args.iter();
+    while let Some (arg) = iter.next() {
+        let mut value = || iter.next() .cloned() .ok_or (format! ("Missing value for {arg:?}"));
+        match arg.as_str() {
+            "-e" | "--exe"   => xargs.facts.exe = value()?,
+            "-k" | "--class" => xargs.facts.class = value()?,
+            "-t" | "--title" => xargs.facts.title = value()?,
+            "-c" | "--conf"  => xargs.conf = Some (value()?),
+            "-l" | "--luminance" => {
+                let v = value()?;
+                let lum = v.parse::<f32>() .ok() .filter (|l| (0.0..=1.0).contains (l)) .ok_or (format! ("Luminance must be 0.0 to 1.0, got {v:?}"))?;
+                xargs.facts.luminance = Some ((u8::MAX as f32 * lum) as u8);
+                // ^^ (same conversion as for luminance values in the confs)
+            }
+            _ => return Err (format! ("Unexpected explain-rules argument {arg:?}")),
+        }
+    }
+    if xargs.facts.exe.is_empty() && xargs.facts.class.is_empty() && xargs.facts.title.is_empty() {
+        return Err ("Specify at least one of --exe, --class or --title for explain-rules".into())
+    }
+    Ok (xargs)
+}
+
+
+fn explain_rules (args: &[String]) -> i32 {
+
+    let xargs = match parse_explain_args (args) {
+        Ok (xargs) => xargs,
+        Err (e) => { eprint! ("{e}\n\n{USAGE}"); return 2 }
+    };
+    let conf = match load_conf (xargs.conf.as_ref()) {
+        Ok (conf) => conf,
+        Err (e) => { eprintln! ("{e}"); return 2 }
+    };
+    let effects = ColorEffects::init (conf);
+    let rules = AutoOverlay::compile_rules (conf, effects);
+
+    for line in explain::explain_rules (&rules, &xargs.facts, |e| e.label()) {
+        println! ("{line}");
+    }
+    0
+}
@@ -4,6 +4,7 @@ use std::ops::Not;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
 use std::{fs, io};
 
 use tracing::metadata::LevelFilter;
@@ -18,8 +19,11 @@ use tracing_subscriber::{reload, Layer, Registry};
 
 use windows::Win32::UI::Input::KeyboardAndMouse::HOT_KEY_MODIFIERS;
 
-use toml_edit::{DocumentMut, Item, Table, Value};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Value};
 
+use crate::auto::remembered::RememberMode;
+use crate::auto::rules::LEARNED_EXCLUSION_PRIORITY;
+use crate::effects::spec::{parse_color_effects, ColorEffectSpec};
 use crate::gamma;
 use crate::keys::VKey;
 
@@ -30,6 +34,8 @@ pub struct Config {
     pub toml     : RwLock <Option <DocumentMut>>,
     pub default  : DocumentMut,
     pub loglevel : RwLock <Option <Handle <LevelFilter, Registry>>>,
+    pub file     : Option <PathBuf>,
+    // ^^ the conf file this was loaded from, if given explicitly (e.g. via --conf for cli commands)
 }
 
 
@@ -61,18 +67,87 @@ pub struct AutoOverlayClass {
 }
 
 
-#[derive (Debug)]
-pub struct ColorEffectSpec {
-    pub name : String,
-    pub transform : [f32; 25],
+#[derive (Debug, Default)]
+pub struct AutoOverlayCondSpec {
+    pub exes      : Vec<String>,
+    pub classes   : Vec<String>,
+    pub title     : Option <(String, bool)>,
+    // ^^ the pattern, and whether its a regex (else a glob)
+    pub luminance : Option <(f32, f32)>,
+    // ^^ (min, max) in 0..1
+    pub not       : Vec <AutoOverlayCondSpec>,
+    // ^^ each of these must NOT hold (while all the other specified conditions must)
 }
 
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AutoOverlayRuleAction { Overlay, Exclude, Nothing }
 
+/// What a rule 'learned' from a window keys on .. its exe, or its window class
 #[derive (Debug, Clone)]
+pub enum LearnedRuleTarget { Exe (String), Class (String) }
+
+#[derive (Debug)]
+pub struct AutoOverlayRule {
+    pub name     : String,
+    pub priority : i64,
+    pub when     : AutoOverlayCondSpec,
+    pub action   : AutoOverlayRuleAction,
+    pub effect   : Option<String>,
+}
+
+
+#[derive (Debug)]
+pub struct AutoOverlayTitle {
+    pub title : String,
+    pub is_regex : bool,
+    // ^^ title patterns are globs by default, or regexes if specified via 'title_regex'
+    pub effect : Option<String>,
+    pub exes : Vec<String>,
+}
+
+
+#[derive (Debug, Clone, PartialEq)]
 pub struct GammaPresetSpec {
     pub preset : String,
     pub gbc : gamma::GBC,
     pub color_temp : u32,
+    pub tint : f32,
+    // ^^ Duv offset from the blackbody locus (+ve towards green, -ve towards magenta)
+}
+
+
+#[derive (Debug, Clone)]
+pub struct GammaScheduleSpec {
+    pub schedule     : gamma::schedule::SolarSchedule,
+    pub day_preset   : String,
+    pub night_preset : String,
+}
+
+
+/// A monitor can be referred to either by its (1-based) enumeration index, or its device name (e.g. \\.\DISPLAY2)
+#[derive (Debug, Clone, PartialEq)]
+pub enum MonitorId {
+    Index (usize),
+    Device (String),
+}
+
+impl MonitorId {
+    pub fn matches (&self, index: usize, device_name: &str) -> bool {
+        match self {
+            MonitorId::Index (i)  => *i == index,
+            MonitorId::Device (d) => {
+                // we'll allow leaving out the '\\.\' prefix (i.e. just 'DISPLAY2'), and ignore case
+                let strip = |s: &str| s.trim_start_matches (['\\', '.']) .to_ascii_uppercase();
+                strip (d) == strip (device_name)
+            }
+        }
+    }
+}
+
+#[derive (Debug, Clone)]
+pub struct MonitorPresetSpec {
+    pub monitor : MonitorId,
+    pub preset  : String,
 }
 
 
@@ -110,13 +185,29 @@ impl Config {
                 default : DocumentMut::from_str (include_str!("../WinDusky.conf.toml")).unwrap(),
                 // ^^ our switche.conf.toml is at root of project, the include_str macro will load the contents at compile time
                 loglevel : RwLock::new (None),
+                file     : None,
             };
             conf.load();
             conf
         } )
     }
 
-    pub const CONF_FILE_NAME  : &'static str = "WinDusky.conf.toml";
+    pub const CONF_FILE_NAME          : &'static str = "WinDusky.conf.toml";
+    pub const GAMMA_RESTORE_FILE_NAME : &'static str = "WinDusky.gamma-restore.txt";
+    pub const OVERRIDES_FILE_NAME     : &'static str = "WinDusky.overrides.txt";
+
+
+    /// Creates a standalone config from a given toml file, w/o touching the active conf location (e.g. for cli use)
+    pub fn from_file (path: &Path) -> Result <Config, String> {
+        let cfg_str = fs::read_to_string (path) .map_err (|e| format! ("Failed to read {path:?} : {e}"))?;
+        let toml = DocumentMut::from_str (&cfg_str) .map_err (|e| format! ("Failed to parse {path:?} : {e}"))?;
+        Ok ( Config {
+            toml     : RwLock::new (Some (toml)),
+            default  : DocumentMut::from_str (include_str!("../WinDusky.conf.toml")).unwrap(),
+            loglevel : RwLock::new (None),
+            file     : Some (path.to_path_buf()),
+        } )
+    }
 
 
     fn get_config_file (&self) -> Option<PathBuf> {
@@ -139,6 +230,14 @@ impl Config {
         }
         None
     }
+    /// The original gamma ramps get persisted next to the config (see gamma::restore)
+    pub fn get_gamma_restore_file (&self) -> Option<PathBuf> {
+        self.get_config_file() .and_then (|p| p.parent() .map (|d| d.join (Self::GAMMA_RESTORE_FILE_NAME)))
+    }
+    /// Remembered per-app user overrides also get persisted next to the config (see auto::remembered)
+    pub fn get_overrides_state_file (&self) -> Option<PathBuf> {
+        self.get_config_file() .and_then (|p| p.parent() .map (|d| d.join (Self::OVERRIDES_FILE_NAME)))
+    }
     pub fn get_log_loc (&self) -> Option<PathBuf> {
         if let Some (conf_path) = self.get_config_file() {
             if let Some (conf_loc) = conf_path.parent() {
@@ -155,7 +254,7 @@ impl Config {
     }
     pub fn trigger_config_file_reset (&self) {
         self.toml.write().unwrap() .replace (self.default.clone());
-        self.write_back_toml();
+        let _ = self.write_back_toml();
     }
 
 
@@ -230,14 +329,14 @@ impl Config {
     }
 
 
-    fn write_back_toml (&self) {
+    fn write_back_toml (&self) -> Result <(), String> {
         //debug! ("write_back_toml");
         let conf_path = self.get_config_file();
-        if conf_path.is_none() { return }
-        let _ = fs::write (
+        if conf_path.is_none() { return Ok(()) }
+        fs::write (
             conf_path.as_ref().unwrap(),
             self.toml.read().unwrap().as_ref() .map (|d| d.to_string()).unwrap_or_default()
-        );
+        ) .map_err (|e| format! ("couldn't write config file {:?} : {e}", conf_path.unwrap()))
     }
     #[allow (dead_code)]
     fn write_back_toml_if_changed (&'static self) {
@@ -262,8 +361,12 @@ impl Config {
     fn get_float (&self, key:&str) -> f32 {
         self.toml.read().unwrap().as_ref()
             .and_then (|t| t.get(key))
-            .and_then (|t| t.as_float().map(|n| n as f32))
-            .unwrap_or ( self.default.get(key) .and_then (|t| t.as_float().map(|n| n as f32)) .unwrap_or_default() )
+            .and_then (Self::as_float_or_int)
+            .unwrap_or ( self.default.get(key) .and_then (Self::as_float_or_int) .unwrap_or_default() )
+    }
+    fn as_float_or_int (item: &Item) -> Option<f32> {
+        // ^^ so that whole-number values like 'delay_ms = 100' dont silently fall back to defaults
+        item.as_float() .or_else (|| item.as_integer() .map (|i| i as f64)) .map (|n| n as f32)
     }
 
     fn get_string (&self, key:&str) -> String {
@@ -344,13 +447,32 @@ impl Config {
     pub fn get_hotkey__next_effect  (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__next_effect") }
     pub fn get_hotkey__prev_effect  (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__prev_effect") }
 
+    pub fn get_hotkey__effect_intensity_up   (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__effect_intensity_up") }
+    pub fn get_hotkey__effect_intensity_down (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__effect_intensity_down") }
+
     pub fn get_hotkey__clear_overlays  (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__clear_overlays") }
     pub fn get_hotkey__clear_overrides (&self)  -> Option<HotKey> { self.get_hotkey ("hotkey__clear_overrides") }
+    pub fn get_hotkey__learn_rule (&self)       -> Option<HotKey> { self.get_hotkey ("hotkey__learn_rule") }
+    pub fn get_hotkey__explain_rules (&self)    -> Option<HotKey> { self.get_hotkey ("hotkey__explain_rules") }
 
     pub fn get_hotkey__gamma_preset_toggle (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_preset_toggle") }
     pub fn get_hotkey__next_gamma_preset (&self)   -> Option<HotKey> { self.get_hotkey ("hotkey__next_gamma_preset") }
     pub fn get_hotkey__prev_gamma_preset (&self)   -> Option<HotKey> { self.get_hotkey ("hotkey__prev_gamma_preset") }
 
+    pub fn get_hotkey__monitor_gamma_toggle (&self)      -> Option<HotKey> { self.get_hotkey ("hotkey__monitor_gamma_toggle") }
+    pub fn get_hotkey__next_monitor_gamma_preset (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__next_monitor_gamma_preset") }
+    pub fn get_hotkey__prev_monitor_gamma_preset (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__prev_monitor_gamma_preset") }
+
+    pub fn get_hotkey__gamma_brightness_up (&self)   -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_brightness_up") }
+    pub fn get_hotkey__gamma_brightness_down (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_brightness_down") }
+    pub fn get_hotkey__gamma_contrast_up (&self)     -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_contrast_up") }
+    pub fn get_hotkey__gamma_contrast_down (&self)   -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_contrast_down") }
+    pub fn get_hotkey__gamma_gamma_up (&self)        -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_gamma_up") }
+    pub fn get_hotkey__gamma_gamma_down (&self)      -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_gamma_down") }
+    pub fn get_hotkey__gamma_temp_up (&self)         -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_temp_up") }
+    pub fn get_hotkey__gamma_temp_down (&self)       -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_temp_down") }
+    pub fn get_hotkey__gamma_save_preset (&self)     -> Option<HotKey> { self.get_hotkey ("hotkey__gamma_save_preset") }
+
     pub fn get_hotkey__screen_mag_toggle (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__screen_magnification_toggle") }
     pub fn get_hotkey__next_mag_level (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__next_magnification_level") }
     pub fn get_hotkey__prev_mag_level (&self) -> Option<HotKey> { self.get_hotkey ("hotkey__prev_magnification_level") }
@@ -374,6 +496,19 @@ impl Config {
         self.check_flag ("auto_overlay_luminance__use_alternate_method")
     }
 
+    pub fn get_auto_overlay_luminance__resample_secs (&self) -> u32 {
+        self.get_float ("auto_overlay_luminance__resample_secs") .max(0.0) as u32
+    }
+
+    pub fn get_auto_overlay_luminance__off_threshold (&self) -> u8 {
+        let lum_fl = self.get_float ("auto_overlay_luminance__off_threshold");
+        (u8::MAX as f32 * lum_fl.clamp(0.0, 1.0)) as u8
+    }
+
+    pub fn get_auto_overlay_luminance__min_flip_secs (&self) -> u32 {
+        self.get_float ("auto_overlay_luminance__min_flip_secs") .max(0.0) as u32
+    }
+
 
     fn parse_auto_overlay_exe (v : &Value) -> Option <AutoOverlayExe> {
         if let Some(entry) = v .as_inline_table() {
@@ -424,28 +559,172 @@ impl Config {
 
 
 
-    pub fn parse_color_effect (table : &Table) -> Option <ColorEffectSpec> {
-        if let Some (name) = table .get("effect") .and_then (|s| s.as_str() .map (|s| s.to_string())) {
-            if let Some (Item::Value (Value::Array(arr))) = table .get("transform") {
-                let matrix: Vec<f32> = arr.iter() .filter_map (|v|
-                    v.as_float() .or_else (|| v.as_integer() .map (|i| i as f64)) .map (|f| f as f32)
-                ) .collect();
-                if matrix.len() == 25 {
-                    let mut transform = [0.0f32; 25];
-                    transform.copy_from_slice (&matrix);
-                    return Some ( ColorEffectSpec { name, transform } )
+    fn parse_auto_overlay_window_title (v : &Value) -> Option <AutoOverlayTitle> {
+        let entry = v .as_inline_table()?;
+        let (title, is_regex) = match (entry.get("title"), entry.get("title_regex")) {
+            (Some(t), _) => (t.as_str()?, false),
+            (_, Some(t)) => (t.as_str()?, true),
+            _ => return None,
+        };
+        let effect = entry .get("effect") .and_then (|s| s.as_str() .map (|s| s.to_string())) .filter (|eff| eff != "default");
+        let exes = entry .get("exes")
+            .and_then (|s| s.as_array())
+            .map (|a| a.iter() .filter_map (|s| s.as_str().map(|s| s.to_string())) .collect::<Vec<_>>())
+            .unwrap_or_default();
+        Some ( AutoOverlayTitle { title: title.to_string(), is_regex, effect, exes } )
+    }
+    pub fn get_auto_overlay_window_titles (&self) -> Vec<AutoOverlayTitle> {
+        if let Some(toml) = self.toml.read().unwrap().as_ref() {
+            return toml .get ("auto_overlay_window_titles") .and_then (|t| t.as_array())
+                .map (|t| t.iter() .filter_map (Self::parse_auto_overlay_window_title) .collect())
+                .unwrap_or_default()
+        }
+        vec![]
+    }
+
+
+
+    fn parse_auto_overlay_cond (entry : &InlineTable) -> Result <AutoOverlayCondSpec, String> {
+        let strs = |key: &str| -> Result <Vec<String>, String> {
+            match entry.get(key) {
+                None => Ok (vec![]),
+                Some (Value::String(s)) => Ok (vec! [s.value().to_string()]),
+                Some (Value::Array(a))  => a.iter() .map (|v| v.as_str() .map (|s| s.to_string()) .ok_or (format! ("non-string in {key:?}"))) .collect(),
+                Some (_) => Err (format! ("{key:?} must be a string or an array of strings")),
+            }
+        };
+        let title = match (entry.get("title"), entry.get("title_regex")) {
+            (Some(t), _) => Some ((t.as_str() .ok_or ("'title' must be a string")? .to_string(), false)),
+            (_, Some(t)) => Some ((t.as_str() .ok_or ("'title_regex' must be a string")? .to_string(), true)),
+            _ => None,
+        };
+        let luminance = match entry.get("luminance") {
+            None => None,
+            Some (Value::Array(a)) if a.len() == 2 => {
+                let v : Vec<f32> = a.iter() .filter_map (|v| v.as_float() .or (v.as_integer() .map (|i| i as f64))) .map (|f| f as f32) .collect();
+                if v.len() != 2 { return Err ("'luminance' must be a [min, max] pair of numbers".into()) }
+                Some ((v[0], v[1]))
+            }
+            Some (_) => return Err ("'luminance' must be a [min, max] pair of numbers".into()),
+        };
+        let not = match entry.get("not") {
+            None => vec![],
+            Some (Value::InlineTable(t)) => vec! [ Self::parse_auto_overlay_cond (t)? ],
+            Some (Value::Array(a)) => a.iter() .map (|v| {
+                v.as_inline_table() .ok_or ("'not' entries must be tables".to_string()) .and_then (Self::parse_auto_overlay_cond)
+            } ) .collect::<Result <Vec<_>, _>>()?,
+            Some (_) => return Err ("'not' must be a table or an array of tables".into()),
+        };
+        Ok ( AutoOverlayCondSpec { exes: strs("exe")?, classes: strs("class_name")?, title, luminance, not } )
+    }
+
+    fn parse_auto_overlay_rule (v : &Value) -> Result <AutoOverlayRule, String> {
+        let entry = v .as_inline_table() .ok_or ("rules must be tables")?;
+        let name = entry .get("name") .and_then (|s| s.as_str()) .map (|s| s.to_string()) .unwrap_or_default();
+        let priority = entry .get("priority") .and_then (|p| p.as_integer()) .ok_or ("rules must have an integer 'priority'")?;
+        let action = match entry .get("action") .and_then (|a| a.as_str()) .unwrap_or ("overlay") {
+            "overlay" => AutoOverlayRuleAction::Overlay,
+            "exclude" => AutoOverlayRuleAction::Exclude,
+            "nothing" => AutoOverlayRuleAction::Nothing,
+            a => return Err (format! ("unknown rule action {a:?} (must be one of overlay, exclude, or nothing)")),
+        };
+        let effect = entry .get("effect") .and_then (|s| s.as_str() .map (|s| s.to_string())) .filter (|eff| eff != "default");
+        let when = Self::parse_auto_overlay_cond (entry)?;
+        Ok ( AutoOverlayRule { name, priority, when, action, effect } )
+    }
+    pub fn get_auto_overlay_rules (&self) -> Vec<AutoOverlayRule> {
+        let toml = self.toml.read().unwrap();
+        let Some (rules) = toml .as_ref() .and_then (|t| t.get ("auto_overlay_rules")) .and_then (|t| t.as_array()) else { return vec![] };
+        rules .iter() .enumerate() .filter_map (|(i, v)| {
+            Self::parse_auto_overlay_rule (v) .map_err (|e| warn! ("Skipping auto_overlay_rules entry #{} : {e}", i+1)) .ok()
+        } ) .collect()
+    }
+
+    pub fn check_flag__learn_rule_by_class (&self) -> bool {
+        self.get_string ("auto_overlay_learn_rule_by") .eq_ignore_ascii_case ("class")
+    }
+
+    /// Writes a rule learned from a window into the config .. an overlay (w its effect) goes into auto_overlay_exes or
+    /// auto_overlay_window_classes (updating any entry already there), while no overlay becomes an 'exclude' compound rule.
+    /// Learning either kind drops any prior one of the other kind for that exe/class, so they dont contend.
+    pub fn save_learned_rule (&self, target: &LearnedRuleTarget, effect: Option <&str>) -> Result <(), String> {
+        if self.get_config_file().is_none() { return Err ("no writeable config file location".into()) }
+        {
+            let mut toml = self.toml.write().unwrap();
+            let doc = toml .as_mut() .ok_or ("config not loaded")?;
+
+            let (list_key, entry_key, name) = match target {
+                LearnedRuleTarget::Exe (exe)     => ("auto_overlay_exes", "exe", exe.as_str()),
+                LearnedRuleTarget::Class (class) => ("auto_overlay_window_classes", "class_name", class.as_str()),
+            };
+            let is_exe = matches! (target, LearnedRuleTarget::Exe(_));
+            let has = |v: &Value, key: &str, val: &str| {
+                v.as_inline_table() .and_then (|t| t.get (key)) .and_then (|s| s.as_str())
+                    .is_some_and (|s| if is_exe { s.eq_ignore_ascii_case (val) } else { s == val })
+            };
+            let excl_name = format! ("learned exclusion : {name}");
+
+            if let Some (rules) = doc .get_mut ("auto_overlay_rules") .and_then (|t| t.as_array_mut()) {
+                rules .retain (|v| !has (v, "name", &excl_name));
+            }
+            let list = Self::get_or_insert_array (doc, list_key)?;
+            let existing = list .iter() .position (|v| has (v, entry_key, name));
+
+            match (effect, existing) {
+                (Some (effect), Some (idx)) => {
+                    if let Some (entry) = list .get_mut (idx) .and_then (|v| v.as_inline_table_mut()) {
+                        entry.insert ("effect", effect.into());
+                    }
+                }
+                (Some (effect), None) => {
+                    let mut entry = InlineTable::new();
+                    entry.insert (entry_key, name.into());
+                    entry.insert ("effect", effect.into());
+                    Self::push_array_line (list, Value::InlineTable (entry));
+                }
+                (None, existing) => {
+                    if let Some (idx) = existing { list.remove (idx); }
+                    let mut entry = InlineTable::new();
+                    entry.insert ("name", excl_name.into());
+                    entry.insert ("priority", LEARNED_EXCLUSION_PRIORITY.into());
+                    entry.insert (entry_key, name.into());
+                    entry.insert ("action", "exclude".into());
+                    let rules = Self::get_or_insert_array (doc, "auto_overlay_rules")?;
+                    Self::push_array_line (rules, Value::InlineTable (entry));
                 }
             }
         }
-        None
+        self.write_back_toml()
     }
-    pub fn get_color_effects (&self) -> Vec<ColorEffectSpec> {
-        if let Some(toml) = self.toml.read().unwrap().as_ref() {
-            return toml .get ("effects") .and_then (|t| t.as_array_of_tables())
-                .map (|t| t.iter() .filter_map (Self::parse_color_effect) .collect())
-                .unwrap_or_default()
+
+    fn get_or_insert_array <'a> (doc: &'a mut DocumentMut, key: &str) -> Result <&'a mut Array, String> {
+        if doc.get (key) .is_none() {
+            let mut arr = Array::new();
+            arr.set_trailing ("\n");
+            doc.insert (key, toml_edit::value (arr));
         }
-        vec![]
+        doc .get_mut (key) .and_then (|t| t.as_array_mut()) .ok_or (format! ("{key} in config isn't an array"))
+    }
+
+    pub fn get_auto_overlay_remember_overrides (&self) -> RememberMode {
+        let mode = self.get_string ("auto_overlay_remember_overrides");
+        RememberMode::parse (&mode) .unwrap_or_else (|| {
+            warn! ("Unknown auto_overlay_remember_overrides value {mode:?} (must be none, app, or app+title) .. using none");
+            RememberMode::None
+        } )
+    }
+
+
+
+    /// The [[effects]] specs from the conf, along w the names of any malformed entries (which get dropped)
+    pub fn get_color_effects (&self) -> (Vec<ColorEffectSpec>, Vec<String>) {
+        let toml = self.toml.read().unwrap();
+        let Some (doc) = toml.as_ref() else { return (vec![], vec![]) };
+        let (specs, malformed) = parse_color_effects (doc);
+        for name in malformed.iter() {
+            warn! ("Ignoring malformed [[effects]] entry {name:?} .. (needs 'effect' name and a 25-float 'transform', a 'compose' list, or 'fit' color pairs)");
+        }
+        (specs, malformed)
     }
 
 
@@ -457,6 +736,10 @@ impl Config {
         self.get_string ("effects_default")
     }
 
+    pub fn get_effects_transition__duration_ms (&self) -> u32 {
+        self.get_float ("effects_transition__duration_ms") .max(0.0) as u32
+    }
+
 
 
 
@@ -472,6 +755,48 @@ impl Config {
         self.check_flag ("apply_gamma_preset_at_startup")
     }
 
+    pub fn check_flag__gamma_watchdog_enabled (&self) -> bool {
+        self.check_flag ("gamma_watchdog_enabled")
+    }
+    pub fn get_gamma_fade_duration (&self) -> Duration {
+        Duration::from_millis (self.get_float ("gamma_fade_duration_ms") .max(0.0) as u64)
+    }
+
+    pub fn check_flag__gamma_matrix_fallback (&self) -> bool {
+        self.check_flag ("gamma_matrix_fallback_enabled")
+    }
+
+    /// The base gamma ramp file (.cal or CSV) that presets get layered on, if any .. relative paths are from the config dir
+    pub fn get_gamma_base_ramp_file (&self) -> Option <PathBuf> {
+        let file = self.get_string ("gamma_base_ramp_file");
+        if file.trim().is_empty() { return None }
+        let path = PathBuf::from (file.trim());
+        if path.is_absolute() { return Some (path) }
+        let conf_dir = match self.file.as_ref() {
+            Some (file) => file.parent() .map (Path::to_path_buf),
+            None => self.get_log_loc(),
+        };
+        Some (conf_dir .map (|d| d.join (&path)) .unwrap_or (path))
+    }
+
+    pub fn get_gamma_watchdog_poll_secs (&self) -> u32 {
+        self.get_float ("gamma_watchdog_poll_secs") .max(0.0) as u32
+    }
+
+    pub fn get_gamma_schedule (&self) -> Option <GammaScheduleSpec> {
+        if !self.check_flag ("gamma_schedule_enabled") { return None }
+        let mins = |key| Duration::from_secs_f32 (self.get_float (key) .max(0.0) * 60.0);
+        let schedule = gamma::schedule::SolarSchedule {
+            latitude  : self.get_float ("gamma_schedule_latitude")  .clamp (-90.0, 90.0) as f64,
+            longitude : self.get_float ("gamma_schedule_longitude") .clamp (-180.0, 180.0) as f64,
+            dawn      : mins ("gamma_schedule_dawn_transition_mins"),
+            dusk      : mins ("gamma_schedule_dusk_transition_mins"),
+        };
+        let day_preset   = self.get_string ("gamma_schedule_day_preset");
+        let night_preset = self.get_string ("gamma_schedule_night_preset");
+        Some ( GammaScheduleSpec { schedule, day_preset, night_preset } )
+    }
+
     fn parse_gamma_preset (v: &Value) -> Option <GammaPresetSpec> {
         let entry = v .as_inline_table()?;
         let preset     = entry .get ("preset")     .and_then (|s| s.as_str()     .map(|s| s.to_string()))?;
@@ -479,9 +804,104 @@ impl Config {
         let bright     = entry .get ("brightness") .and_then (|v| v.as_float()   .map(|f| f as f32))?;
         let contrast   = entry .get ("contrast")   .and_then (|v| v.as_float()   .map(|f| f as f32))?;
         let color_temp = entry .get ("color_temp") .and_then (|v| v.as_integer() .map(|i| i as u32))?;
+        let tint       = entry .get ("tint")       .and_then (|v| v.as_float()   .or_else (|| v.as_integer() .map (|i| i as f64))) .map (|f| f as f32) .unwrap_or_default();
+
+        // each of gamma/brightness/contrast can optionally be overridden per channel (e.g. 'gamma_b'), else the shared value applies
+        let per_channel = |key: &str, shared: f32| ["r", "g", "b"] .map (|c| {
+            entry .get (&format! ("{key}_{c}")) .and_then (|v| v.as_float() .or_else (|| v.as_integer() .map (|i| i as f64)))
+                .map (|f| f as f32) .unwrap_or (shared)
+        } );
+        let gbc = gamma::GBC::with_channels (
+            per_channel ("gamma", gamma),  per_channel ("brightness", bright),  per_channel ("contrast", contrast)
+        );
+        Some ( GammaPresetSpec { preset, gbc, color_temp, tint } )
+    }
+
+    fn parse_monitor_preset (v: &Value) -> Option <MonitorPresetSpec> {
+        let entry = v .as_inline_table()?;
+        let preset  = entry .get ("preset") .and_then (|s| s.as_str() .map (|s| s.to_string()))?;
+        let monitor = entry .get ("monitor") .and_then (|m|
+            m.as_integer() .map (|i| MonitorId::Index (i.max(0) as usize)) .or_else (|| m.as_str() .map (|s| MonitorId::Device (s.to_string())))
+        )?;
+        Some ( MonitorPresetSpec { monitor, preset } )
+    }
+
+    pub fn get_gamma_monitor_presets (&self) -> Vec <MonitorPresetSpec> {
+        if let Some(toml) = self.toml.read().unwrap().as_ref() {
+            return toml .get ("gamma_monitor_presets") .and_then (|t| t.as_array())
+                .map (|t| t.iter() .filter_map (|v| {
+                    let spec = Self::parse_monitor_preset (v);
+                    if spec.is_none() { warn! ("Ignoring malformed gamma_monitor_presets entry : {}", v.to_string().trim()) }
+                    spec
+                } ) .collect())
+                .unwrap_or_default();
+        }
+        vec![]
+    }
+
+    pub fn check_flag__gamma_monitor_hotkeys_use_fgnd (&self) -> bool {
+        self.get_string ("gamma_monitor_hotkeys_target") .eq_ignore_ascii_case ("foreground")
+    }
+
+    /// Serializes a preset into a gamma_presets entry (per-channel keys only where channels actually differ)
+    fn gamma_preset_to_inline_table (spec: &GammaPresetSpec) -> InlineTable {
+        let round = |v: f32| ((v as f64) * 1000.0).round() / 1000.0;
+        let mut entry = InlineTable::new();
+        entry.insert ("preset", spec.preset.as_str().into());
+        let (gamma, bright, contrast) = spec.gbc.channels();
+        for (key, vals) in [("gamma", gamma), ("brightness", bright), ("contrast", contrast)] {
+            entry.insert (key, round (vals[0]).into());
+            if vals .iter() .any (|v| *v != vals[0]) {
+                for (c, v) in ["r", "g", "b"] .iter() .zip (vals) {
+                    let ch_key = format! ("{key}_{c}");
+                    entry.insert (&ch_key, round (v).into());
+                }
+            }
+        }
+        entry.insert ("color_temp", (spec.color_temp as i64).into());
+        if spec.tint != 0.0 { entry.insert ("tint", round (spec.tint).into()); }
+        entry
+    }
+
+    /// Appends a value on its own line to a multi-line array (as our conf arrays are laid out)
+    fn push_array_line (arr: &mut Array, mut v: Value) {
+        // w/o a trailing comma, the closing newline hangs off the last value, so it has to move to after the new one
+        if let Some (last) = arr .iter_mut() .last() {
+            let suffix = last.decor() .suffix() .and_then (|s| s.as_str()) .unwrap_or_default() .to_string();
+            if !suffix.is_empty() {
+                last.decor_mut() .set_suffix ("");
+                arr.set_trailing (suffix);
+            }
+        }
+        v.decor_mut() .set_prefix ("\n    ");
+        arr.push_formatted (v);
+    }
+
+    /// Writes a preset into gamma_presets (replacing any of the same name), and adds it to the cycle-order if not there already
+    /// .. the edits go through toml_edit, so the rest of the file (comments, formatting etc) is kept as is
+    pub fn save_gamma_preset (&self, spec: &GammaPresetSpec) -> Result <(), String> {
+        if self.get_config_file().is_none() { return Err ("no writeable config file location".into()) }
+        {
+            let mut toml = self.toml.write().unwrap();
+            let doc = toml .as_mut() .ok_or ("config not loaded")?;
+
+            let presets = doc .get_mut ("gamma_presets") .and_then (|t| t.as_array_mut()) .ok_or ("no gamma_presets array in config")?;
+            let entry = Value::InlineTable (Self::gamma_preset_to_inline_table (spec));
+            let existing = presets .iter() .position (|v| {
+                v.as_inline_table() .and_then (|t| t.get ("preset")) .and_then (|p| p.as_str()) == Some (spec.preset.as_str())
+            } );
+            match existing {
+                Some (idx) => { presets.replace (idx, entry); }
+                None => { Self::push_array_line (presets, entry); }
+            }
 
-        let gbc = gamma::GBC::new (gamma, bright, contrast);
-        Some ( GammaPresetSpec { preset, gbc, color_temp } )
+            if let Some (order) = doc .get_mut ("gamma_presets_cycle_order") .and_then (|t| t.as_array_mut()) {
+                if !order .iter() .any (|v| v.as_str() == Some (spec.preset.as_str())) {
+                    Self::push_array_line (order, spec.preset.as_str().into());
+                }
+            }
+        }
+        self.write_back_toml()
     }
 
     pub fn get_gamma_presets (&self) -> Vec <GammaPresetSpec> {
@@ -3,29 +3,41 @@
 //use no_deadlocks::RwLock;
 
 use std::collections::{HashMap, HashSet};
+use std::os::windows::process::CommandExt;
+use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{OnceLock, RwLock};
-use tracing::{info, warn};
+use std::thread;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
 use windows::Win32::Foundation::{GetLastError, FALSE, LPARAM, WPARAM};
-use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::System::Threading::{GetCurrentThreadId, CREATE_NEW_PROCESS_GROUP, CREATE_NO_WINDOW, DETACHED_PROCESS};
 use windows::Win32::UI::HiDpi::{SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
-use windows::Win32::UI::Magnification::{MagInitialize, MagUninitialize};
+use windows::Win32::UI::Magnification::{MagInitialize, MagSetFullscreenColorEffect, MagSetFullscreenTransform, MagUninitialize};
 use windows::Win32::UI::WindowsAndMessaging::{DispatchMessageW, GetMessageW, KillTimer, PostQuitMessage, PostThreadMessageW, SetTimer, MSG, WM_APP, WM_DESTROY, WM_HOTKEY, WM_TIMER};
 
 
+mod gamma_fade;
+mod gamma_watch;
 mod hooks;
 mod hotkeys;
 mod overlay_effect;
 mod overlay_fs_effect;
 mod overlay_mag;
 
+use gamma_fade::GammaFader;
 pub use overlay_effect::{Overlay};
 pub use overlay_fs_effect::FullScreenOverlay;
 pub use overlay_mag::{MagEffect, MagOverlay, MAG_EFFECT_DEFAULT, MAG_EFFECT_IDENTITY};
 
 use crate::{*, types::*};
-use crate::effects::{ColorEffect};
-use crate::presets::{GammaPresets, GammaPreset, GammaPresetAtomic};
+use crate::effects::{ColorEffect, COLOR_EFF__IDENTITY};
+use crate::effects::fit::FIT_RESIDUAL_WARN;
+use crate::config::GammaPresetSpec;
+use crate::gamma::schedule::SolarPhase;
+use crate::gamma::tune::{self, TuneParam};
+use crate::presets::{GammaPresets, GammaPreset, GammaPresetAtomic, MonitorGamma, SCHEDULED_PRESET_NAME, TUNED_PRESET_NAME};
 
 
 
@@ -40,6 +52,14 @@ const WM_APP__REQ_TOGGLE_FULLSCREEN_MODE  : u32 = WM_APP + 5;
 const WM_APP__REQ_TOGGLE_FULLSCREEN_EFF   : u32 = WM_APP + 6;
 const WM_APP__REQ_TOGGLE_SCREEN_MAG_LEVEL : u32 = WM_APP + 7;
 const WM_APP__REQ_MAG_REFRESH             : u32 = WM_APP + 8;
+const WM_APP__REQ_GAMMA_SCHEDULE          : u32 = WM_APP + 9;
+const WM_APP__REQ_GAMMA_CHECK             : u32 = WM_APP + 10;
+const WM_APP__REQ_OVERLAY_FADE_OUT        : u32 = WM_APP + 11;
+const WM_APP__REQ_RESTART                 : u32 = WM_APP + 12;
+const WM_APP__REQ_GAMMA_FADE_DONE         : u32 = WM_APP + 13;
+
+/// How often we re-evaluate the solar gamma schedule (transitions are long, so this can be pretty relaxed)
+const GAMMA_SCHEDULE_TICK : Duration = Duration::from_secs (30);
 
 
 
@@ -59,6 +79,20 @@ pub struct WinDusky {
     gamma_active : Flag,
     gamma_preset : GammaPresetAtomic,
 
+    gamma_monitors : RwLock <Vec <MonitorGamma>>,
+    // ^^ per-monitor gamma overrides (if any) .. we re-sync this against the actual monitors every time we update gamma
+
+    gamma_sched_spec     : RwLock <Option <GammaPresetSpec>>,
+    gamma_sched_override : RwLock <Option <SolarPhase>>,
+    // ^^ the blended spec while the solar schedule is driving gamma, and the phase in which it was manually overridden (if so)
+
+    gamma_tuned : RwLock <Option <GammaPresetSpec>>,
+    // ^^ live-tuned values (via the nudge hotkeys) that override the global preset until saved, or another preset is picked
+
+    gamma_reapplied       : AtomicUsize,
+    gamma_drift_unfixable : Flag,
+    // ^^ how many times the watchdog had to re-apply gamma, and whether re-applying didnt help (so we stop trying)
+
     overlays : RwLock <HashMap <Hwnd, Overlay>>,
     hosts    : RwLock <HashSet <Hwnd>>,
 
@@ -105,6 +139,17 @@ impl WinDusky {
 
         conf.check_dusky_conf_version_match();
 
+        // before we touch anything, we'll capture the original gamma ramps (or restore them, if a prior run crashed) ..
+        // and we'll clear any full-screen effects a crashed run might have left behind, then guard against our own panics
+        if gamma::init_gamma_restore (conf.get_gamma_restore_file()) {
+            reset_screen_effects();
+        }
+        install_panic_restore_hook();
+
+        if let Err (e) = gamma::load_base_ramp (conf.get_gamma_base_ramp_file().as_deref()) {
+            warn! ("{e} .. continuing w/o a base gamma ramp");
+        }
+
         let effects = effects::ColorEffects::init (conf);
         let presets = GammaPresets::init (conf);
 
@@ -124,6 +169,16 @@ impl WinDusky {
             gamma_active : Flag::default(),
             gamma_preset : GammaPresetAtomic::default(),
 
+            gamma_monitors : RwLock::new (Vec::new()),
+
+            gamma_sched_spec     : RwLock::new (None),
+            gamma_sched_override : RwLock::new (None),
+
+            gamma_tuned : RwLock::new (None),
+
+            gamma_reapplied       : AtomicUsize::default(),
+            gamma_drift_unfixable : Flag::default(),
+
             overlays : RwLock::new (HashMap::default()),
             hosts    : RwLock::new (HashSet::default()),
 
@@ -150,20 +205,51 @@ impl WinDusky {
         // we'll setup gamma, but only if specified active at startup (to avoid resetting otherwise)
         self.gamma_active.store (self.conf.check_flag__gamma_at_startup());
         self.gamma_preset.store (self.presets.default);
-        if self.gamma_active.is_set() { self.update_gamma_state(); }
+        if self.gamma_active.is_set() {
+            self.update_gamma_state();
+        } else if self.has_monitor_gamma_presets() {
+            // monitors w their own presets assigned get those regardless, but the rest keep their ramps (e.g. calibrations) as is
+            self.gamma_monitors.write().unwrap() .iter_mut() .filter (|m| m.preset.is_some()) .for_each (|m| m.active = Some (true));
+            self.apply_gamma_state (false);
+        }
+
+        // if there's a solar schedule for gamma, that takes over from here, and we'll re-evaluate it periodically
+        if self.presets.schedule.is_some() {
+            self.update_gamma_schedule();
+            thread::spawn (|| loop {
+                thread::sleep (GAMMA_SCHEDULE_TICK);
+                WinDusky::instance() .post_req__gamma_schedule();
+            } );
+        }
+
+        // we'll also keep an eye out for anything else resetting our gamma ramps
+        self.start_gamma_watchdog();
+
+        // and if configured, periodically re-sample luminance for windows whose auto-overlay decision hinged on it
+        self.auto.start_lum_resampling (WinDusky::instance());
+
+        // and we'll report any problems found while loading color-effects (the tray should be up by now)
+        tray::update_tray__effects_check (self.effects.lint_problems_count());
+        self.auto.report_remembered_overrides();
 
         // finally we just babysit the message loop
         let mut msg: MSG = std::mem::zeroed();
 
         loop {
 
-            if GetMessageW (&mut msg, None, 0, 0) == false {
-                let _ = MagUninitialize();
-                return Err (format!("GetMessageW failed with error: {:?}", GetLastError()));
+            match GetMessageW (&mut msg, None, 0, 0) .0 {
+                0 => return Ok(()),
+                // ^^ WM_QUIT, posted once we're done shutting down (so we'll leave quietly, w/o the panic hook redoing any of it)
+                -1 => {
+                    let _ = MagUninitialize();
+                    return Err (format!("GetMessageW failed with error: {:?}", GetLastError()));
+                }
+                _ => { }
             }
 
             match msg.message {
                 WM_TIMER | WM_APP__REQ_REFRESH => {
+                    self.fs_overlay.step_transition();
                     self.refresh_overlays();
                 }
                 WM_HOTKEY => {
@@ -181,8 +267,20 @@ impl WinDusky {
                 WM_APP__REQ_MAG_REFRESH => {
                     self.mag_overlay.refresh_mag_overlay()
                 }
+                WM_APP__REQ_GAMMA_SCHEDULE => {
+                    self.update_gamma_schedule();
+                }
+                WM_APP__REQ_GAMMA_CHECK => {
+                    self.check_gamma_drift();
+                }
+                WM_APP__REQ_GAMMA_FADE_DONE => {
+                    self.handle_gamma_fade_done (msg.wParam.0);
+                }
                 WM_APP__REQ_OVERLAY_CREATE => {
-                    self.create_overlay (Hwnd (msg.wParam.0 as _), ColorEffect (msg.lParam.0 as _));
+                    self.create_overlay (Hwnd (msg.wParam.0 as _), ColorEffect::from_bits (msg.lParam.0 as _));
+                }
+                WM_APP__REQ_OVERLAY_FADE_OUT => {
+                    self.fade_out_overlay (Hwnd (msg.wParam.0 as _));
                 }
                 WM_APP__REQ_OVERLAY_CLEAR_ALL => {
                     self.clear_overlays();
@@ -190,11 +288,18 @@ impl WinDusky {
                 WM_APP__UN_REGISTER_HOTEKYS => {
                     self.un_register_hotkeys();
                 }
+                WM_APP__REQ_RESTART => {
+                    self.shut_down();
+                    // ^^ the new instance must only start once we're done w the gamma restore file, else it'd see it as a crash
+                    let mut cmd = Command::new (std::env::current_exe().unwrap());
+                    cmd .creation_flags ((DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW).0);
+                    match cmd .spawn() {
+                        Ok (proc) => warn! ("Launched a new WinDusky process with pid: {:?}", proc.id()),
+                        Err (e) => error! ("Failed to launch a new WinDusky process : {e}"),
+                    }
+                }
                 WM_DESTROY => {
-                    warn!("Shutting down .. ~~~~ GOOD BYE ~~~~ !!");
-                    if self.gamma_active.is_set() { gamma::reset_screen_ramp(); }
-                    let _ = MagUninitialize();
-                    PostQuitMessage(0);
+                    self.shut_down();
                 }
                 _ => { DispatchMessageW(&msg); }
             }
@@ -204,39 +309,301 @@ impl WinDusky {
 
 
 
+    /// Re-enumerates monitors, keeping existing per-monitor state, and picking up conf assignments for new ones
+    fn sync_gamma_monitors (&self) -> Vec <MonitorGamma> {
+        let mut gamma_monitors = self.gamma_monitors.write().unwrap();
+        let assignments = self.conf.get_gamma_monitor_presets();
+        let synced : Vec <MonitorGamma> = gamma::enumerate_monitors() .into_iter() .enumerate() .map (|(i, mon)| {
+            let index = i + 1;
+            if let Some (existing) = gamma_monitors .iter() .find (|m| m.device == mon.device_name) {
+                return MonitorGamma { index, ..existing.clone() }
+            }
+            let preset = assignments .iter() .find (|a| a.monitor.matches (index, &mon.device_name)) .and_then (|a| {
+                let preset = self.presets.find_by_name (&a.preset);
+                if preset.is_none() { warn! ("Unknown gamma preset {:?} assigned to monitor {:?}", a.preset, mon.device_name) }
+                preset
+            } );
+            MonitorGamma { device: mon.device_name, index, active: None, preset, tuned: None }
+        } ) .collect();
+        *gamma_monitors = synced.clone();
+        synced
+    }
+
+    fn has_monitor_gamma_presets (&self) -> bool {
+        self.sync_gamma_monitors() .iter() .any (|m| m.preset.is_some())
+    }
+
+    fn check_any_gamma_active (&self) -> bool {
+        let global_active = self.gamma_active.is_set();
+        global_active || self.gamma_monitors.read().unwrap() .iter() .any (|m| m.effective_active (global_active))
+    }
+
+    /// The gamma spec (and its name) that applies globally .. either live-tuned values, the blended solar-schedule spec, or the selected preset
+    fn get_global_gamma_spec (&self) -> (GammaPresetSpec, &'static str) {
+        if let Some (spec) = self.gamma_tuned.read().unwrap() .as_ref() {
+            return (spec.clone(), TUNED_PRESET_NAME)
+        }
+        if let Some (spec) = self.gamma_sched_spec.read().unwrap() .as_ref() {
+            return (spec.clone(), SCHEDULED_PRESET_NAME)
+        }
+        let preset = GammaPreset::from (&self.gamma_preset);
+        (preset.get(), preset.name())
+    }
+
+    fn get_monitor_gamma_spec (mon: &MonitorGamma, global: &(GammaPresetSpec, &'static str)) -> (GammaPresetSpec, &'static str) {
+        if let Some (spec) = mon.tuned.as_ref() { return (spec.clone(), TUNED_PRESET_NAME) }
+        mon.preset .map (|p| (p.get(), p.name())) .unwrap_or_else (|| global.clone())
+    }
+
     pub fn update_gamma_state (&self) {
-        if let Some ((spec, name)) = self.gamma_active.is_set() .then_some ( {
-            let preset = GammaPreset::from (&self.gamma_preset);
-            (preset.get(), preset.name())
-        } ) {
-            info! ("Applying GammaPreset values from Preset : {:?}", name);
-            let succeeded = gamma::set_screen_ramp_gbct (&spec.gbc, spec.color_temp);
-            tray::update_tray__gamma_state (true, succeeded, Some(name));
-        } else {
-            info! ("Resetting Gamma values to Normal");
-            gamma::reset_screen_ramp();
-            tray::update_tray__gamma_state (false, true, None);
+        self.apply_gamma_state (true)
+    }
+
+    /// Applies presets to the monitors they're active on, and (if reset_inactive) resets the rest to Normal
+    fn apply_gamma_state (&self, reset_inactive: bool) {
+        if let Some (fader) = GammaFader::get() { fader.cancel() }
+        // ^^ any direct update supersedes a fade in progress
+        self.gamma_drift_unfixable.clear();
+        let monitors = self.sync_gamma_monitors();
+        let (global_active, global_spec) = (self.gamma_active.is_set(), self.get_global_gamma_spec());
+
+        if monitors.is_empty() {
+            // if we couldnt enumerate monitors for whatever reason, we'll fall back to the whole-screen DC
+            if global_active {
+                let (spec, name) = &global_spec;
+                info! ("Applying GammaPreset values from Preset : {:?}", name);
+                let applied = gamma::set_screen_ramp_gbct (&spec.gbc, spec.color_temp, spec.tint);
+                let fallback = self.update_gamma_fallback ((!applied.succeeded()) .then_some (spec));
+                tray::update_tray__gamma_state (true, applied.succeeded(), applied.is_clamped(), fallback, Some(name));
+            } else if reset_inactive {
+                info! ("Resetting Gamma values to Normal");
+                gamma::reset_screen_ramp();
+                self.update_gamma_fallback (None);
+                tray::update_tray__gamma_state (false, true, false, false, None);
+            }
+            return
+        }
+
+        let (mut all_succeeded, mut any_clamped) = (true, false);
+        let (mut n_active, mut failed_specs) = (0, vec![]);
+        let mut tray_states = vec![];
+        for mon in monitors.iter() {
+            let preset = mon.effective_active (global_active) .then (|| Self::get_monitor_gamma_spec (mon, &global_spec));
+            let (succeeded, clamped) = if let Some ((spec, name)) = preset.as_ref() {
+                info! ("Applying GammaPreset values on {} from Preset : {:?}", mon.label(), name);
+                let applied = gamma::set_monitor_ramp_gbct (&mon.device, &spec.gbc, spec.color_temp, spec.tint);
+                n_active += 1;
+                if !applied.succeeded() { failed_specs.push (spec.clone()) }
+                (applied.succeeded(), applied.is_clamped())
+            } else if reset_inactive {
+                info! ("Resetting Gamma values on {} to Normal", mon.label());
+                (gamma::reset_monitor_ramp (&mon.device), false)
+            } else { (true, false) };
+            all_succeeded &= succeeded;
+            any_clamped |= clamped;
+            tray_states.push ( tray::MonitorGammaState {
+                device: mon.device.clone(), label: mon.label(), preset: preset.map (|(_, name)| name), succeeded, clamped, fallback: false
+            } );
+        }
+
+        // the fallback matrix is full-screen, so we can only use it if no monitor got its ramp (else those would get it twice)
+        let all_failed = n_active > 0 && failed_specs.len() == n_active;
+        if !failed_specs.is_empty() && !all_failed {
+            warn! ("Gamma ramps failed on only some monitors .. so can't use the (full-screen) color-matrix fallback for them");
+        }
+        if all_failed && failed_specs .iter() .any (|s| *s != failed_specs[0]) {
+            warn! ("Monitors have differing gamma presets, but the color-matrix fallback can only apply one of them to all");
         }
+        let fallback = self.update_gamma_fallback (failed_specs .first() .filter (|_| all_failed));
+        if fallback { tray_states .iter_mut() .filter (|m| !m.succeeded) .for_each (|m| m.fallback = true) }
+
+        tray::update_tray__gamma_state (global_active, all_succeeded, any_clamped, fallback, global_active.then_some (global_spec.1));
+        tray::update_tray__monitor_gamma (tray_states);
+    }
+
+    /// Applies the color-matrix equivalent of the spec (if given) via the full-screen color effect, or clears it .. returns whether it's in use
+    fn update_gamma_fallback (&self, failed: Option <&GammaPresetSpec>) -> bool {
+        let spec = failed .filter (|_| self.conf.check_flag__gamma_matrix_fallback());
+        let matrix = spec .map (|spec| {
+            let fit = gamma::calc_gbct_matrix (&spec.gbc, spec.color_temp, spec.tint);
+            let max_residual = fit.residuals .iter() .cloned() .fold (0.0, f32::max);
+            warn! ("Hardware gamma ramps unavailable .. applying gamma preset as a full-screen color matrix instead");
+            if max_residual > FIT_RESIDUAL_WARN {
+                warn! ("(the preset's gamma curve can only be approximated by a color matrix .. off by up to {max_residual:.1} levels)");
+            }
+            fit.matrix
+        } );
+        self.fs_overlay.set_gamma_fallback (matrix);
+        matrix.is_some()
     }
 
     pub fn toggle_gamma_active (&self) {
-        if self.gamma_active.is_set() && !self.check_active_gamma_preset_match().unwrap_or_default() {
+        let fading = GammaFader::get() .is_some_and (|f| f.is_fading());
+        // ^^ mid-fade, ramps wont match the preset yet .. and a toggle then just retargets the fade
+        if self.gamma_active.is_set() && !fading && !self.check_active_gamma_preset_match().unwrap_or_default() {
             warn! ("Gamma Preset Toggle requested, but active ramp does not match preset .. Re-applying instead !!");
             self.update_gamma_state();
             return
         }
+        self.register_gamma_schedule_override();
         self.gamma_active.toggle();
-        self.update_gamma_state();
+        // the global toggle acts on all monitors, so we'll clear any per-monitor on/off toggling (but keep their presets)
+        self.gamma_monitors.write().unwrap() .iter_mut() .for_each (|m| m.active = None);
+        self.fade_gamma_state();
     }
 
     pub fn check_active_gamma_preset_match (&self) -> Option <bool> {
-        let preset = GammaPreset::from (&self.gamma_preset) .get();
-        gamma::check_active_gamma_match (&preset.gbc, preset.color_temp)
+        let (global_active, global_spec) = (self.gamma_active.is_set(), self.get_global_gamma_spec());
+        let monitors = self.gamma_monitors.read().unwrap().clone();
+        if monitors.is_empty() {
+            let (spec, _) = global_spec;
+            return gamma::check_active_gamma_match (&spec.gbc, spec.color_temp, spec.tint)
+        }
+        let mut all_match = true;
+        for mon in monitors .iter() .filter (|m| m.effective_active (global_active)) {
+            let (spec, _) = Self::get_monitor_gamma_spec (mon, &global_spec);
+            all_match &= gamma::check_monitor_gamma_match (&mon.device, &spec.gbc, spec.color_temp, spec.tint)?;
+        }
+        Some (all_match)
     }
 
     pub fn cycle_gamma_preset (&self, forward: bool) {
         if self.gamma_active.is_clear() { return }
-        self.gamma_preset.cycle (forward);
+        if self.gamma_sched_spec.read().unwrap().is_none() && self.gamma_tuned.write().unwrap() .take() .is_none() {
+            self.gamma_preset.cycle (forward);
+        }
+        // ^^ if the schedule (or tuning) was driving things, we'll first just switch to the selected preset (rather than skip over it)
+        self.register_gamma_schedule_override();
+        self.fade_gamma_state();
+    }
+
+
+    /// Applies the solar-schedule gamma for the current time (unless the user has manually overridden the current phase)
+    pub fn update_gamma_schedule (&self) {
+        let Some (sched) = self.presets.schedule.as_ref() else { return };
+        let now = OffsetDateTime::now_utc();
+        let phase = sched.schedule.phase (now);
+        {
+            let mut override_phase = self.gamma_sched_override.write().unwrap();
+            match *override_phase {
+                Some (p) if p == phase => return,
+                Some (_) => {
+                    info! ("Gamma schedule moved on to phase {:?} .. resuming schedule after manual override", phase);
+                    *override_phase = None;
+                    if self.gamma_tuned.write().unwrap() .take() .is_some() {
+                        warn! ("Dropping unsaved live-tuned gamma values as the gamma schedule resumes");
+                    }
+                }
+                None => { }
+            }
+        }
+        let spec = sched.spec_at (now);
+        if self.gamma_active.is_set() && self.gamma_sched_spec.read().unwrap() .as_ref() == Some (&spec) { return }
+        // ^^ outside of dawn/dusk transitions, the spec mostly stays the same, so we'll avoid re-applying needlessly
+        *self.gamma_sched_spec.write().unwrap() = Some (spec);
+        self.gamma_active.set();
+        self.update_gamma_state();
+    }
+
+    /// Nudges a gamma parameter of the active (global) preset by a step, applying it live
+    pub fn nudge_gamma (&self, param: TuneParam, up: bool) {
+        if self.get_gamma_target_monitor() .is_some_and (|device| self.nudge_monitor_gamma (&device, param, up)) { return }
+        // ^^ a monitor w its own preset gets that tuned, rather than the global one
+
+        let (mut spec, _) = self.get_global_gamma_spec();
+        if self.gamma_active.is_clear() {
+            // nudging while gamma is off starts from the selected preset (rather than from whatever is showing)
+            spec = GammaPreset::from (&self.gamma_preset) .get();
+            self.gamma_active.set();
+        } else if self.gamma_tuned.read().unwrap().is_none() && self.gamma_sched_spec.read().unwrap().is_some() {
+            // the schedule's blend isnt a preset itself, so tuning it gets named after the preset it's mostly showing
+            if let Some (sched) = self.presets.schedule.as_ref() {
+                spec.preset = sched.nearest_preset_at (OffsetDateTime::now_utc()) .name() .to_string();
+            }
+        }
+        (spec.gbc, spec.color_temp) = tune::nudge (&spec.gbc, spec.color_temp, param, up);
+        info! ("Live-tuned gamma {:?} {} .. now : {:?}, temp: {:?}", param, if up {"up"} else {"down"}, spec.gbc, spec.color_temp);
+        // ^^ the spec keeps the name of the preset we started tuning from (which the saved preset then gets named after)
+        self.register_gamma_schedule_override();
+        *self.gamma_tuned.write().unwrap() = Some (spec);
+        self.update_gamma_state();
+    }
+
+    /// Nudges the gamma of a monitor that has its own preset .. returns false if it doesnt (so the global preset applies)
+    fn nudge_monitor_gamma (&self, device: &str, param: TuneParam, up: bool) -> bool {
+        self.sync_gamma_monitors();
+        let global_active = self.gamma_active.is_set();
+        {
+            let mut monitors = self.gamma_monitors.write().unwrap();
+            let Some (mon) = monitors .iter_mut() .find (|m| m.device == device) else { return false };
+            let Some (preset) = mon.preset else { return false };
+            let mut spec = mon.tuned .clone() .unwrap_or_else (|| preset.get());
+            (spec.gbc, spec.color_temp) = tune::nudge (&spec.gbc, spec.color_temp, param, up);
+            info! ("Live-tuned gamma on {} {:?} {} .. now : {:?}, temp: {:?}", mon.label(), param, if up {"up"} else {"down"}, spec.gbc, spec.color_temp);
+            if !mon.effective_active (global_active) { mon.active = Some (true) }
+            mon.tuned = Some (spec);
+        }
+        self.update_gamma_state();
+        true
+    }
+
+    /// Saves live-tuned gamma values back to the config as a preset (named after the preset the tuning started from) ..
+    /// for the targeted monitor's own preset if that was tuned, else for the global one
+    pub fn save_tuned_gamma_preset (&self) {
+        let mon_tuned = self.get_gamma_target_monitor() .and_then (|device| {
+            self.gamma_monitors.read().unwrap() .iter() .find (|m| m.device == device) .and_then (|m| m.tuned.clone())
+        } );
+        let Some (mut spec) = mon_tuned .or_else (|| self.gamma_tuned.read().unwrap().clone()) else {
+            info! ("Save gamma preset requested, but there are no live-tuned values to save");
+            return
+        };
+        spec.preset = tune::tuned_preset_name (&spec.preset);
+        match self.conf.save_gamma_preset (&spec) {
+            Ok(_) => info! ("Saved live-tuned gamma values to config as preset {:?} (it'll be in the presets cycle after a restart)", spec.preset),
+            Err(e) => error! ("Failed to save live-tuned gamma preset {:?} : {e}", spec.preset),
+        }
+    }
+
+    /// Manual gamma changes pause the schedule until its next phase
+    fn register_gamma_schedule_override (&self) {
+        let Some (sched) = self.presets.schedule.as_ref() else { return };
+        let phase = sched.schedule.phase (OffsetDateTime::now_utc());
+        if self.gamma_sched_override.write().unwrap() .replace (phase) .is_none() {
+            info! ("Manual gamma change .. pausing gamma schedule until the current phase ({:?}) ends", phase);
+        }
+        *self.gamma_sched_spec.write().unwrap() = None;
+    }
+
+
+    /// The monitor that per-monitor gamma hotkeys should act on (under the cursor, or with the fgnd window, per conf)
+    fn get_gamma_target_monitor (&self) -> Option <String> {
+        if self.conf.check_flag__gamma_monitor_hotkeys_use_fgnd() {
+            let fgnd = self.fgnd_cache.load();
+            if fgnd.is_valid() { return gamma::get_monitor_device_for_hwnd (fgnd) }
+        }
+        gamma::get_monitor_device_at_point (win_utils::get_pointer_loc())
+    }
+
+    pub fn toggle_monitor_gamma (&self, device: Option <String>) {
+        let Some (device) = device .or_else (|| self.get_gamma_target_monitor()) else { return };
+        self.sync_gamma_monitors();
+        let global_active = self.gamma_active.is_set();
+        if let Some (mon) = self.gamma_monitors.write().unwrap() .iter_mut() .find (|m| m.device == device) {
+            mon.active = Some (!mon.effective_active (global_active));
+            info! ("Toggling gamma on {} to : {:?}", mon.label(), mon.active);
+        }
+        self.update_gamma_state();
+    }
+
+    pub fn cycle_monitor_gamma_preset (&self, forward: bool) {
+        let Some (device) = self.get_gamma_target_monitor() else { return };
+        self.sync_gamma_monitors();
+        let (global_active, global_preset) = (self.gamma_active.is_set(), GammaPreset::from (&self.gamma_preset));
+        if let Some (mon) = self.gamma_monitors.write().unwrap() .iter_mut() .find (|m| m.device == device) {
+            if !mon.effective_active (global_active) { return }
+            mon.preset = Some (mon.effective_preset (global_preset) .cycled (forward));
+            mon.tuned = None;
+        }
         self.update_gamma_state();
     }
 
@@ -284,20 +651,30 @@ impl WinDusky {
         //else {}  // <- if we just toggled off fs-mode .. thats it, nothing more to do
 
         let effect = if !enabled { prior_eff } else { (&self.fs_overlay.effect).into() };
-        tray::update_tray__full_screen_mode (enabled, Some(effect));
+        self.fs_effect_updated (enabled, Some(effect));
     }
     fn toggle_full_screen_effect (&self) {
         let eff = self.fs_overlay.toggle_effect();
-        tray::update_tray__full_screen_mode (self.fs_overlay.enabled.is_set(), eff);
+        self.fs_effect_updated (self.fs_overlay.enabled.is_set(), eff);
     }
     fn cycle_full_screen_effect (&self, forward: bool) {
         if self.fs_overlay.active.is_clear() { return }
         let eff = self.fs_overlay.apply_effect_cycled (Some(forward));
-        tray::update_tray__full_screen_mode (true, Some(eff))
+        self.fs_effect_updated (true, Some(eff))
+    }
+    fn step_full_screen_effect_intensity (&self, up: bool) {
+        if self.fs_overlay.active.is_clear() { return }
+        let eff = self.fs_overlay.apply_intensity_stepped (up);
+        self.fs_effect_updated (true, Some(eff))
     }
     fn clear_full_screen_effect (&self) {
         let eff = self.fs_overlay .unapply_effect();
-        tray::update_tray__full_screen_mode (false, eff);
+        self.fs_effect_updated (false, eff);
+    }
+    fn fs_effect_updated (&self, enabled: bool, eff: Option<ColorEffect>) {
+        // if the change kicked off an animated transition, we'll need the timer ticking to step through it
+        if self.fs_overlay.in_transition() { self.ensure_timer_running() }
+        tray::update_tray__full_screen_mode (enabled, eff);
     }
 
 
@@ -320,9 +697,9 @@ impl WinDusky {
             self.occl_marked.set();
             overlays.insert (target, overlay);
             tray::update_tray__overlay_count (overlays.len());
-            info! ("Created Overlay on {:?} with effect: {:?}, tot: {:?}", target, effect.name(), overlays.len());
+            info! ("Created Overlay on {:?} with effect: {:?}, tot: {:?}", target, effect.label(), overlays.len());
         } else {
-            warn! ("Failed to create Overlay on {:?} with effect: {:?}, tot: {:?}", target, effect.name(), overlays.len());
+            warn! ("Failed to create Overlay on {:?} with effect: {:?}, tot: {:?}", target, effect.label(), overlays.len());
         }
     }
 
@@ -340,12 +717,32 @@ impl WinDusky {
         tray::update_tray__overlay_count (overlays.len());
     }
 
+    fn fade_out_overlay (&self, target:Hwnd) {
+        // Warning : This should only be called from overlay-manager thread
+        if self.effects.transition.is_zero() {
+            self.remove_overlay (target);
+            return
+        }
+        // we'll leave the overlay in place while it fades, and the timer refresh will remove it once its done
+        if let Some (overlay) = self.overlays.read().unwrap().get (&target) {
+            overlay.begin_fade_out();
+        }
+    }
+
     fn refresh_overlays (&self) {
         if self.occl_marked.is_set() {
             self.refresh_viz_bounds();
         }
+        let mut faded = vec![];
         for overlay in self.overlays.read().unwrap().values() {
             overlay.refresh(self);
+            if overlay.is_faded_out() { faded.push (overlay.target) }
+        }
+        for target in faded {
+            self.remove_overlay (target);
+        }
+        if self.overlays.read().unwrap().is_empty() && !self.fs_overlay.in_transition() {
+            self.disable_timer();
         }
     }
 
@@ -364,6 +761,15 @@ impl WinDusky {
         }   }
     }
 
+    fn shut_down (&self) { unsafe {
+        warn!("Shutting down .. ~~~~ GOOD BYE ~~~~ !!");
+        if let Some (fader) = GammaFader::get() { fader.cancel() }
+        gamma::end_gamma_restore (self.check_any_gamma_active());
+        reset_screen_effects();
+        let _ = MagUninitialize();
+        PostQuitMessage(0);
+    } }
+
     fn clear_overlays (&self) {
         // Reminder that this MUST be called from overlay-manager thread to have effect on overlay hwnds
         let mut overlays = self.overlays.write().unwrap();
@@ -397,24 +803,40 @@ impl WinDusky {
     pub fn post_req__toggle_mag_level    (&self) { self.post_simple_req (WM_APP__REQ_TOGGLE_SCREEN_MAG_LEVEL) }
     pub fn post_req__mag_refresh         (&self) { self.post_simple_req (WM_APP__REQ_MAG_REFRESH) }
     pub fn post_req__refresh             (&self) { self.post_simple_req (WM_APP__REQ_REFRESH) }
+    pub fn post_req__gamma_schedule      (&self) { self.post_simple_req (WM_APP__REQ_GAMMA_SCHEDULE) }
+    pub fn post_req__gamma_check         (&self) { self.post_simple_req (WM_APP__REQ_GAMMA_CHECK) }
     pub fn post_req__overlay_clear_all   (&self) { self.post_simple_req (WM_APP__REQ_OVERLAY_CLEAR_ALL) }
     pub fn post_req__un_register_hotkeys (&self) { self.post_simple_req (WM_APP__UN_REGISTER_HOTEKYS) }
     pub fn post_req__quit                (&self) { self.post_simple_req (WM_DESTROY) }
+    pub fn post_req__restart             (&self) { self.post_simple_req (WM_APP__REQ_RESTART) }
 
     pub fn post_req__overlay_create (&self, target:Hwnd, effect:ColorEffect) { unsafe {
         let _ = PostThreadMessageW (
-            self.thread_id, WM_APP__REQ_OVERLAY_CREATE, WPARAM (target.0 as _), LPARAM (effect.0 as _)
+            self.thread_id, WM_APP__REQ_OVERLAY_CREATE, WPARAM (target.0 as _), LPARAM (effect.to_bits() as _)
         );
     } }
 
+    pub fn post_req__overlay_fade_out (&self, target:Hwnd) { unsafe {
+        let _ = PostThreadMessageW (self.thread_id, WM_APP__REQ_OVERLAY_FADE_OUT, WPARAM (target.0 as _), LPARAM(0));
+    } }
+
+    pub fn post_req__gamma_fade_done (&self, gen:usize) { unsafe {
+        let _ = PostThreadMessageW (self.thread_id, WM_APP__REQ_GAMMA_FADE_DONE, WPARAM (gen), LPARAM(0));
+    } }
+
 
     fn ensure_timer_running (&self) { unsafe {
+        if self.cur_timer .load(Ordering::Acquire) != 0 { return }
+        // ^^ (w/o a hwnd, every SetTimer call creates a new timer .. so we'll only start one if we dont have one running)
         let timer_id = SetTimer (None, 0, TIMER_TICK_MS, None);
         self.cur_timer .store (timer_id, Ordering::Release);
     } }
 
     fn disable_timer (&self) { unsafe {
-        let _ = KillTimer (None, self.cur_timer .load(Ordering::Acquire));
+        if self.fs_overlay.in_transition() { return }
+        // ^^ full-screen transitions also step on our timer, so we'll let those complete (the refresh will stop it after)
+        let timer_id = self.cur_timer .swap (0, Ordering::AcqRel);
+        if timer_id != 0 { let _ = KillTimer (None, timer_id); }
     } }
 
 
@@ -428,3 +850,27 @@ impl WinDusky {
 
 
 
+/// Resets the full-screen color effect and magnifier transform to identity (e.g. on exit, or after a prior crash)
+fn reset_screen_effects () { unsafe {
+    let _ = MagSetFullscreenColorEffect (&COLOR_EFF__IDENTITY);
+    let _ = MagSetFullscreenTransform (MAG_EFFECT_IDENTITY.get(), 0, 0);
+} }
+
+/// On panic, we'd rather not leave the screen dark (or inverted, or zoomed) .. so we restore all that before bailing
+fn install_panic_restore_hook () {
+    let dusky_thread = unsafe { GetCurrentThreadId() };
+    // ^^ we're installed from init, on what becomes the dusky thread
+    let prior_hook = std::panic::take_hook();
+    std::panic::set_hook ( Box::new ( move |info| {
+        if unsafe { GetCurrentThreadId() } == dusky_thread {
+            error! ("WinDusky panicked : {info} .. restoring original gamma and clearing screen effects");
+            gamma::end_gamma_restore (true);
+            reset_screen_effects();
+        } else {
+            // panics elsewhere (tray, fader, watcher threads etc) only unwind that thread, and we keep running .. so we'll
+            // leave gamma (and the restore file guarding it) as is
+            error! ("WinDusky panicked (off the dusky thread) : {info}");
+        }
+        prior_hook (info);
+    } ) );
+}
@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::config::GammaPresetSpec;
+use crate::dusky::WinDusky;
+use crate::gamma::{self, GammaRamp, SCREEN_DC_KEY};
+use crate::gamma::ramp::lerp_ramps;
+
+
+
+// Smooth fades between gamma presets (for toggling and cycling presets) .. jumping straight to a much darker/brighter
+// ramp is harsh in a dark room. The fade steps through interpolated ramps on its own thread, and a new request arriving
+// mid-fade retargets it from wherever it currently is (rather than queueing). Once a fade completes, the dusky thread
+// does a regular gamma update (unless the fade got superseded by then), which sets the exact final ramps, and takes care
+// of fallbacks, tray updates etc as usual.
+
+
+
+/// Interval between fade steps (ramp updates are cheap, but there's no point going much beyond what the eye can tell)
+const FADE_STEP : Duration = Duration::from_millis (30);
+
+
+#[derive (Debug)]
+struct FadeTarget {
+    key  : String,
+    // ^^ the monitor device name (or SCREEN_DC_KEY)
+    from : GammaRamp,
+    to   : GammaRamp,
+}
+
+#[derive (Debug)]
+struct Fade {
+    targets  : Vec <FadeTarget>,
+    start    : Instant,
+    duration : Duration,
+}
+
+impl Fade {
+    fn progress (&self, now: Instant) -> f32 {
+        (now .saturating_duration_since (self.start) .as_secs_f32() / self.duration.as_secs_f32()) .min (1.0)
+    }
+    fn shown (&self, key: &str, now: Instant) -> Option <GammaRamp> {
+        self.targets .iter() .find (|t| t.key == key) .map (|t| lerp_ramps (&t.from, &t.to, self.progress (now)))
+    }
+}
+
+
+#[derive (Debug, Default)]
+pub struct GammaFader {
+    fade : Mutex <Option <Fade>>,
+    wake : Condvar,
+    gen  : AtomicUsize,
+    // ^^ bumped (under the fade lock) whenever a fade starts or gets cancelled, so a completion can tell if it's stale
+}
+
+static GAMMA_FADER : OnceLock <GammaFader> = OnceLock::new();
+
+impl GammaFader {
+
+    pub(super) fn instance() -> &'static GammaFader {
+        GAMMA_FADER .get_or_init ( || {
+            thread::spawn (|| GammaFader::instance() .run());
+            GammaFader::default()
+        } )
+    }
+
+    /// The fader, only if it was ever started (so that checks and cancels dont spin up its thread needlessly)
+    pub(super) fn get() -> Option <&'static GammaFader> {
+        GAMMA_FADER.get()
+    }
+
+    fn run (&self) {
+        loop {
+            let mut fade = self.fade.lock().unwrap();
+            while fade.is_none() { fade = self.wake.wait (fade).unwrap(); }
+
+            let now = Instant::now();
+            let done = fade .as_ref() .is_some_and (|f| f.progress (now) >= 1.0);
+            if let Some (f) = fade.as_ref() {
+                for t in f.targets.iter() {
+                    gamma::set_ramp_for_key (&t.key, &lerp_ramps (&t.from, &t.to, f.progress (now)));
+                }
+                // ^^ we hold the lock while setting ramps, so cancelling (e.g. at exit) cant race a step already underway
+            }
+            if done { *fade = None; }
+            let gen = self.gen.load (Ordering::Acquire);
+            drop (fade);
+
+            if done { WinDusky::instance() .post_req__gamma_fade_done (gen); }
+            else { thread::sleep (FADE_STEP); }
+        }
+    }
+
+    /// Starts (or retargets) a fade to the given ramps, from whatever is showing now (including mid-fade)
+    fn start (&self, targets: Vec <(String, GammaRamp)>, duration: Duration) {
+        let mut fade = self.fade.lock().unwrap();
+        let now = Instant::now();
+        let targets = targets .into_iter() .filter_map (|(key, to)| {
+            let from = fade .as_ref() .and_then (|f| f.shown (&key, now)) .or_else (|| gamma::get_ramp_for_key (&key))?;
+            Some ( FadeTarget { key, from, to } )
+        } ) .collect();
+        *fade = Some ( Fade { targets, start: now, duration } );
+        self.gen.fetch_add (1, Ordering::AcqRel);
+        self.wake.notify_one();
+    }
+
+    pub(super) fn cancel (&self) {
+        let mut fade = self.fade.lock().unwrap();
+        if fade.take().is_some() { self.gen.fetch_add (1, Ordering::AcqRel); }
+    }
+
+    pub(super) fn is_fading (&self) -> bool {
+        self.fade.lock().unwrap() .is_some()
+    }
+
+    /// Whether the fade that completed as the given gen is still the latest (ie nothing started or cancelled since)
+    fn is_done_as (&self, gen: usize) -> bool {
+        let fade = self.fade.lock().unwrap();
+        fade.is_none() && self.gen.load (Ordering::Acquire) == gen
+    }
+}
+
+
+
+impl WinDusky {
+
+    /// Once a fade completes, we set the exact final state (from the dusky thread, and only if nothing superseded the fade)
+    pub(super) fn handle_gamma_fade_done (&self, gen: usize) {
+        if GammaFader::get() .is_some_and (|f| f.is_done_as (gen)) {
+            self.update_gamma_state();
+        }
+    }
+
+    /// Like update_gamma_state, but fades to the new state (per the configured fade duration)
+    pub(super) fn fade_gamma_state (&self) {
+        let duration = self.conf.get_gamma_fade_duration();
+        if duration.is_zero() || self.fs_overlay.has_gamma_fallback() {
+            // ^^ w the color-matrix fallback, there are no working ramps to fade
+            return self.update_gamma_state()
+        }
+        let targets = self.calc_gamma_target_ramps();
+        info! ("Fading gamma over {:?} for : {:?}", duration, targets .iter() .map (|(k, _)| k) .collect::<Vec<_>>());
+        GammaFader::instance() .start (targets, duration);
+    }
+
+    /// The ramps the current gamma state would have us apply, per monitor (or for the screen DC if we cant enumerate monitors)
+    fn calc_gamma_target_ramps (&self) -> Vec <(String, GammaRamp)> {
+        let monitors = self.sync_gamma_monitors();
+        let (global_active, global_spec) = (self.gamma_active.is_set(), self.get_global_gamma_spec());
+        let ramp_for = |key: &str, active: bool, spec: &GammaPresetSpec| {
+            if active { gamma::calc_acceptable_ramp (&spec.gbc, spec.color_temp, spec.tint) .0 } else { gamma::get_reset_ramp (key) }
+        };
+        if monitors.is_empty() {
+            return vec! [ (SCREEN_DC_KEY.to_string(), ramp_for (SCREEN_DC_KEY, global_active, &global_spec.0)) ]
+        }
+        monitors .iter() .map (|mon| {
+            let (spec, _) = Self::get_monitor_gamma_spec (mon, &global_spec);
+            (mon.device.clone(), ramp_for (&mon.device, mon.effective_active (global_active), &spec))
+        } ) .collect()
+    }
+
+}
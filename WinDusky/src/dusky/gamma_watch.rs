@@ -0,0 +1,146 @@
+#![allow (non_snake_case)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{GetLastError, ERROR_CLASS_ALREADY_EXISTS, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::dusky::WinDusky;
+use crate::dusky::gamma_fade::GammaFader;
+use crate::tray;
+use crate::win_utils::wide_string;
+
+
+
+// Gamma ramps get reset from under us all the time .. by sleep/resume, display hot-plugs, UAC prompts, full-screen
+// games, other color tools etc. So we watch for display-change and power-resume broadcasts (which need a top-level
+// window to receive them), and also poll slowly (for the cases that dont broadcast anything), and re-apply on drift.
+
+
+
+const WATCH_WINDOW_CLASS_NAME : &str = "WinDuskyGammaWatchWindowClass";
+const WATCH_WINDOW_TITLE      : &str = "WinDusky Gamma Watch";
+
+/// Drivers often reset ramps a little after the event itself, so we give things a bit to settle before checking
+const DISPLAY_EVENT_SETTLE : Duration = Duration::from_millis (2000);
+
+/// While the color-matrix fallback is in use, hardware ramps get retried on every this many checks
+const FALLBACK_RETRY_INTERVAL : usize = 6;
+
+static FALLBACK_CHECKS : AtomicUsize = AtomicUsize::new (0);
+
+
+
+extern "system" fn watch_window_proc (hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT { unsafe {
+    match msg {
+        WM_DISPLAYCHANGE => {
+            request_settled_gamma_check ("display change");
+        }
+        WM_POWERBROADCAST if matches! (wparam.0 as u32, PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND) => {
+            request_settled_gamma_check ("power resume");
+        }
+        _ => { }
+    }
+    DefWindowProcW (hwnd, msg, wparam, lparam)
+} }
+
+fn request_settled_gamma_check (reason: &'static str) {
+    info! ("Gamma watchdog : got {reason} event .. will check gamma ramps shortly");
+    thread::spawn (|| {
+        thread::sleep (DISPLAY_EVENT_SETTLE);
+        WinDusky::instance() .post_req__gamma_check();
+    } );
+}
+
+
+unsafe fn create_watch_window () -> Result <(), String> {
+
+    let Ok(instance) = GetModuleHandleW(None) else {
+        return Err (format!("GetModuleHandleW failed with error: {:?}", GetLastError()));
+    };
+
+    let class_name = wide_string (WATCH_WINDOW_CLASS_NAME);
+    let wc = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpfnWndProc: Some(watch_window_proc),
+        hInstance: instance.into(),
+        lpszClassName: PCWSTR::from_raw (class_name.as_ptr()),
+        ..Default::default()
+    };
+    if RegisterClassExW (&wc) == 0 && GetLastError() != ERROR_CLASS_ALREADY_EXISTS {
+        return Err (format!("RegisterClassExW (Gamma Watch) failed with error: {:?}", GetLastError()));
+    }
+
+    // note that this cant be a message-only window, as those dont get broadcasts .. so its just a never-shown top-level
+    CreateWindowExW (
+        WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+        PCWSTR::from_raw (class_name.as_ptr()),
+        PCWSTR::from_raw (wide_string (WATCH_WINDOW_TITLE).as_ptr()),
+        WS_POPUP, 0, 0, 0, 0, None, None, Some(instance.into()), None,
+    ) .map_err (|e| format! ("CreateWindowExW (Gamma Watch) failed with error: {:?}", e))?;
+
+    Ok(())
+}
+
+
+
+impl WinDusky {
+
+    pub(super) fn start_gamma_watchdog (&self) {
+        if !self.conf.check_flag__gamma_watchdog_enabled() { return }
+
+        if let Err(e) = unsafe { create_watch_window() } {
+            warn! ("Gamma watchdog won't get display/power events : {e}");
+        }
+        let poll_secs = self.conf.get_gamma_watchdog_poll_secs();
+        if poll_secs > 0 {
+            thread::spawn (move || loop {
+                thread::sleep (Duration::from_secs (poll_secs as u64));
+                WinDusky::instance() .post_req__gamma_check();
+            } );
+        }
+        info! ("Started gamma watchdog (polling every {poll_secs}s)");
+    }
+
+    /// Re-applies gamma if the actual ramps have drifted from what we expect to have applied
+    pub(super) fn check_gamma_drift (&self) {
+        self.sync_gamma_monitors();
+        // ^^ picks up any hot-plugged monitors (which would then show up as drifted, if gamma is active for them)
+        if !self.check_any_gamma_active() || self.gamma_drift_unfixable.is_set() { return }
+        if self.fs_overlay.has_gamma_fallback() { return self.retry_gamma_ramps() }
+        // ^^ ramps couldnt be set at all, and the color-matrix fallback is in use instead .. so nothing to drift
+        if GammaFader::get() .is_some_and (|f| f.is_fading()) { return }
+        if self.check_active_gamma_preset_match() != Some (false) { return }
+
+        let n_reapplied = self.gamma_reapplied.fetch_add (1, Ordering::AcqRel) + 1;
+        warn! ("Gamma watchdog : ramps have drifted from the active preset .. re-applying (re-application #{n_reapplied})");
+        self.apply_gamma_state (false);
+        // ^^ only the active monitors get checked for drift, so we'll leave the others' ramps alone
+
+        if self.check_active_gamma_preset_match() == Some (false) {
+            // some drivers adjust ramps as they're set, so they'll never read back exactly .. no point fighting that
+            warn! ("Gamma watchdog : ramps still dont match after re-applying .. pausing drift checks until gamma is next changed");
+            self.gamma_drift_unfixable.set();
+        }
+        tray::update_tray__gamma_watchdog (n_reapplied);
+    }
+
+    /// Ramps can start working again (e.g. after driver updates, or monitor changes), so while the color-matrix fallback
+    /// is in use, we retry them every so often .. and the fallback gets dropped once they do work
+    fn retry_gamma_ramps (&self) {
+        if FALLBACK_CHECKS .fetch_add (1, Ordering::AcqRel) % FALLBACK_RETRY_INTERVAL != FALLBACK_RETRY_INTERVAL - 1 { return }
+        if GammaFader::get() .is_some_and (|f| f.is_fading()) { return }
+
+        info! ("Gamma watchdog : retrying hardware gamma ramps (the color-matrix fallback is in use)");
+        self.apply_gamma_state (false);
+        if !self.fs_overlay.has_gamma_fallback() {
+            info! ("Gamma watchdog : hardware gamma ramps work again .. dropped the color-matrix fallback");
+        }
+    }
+
+}
@@ -3,6 +3,7 @@
 use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
 use windows::Win32::UI::Accessibility::{SetWinEventHook, HWINEVENTHOOK};
 use windows::Win32::UI::WindowsAndMessaging::{CallNextHookEx, SetWindowsHookExW, HC_ACTION, WH_MOUSE_LL, WM_MOUSEMOVE};
+use crate::auto::RulesResult;
 use crate::dusky::WinDusky;
 use crate::types::Hwnd;
 use crate::win_utils;
@@ -34,6 +35,8 @@ impl WinDusky {
 
                 0x800B : EVENT_OBJECT_LOCATIONCHANGE
                 // ^^ needed if want overlay to try keeping sync during window drag .. but will be laggy still
+                0x800C : EVENT_OBJECT_NAMECHANGE
+                // ^^ for re-checking auto-overlay title rules (e.g. as browser titles follow the active tab)
 
                 0x8017 : EVENT_OBJECT_CLOAKED
                 0x8018 : EVENT_OBJECT_UNCLOAKED
@@ -58,7 +61,7 @@ impl WinDusky {
         let _ = SetWinEventHook (0x0016, 0x0017, None, Some(win_event_proc), 0, 0, 0 );
 
         let _ = SetWinEventHook (0x8000, 0x8003, None, Some(win_event_proc), 0, 0, 0 );
-        let _ = SetWinEventHook (0x800B, 0x800B, None, Some(win_event_proc), 0, 0, 0 );
+        let _ = SetWinEventHook (0x800B, 0x800C, None, Some(win_event_proc), 0, 0, 0 );
         let _ = SetWinEventHook (0x8017, 0x8018, None, Some(win_event_proc), 0, 0, 0 );
 
     } }
@@ -90,6 +93,9 @@ impl WinDusky {
                 if self.overlays .read().unwrap() .contains_key (&hwnd) {
                     self.remove_overlay (hwnd)
                 }
+                if event == EVENT_OBJECT_DESTROY {
+                    self.auto.forget_hwnd (hwnd);
+                }
                 self.occl_marked.set();
             }
 
@@ -120,6 +126,18 @@ impl WinDusky {
                 self.auto.handle_auto_overlay (hwnd, self);
             }
 
+            EVENT_OBJECT_NAMECHANGE => {
+                // title changes can flip title based auto-overlay rules (e.g. a browser switching to a light or dark site)
+                if let Some (RulesResult { enabled, effect, .. }) = self.auto.re_check_title_rules (hwnd) {
+                    let has_overlay = self.has_overlay (&hwnd);
+                    if enabled && !has_overlay {
+                        self.create_overlay (hwnd, effect.unwrap_or (self.effects.default))
+                    } else if !enabled && has_overlay {
+                        self.fade_out_overlay (hwnd)
+                    }
+                }
+            }
+
             EVENT_SYSTEM_MINIMIZESTART | EVENT_SYSTEM_MINIMIZEEND | EVENT_SYSTEM_MOVESIZESTART | EVENT_SYSTEM_MOVESIZEEND |
             EVENT_OBJECT_CREATE | EVENT_OBJECT_SHOW | EVENT_OBJECT_UNCLOAKED | EVENT_OBJECT_LOCATIONCHANGE =>
             {
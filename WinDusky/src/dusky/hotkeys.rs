@@ -1,5 +1,7 @@
 
 
+use std::thread;
+
 use tracing::{error, info};
 
 use windows::Win32::Foundation::GetLastError;
@@ -7,6 +9,8 @@ use windows::Win32::UI::Input::KeyboardAndMouse::{RegisterHotKey, UnregisterHotK
 
 use crate::config::HotKey;
 use crate::dusky::WinDusky;
+use crate::effects::ColorEffect;
+use crate::gamma::tune::TuneParam;
 
 const HOTKEY_ID__FULLSCREEN_TOGGLE : usize = 1;
 
@@ -25,8 +29,28 @@ const HOTKEY_ID__MAG_LEVEL_TOGGLE : usize = 10;
 const HOTKEY_ID__MAG_LEVEL_NEXT   : usize = 11;
 const HOTKEY_ID__MAG_LEVEL_PREV   : usize = 12;
 
+const HOTKEY_ID__EFFECT_INTENSITY_UP   : usize = 13;
+const HOTKEY_ID__EFFECT_INTENSITY_DOWN : usize = 14;
+
+const HOTKEY_ID__MONITOR_GAMMA_TOGGLE : usize = 15;
+const HOTKEY_ID__MONITOR_GAMMA_NEXT   : usize = 16;
+const HOTKEY_ID__MONITOR_GAMMA_PREV   : usize = 17;
+
+const HOTKEY_ID__GAMMA_BRIGHTNESS_UP   : usize = 18;
+const HOTKEY_ID__GAMMA_BRIGHTNESS_DOWN : usize = 19;
+const HOTKEY_ID__GAMMA_CONTRAST_UP     : usize = 20;
+const HOTKEY_ID__GAMMA_CONTRAST_DOWN   : usize = 21;
+const HOTKEY_ID__GAMMA_GAMMA_UP        : usize = 22;
+const HOTKEY_ID__GAMMA_GAMMA_DOWN      : usize = 23;
+const HOTKEY_ID__GAMMA_TEMP_UP         : usize = 24;
+const HOTKEY_ID__GAMMA_TEMP_DOWN       : usize = 25;
+const HOTKEY_ID__GAMMA_SAVE_PRESET     : usize = 26;
+
+const HOTKEY_ID__LEARN_RULE    : usize = 27;
+const HOTKEY_ID__EXPLAIN_RULES : usize = 28;
+
 
-const HOTKEY_ID_MAX_REGISTERED : usize = HOTKEY_ID__MAG_LEVEL_PREV;
+const HOTKEY_ID_MAX_REGISTERED : usize = HOTKEY_ID__EXPLAIN_RULES;
 
 
 
@@ -46,13 +70,32 @@ impl WinDusky {
         self.conf.get_hotkey__next_effect() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__NEXT_EFFECT as _));
         self.conf.get_hotkey__prev_effect() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__PREV_EFFECT as _));
 
+        self.conf.get_hotkey__effect_intensity_up()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__EFFECT_INTENSITY_UP as _));
+        self.conf.get_hotkey__effect_intensity_down() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__EFFECT_INTENSITY_DOWN as _));
+
         self.conf.get_hotkey__clear_overlays()  .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__CLEAR_OVERLAYS as _));
         self.conf.get_hotkey__clear_overrides() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__CLEAR_OVERRIDES as _));
+        self.conf.get_hotkey__learn_rule()      .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__LEARN_RULE as _));
+        self.conf.get_hotkey__explain_rules()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__EXPLAIN_RULES as _));
 
         self.conf.get_hotkey__gamma_preset_toggle() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_PRESET_TOGGLE as _));
         self.conf.get_hotkey__next_gamma_preset()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_PRESET_NEXT as _));
         self.conf.get_hotkey__prev_gamma_preset()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_PRESET_PREV as _));
 
+        self.conf.get_hotkey__monitor_gamma_toggle()      .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MONITOR_GAMMA_TOGGLE as _));
+        self.conf.get_hotkey__next_monitor_gamma_preset() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MONITOR_GAMMA_NEXT as _));
+        self.conf.get_hotkey__prev_monitor_gamma_preset() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MONITOR_GAMMA_PREV as _));
+
+        self.conf.get_hotkey__gamma_brightness_up()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_BRIGHTNESS_UP as _));
+        self.conf.get_hotkey__gamma_brightness_down() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_BRIGHTNESS_DOWN as _));
+        self.conf.get_hotkey__gamma_contrast_up()     .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_CONTRAST_UP as _));
+        self.conf.get_hotkey__gamma_contrast_down()   .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_CONTRAST_DOWN as _));
+        self.conf.get_hotkey__gamma_gamma_up()        .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_GAMMA_UP as _));
+        self.conf.get_hotkey__gamma_gamma_down()      .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_GAMMA_DOWN as _));
+        self.conf.get_hotkey__gamma_temp_up()         .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_TEMP_UP as _));
+        self.conf.get_hotkey__gamma_temp_down()       .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_TEMP_DOWN as _));
+        self.conf.get_hotkey__gamma_save_preset()     .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__GAMMA_SAVE_PRESET as _));
+
         self.conf.get_hotkey__screen_mag_toggle() .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MAG_LEVEL_TOGGLE as _));
         self.conf.get_hotkey__next_mag_level()    .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MAG_LEVEL_NEXT as _));
         self.conf.get_hotkey__prev_mag_level()    .into_iter().for_each (|hk| register_hotkey (hk, HOTKEY_ID__MAG_LEVEL_PREV as _));
@@ -86,6 +129,20 @@ impl WinDusky {
             HOTKEY_ID__GAMMA_PRESET_NEXT   => { self.cycle_gamma_preset (true); }
             HOTKEY_ID__GAMMA_PRESET_PREV   => { self.cycle_gamma_preset (false); }
 
+            HOTKEY_ID__MONITOR_GAMMA_TOGGLE => { self.toggle_monitor_gamma (None); }
+            HOTKEY_ID__MONITOR_GAMMA_NEXT   => { self.cycle_monitor_gamma_preset (true); }
+            HOTKEY_ID__MONITOR_GAMMA_PREV   => { self.cycle_monitor_gamma_preset (false); }
+
+            HOTKEY_ID__GAMMA_BRIGHTNESS_UP   => { self.nudge_gamma (TuneParam::Brightness, true); }
+            HOTKEY_ID__GAMMA_BRIGHTNESS_DOWN => { self.nudge_gamma (TuneParam::Brightness, false); }
+            HOTKEY_ID__GAMMA_CONTRAST_UP     => { self.nudge_gamma (TuneParam::Contrast, true); }
+            HOTKEY_ID__GAMMA_CONTRAST_DOWN   => { self.nudge_gamma (TuneParam::Contrast, false); }
+            HOTKEY_ID__GAMMA_GAMMA_UP        => { self.nudge_gamma (TuneParam::Gamma, true); }
+            HOTKEY_ID__GAMMA_GAMMA_DOWN      => { self.nudge_gamma (TuneParam::Gamma, false); }
+            HOTKEY_ID__GAMMA_TEMP_UP         => { self.nudge_gamma (TuneParam::ColorTemp, true); }
+            HOTKEY_ID__GAMMA_TEMP_DOWN       => { self.nudge_gamma (TuneParam::ColorTemp, false); }
+            HOTKEY_ID__GAMMA_SAVE_PRESET     => { self.save_tuned_gamma_preset(); }
+
             HOTKEY_ID__MAG_LEVEL_TOGGLE => { self.toggle_mag_overlay(); }
             HOTKEY_ID__MAG_LEVEL_NEXT   => { self.cycle_mag_level (true); }
             HOTKEY_ID__MAG_LEVEL_PREV   => { self.cycle_mag_level (false); }
@@ -100,6 +157,8 @@ impl WinDusky {
                 HOTKEY_ID__EFFECT_TOGGLE  => { self.toggle_full_screen_effect(); }
                 HOTKEY_ID__NEXT_EFFECT    => { self.cycle_full_screen_effect (true); }
                 HOTKEY_ID__PREV_EFFECT    => { self.cycle_full_screen_effect (false); }
+                HOTKEY_ID__EFFECT_INTENSITY_UP   => { self.step_full_screen_effect_intensity (true); }
+                HOTKEY_ID__EFFECT_INTENSITY_DOWN => { self.step_full_screen_effect_intensity (false); }
                 HOTKEY_ID__CLEAR_OVERLAYS => { self.clear_full_screen_effect(); }
                 _ => {}
             }
@@ -125,30 +184,64 @@ impl WinDusky {
         // and finally we have can process the per-hwnd hotkeys that need a hwnd target
         match hotkey {
             HOTKEY_ID__EFFECT_TOGGLE => {
-                if self .overlays .read().unwrap() .contains_key (&target) {
-                    self.remove_overlay (target);
+                let fading = self .overlays .read().unwrap() .get (&target) .map (|ov| ov.fading.is_set());
+                if fading == Some (true) {
+                    // toggling back on while an overlay was still fading out just brings it back
+                    if let Some(overlay) = self.overlays .read().unwrap() .get (&target) { overlay.cancel_fade_out(); }
+                    self.auto.register_user_applied (target);
+                } else if fading.is_some() {
+                    self.fade_out_overlay (target);
                     self.auto.register_user_unapplied (target);
                 } else {
                     // if there was some effect for it in eval cache, we'll use that or the overlay
                     // (e.g. this would preserve last effect when the overlay might have been last toggled on/off)
                     // however, if we're toggling on, we dont want to toggle to nothing .. so we'll find some default
                     let effect = self.auto.check_rule_cached (target) .and_then (|r| r.effect)
+                        .or_else (|| self.auto.find_remembered_effect (target))
                         .filter (|eff| !eff.is_identity()) .unwrap_or (self.effects.default);
                     self.create_overlay (target, effect);
+                    self.auto.register_user_applied (target);
                 }
             }
             HOTKEY_ID__NEXT_EFFECT => {
                 if let Some(overlay) = self.overlays .read().unwrap() .get (&target) {
+                    let was_fading = overlay.fading.is_set();
                     let effect = overlay.apply_effect_next();
                     self.auto.update_cached_rule_result_effect (target, effect);
+                    if was_fading { self.auto.register_user_applied (target); }
+                    // ^^ changing the effect mid fade-out keeps the overlay on, so that undoes the toggle off
                 }
             }
             HOTKEY_ID__PREV_EFFECT => {
                 if let Some(overlay) = self.overlays .read().unwrap() .get (&target) {
+                    let was_fading = overlay.fading.is_set();
                     let effect = overlay.apply_effect_prev();
                     self.auto.update_cached_rule_result_effect (target, effect);
+                    if was_fading { self.auto.register_user_applied (target); }
+                }
+            }
+            HOTKEY_ID__EFFECT_INTENSITY_UP | HOTKEY_ID__EFFECT_INTENSITY_DOWN => {
+                if let Some(overlay) = self.overlays .read().unwrap() .get (&target) {
+                    let was_fading = overlay.fading.is_set();
+                    let effect = overlay.apply_intensity_stepped (hotkey == HOTKEY_ID__EFFECT_INTENSITY_UP);
+                    self.auto.update_cached_rule_result_effect (target, effect);
+                    if was_fading { self.auto.register_user_applied (target); }
+                    // ^^ the intensity gets remembered along with the effect, so toggling the overlay back on keeps it
+                }
+            }
+            HOTKEY_ID__LEARN_RULE => {
+                // we learn the overlay's current effect, or an exclusion if it has none (or is fading out after a toggle off)
+                let effect = self.overlays .read().unwrap() .get (&target) .filter (|ov| ov.fading.is_clear()) .map (|ov| ColorEffect::from (&ov.effect));
+                match self.auto.learn_rule (target, effect, self.conf, self.effects) {
+                    Ok (rule) => info! ("Learned auto-overlay rule from {:?} into config : {}", target, rule),
+                    Err (e) => error! ("Failed to learn auto-overlay rule from {:?} : {e}", target),
                 }
             }
+            HOTKEY_ID__EXPLAIN_RULES => {
+                // explaining might need a screen capture for luminance, so we'll do it off the msg-loop thread
+                let auto = self.auto;
+                thread::spawn ( move || info! ("{}", auto.explain_window (target) .join ("\n    ")) );
+            }
             _ => { }
         }
     }
@@ -1,4 +1,5 @@
 
+use std::time::Instant;
 use tracing::{error, info};
 
 use windows::core::PCWSTR;
@@ -10,7 +11,8 @@ use windows::Win32::UI::Magnification::{MagSetColorEffect, MagSetWindowSource, M
 use windows::Win32::UI::WindowsAndMessaging::*;
 
 use crate::dusky::WinDusky;
-use crate::effects::{ColorEffect, ColorEffectAtomic};
+use crate::effects::transition::EffectAnimator;
+use crate::effects::{ColorEffect, ColorEffectAtomic, ColorEffects, COLOR_EFF__IDENTITY};
 use crate::occlusion::Rect;
 use crate::types::{Flag, Hwnd};
 use crate::win_utils::*;
@@ -74,9 +76,12 @@ pub struct Overlay {
     pub target : Hwnd,
 
     pub effect : ColorEffectAtomic,
+    pub anim   : EffectAnimator,
 
     pub is_top : Flag,
     pub marked : Flag,
+    pub fading : Flag,
+    // ^^ set when toggled off with transitions enabled .. we fade to identity first, and get removed once that completes
 
     pub viz_bounds : Option<Rect>
 }
@@ -117,12 +122,15 @@ impl Overlay {
             mag    : mag.into(),
             target,
             effect : ColorEffectAtomic::new (effect),
+            anim   : EffectAnimator::default(),
             is_top : Flag::new(false),
             marked : Flag::new(false),
+            fading : Flag::new(false),
             viz_bounds : None,
         };
 
         // we'll apply the default smart inversion color-effect .. can ofc be cycled through via hotkeys later
+        // (if transitions are enabled, the animator starts from identity, so new overlays fade in)
         overlay.apply_color_effect (overlay.effect.get());
 
         // we'll mark the overlay which will make our main loop timer-handler sync dimensions and position with the target
@@ -198,6 +206,9 @@ impl Overlay {
 
 
     pub(super) fn refresh (&self, wd: &WinDusky) { unsafe {
+        if let Some (matrix) = self.anim.step (Instant::now()) {
+            self.set_mag_color_effect (matrix.into());
+        }
         if self.marked.is_set() {
             // if we were marked for update, we'll update then invalidate our full rect
             self.update(wd);
@@ -227,18 +238,50 @@ impl Overlay {
     } }
 
 
-    fn apply_color_effect (&self, effect: MAGCOLOREFFECT) { unsafe {
+    fn apply_color_effect (&self, effect: MAGCOLOREFFECT) {
+        // with transitions enabled, this only sets the target .. the timer-driven refresh then steps us there
+        let matrix = self.anim.start (effect.into(), ColorEffects::instance().transition, Instant::now());
+        self.set_mag_color_effect (matrix.into());
+    }
+    fn set_mag_color_effect (&self, effect: MAGCOLOREFFECT) { unsafe {
         if ! MagSetColorEffect (self.mag.into(), &effect as *const _ as _) .as_bool() {
             error! ("Setting Color Effect failed with error: {:?}", GetLastError());
         }
     } }
+
+    /// Starts fading the overlay out to identity (the overlay should be removed once that completes)
+    pub(super) fn begin_fade_out (&self) {
+        info! ("Fading out overlay on {:?}", self.target);
+        self.fading.set();
+        self.apply_color_effect (COLOR_EFF__IDENTITY);
+    }
+    /// Reverses an in-progress fade-out, transitioning back to the overlay effect
+    pub(super) fn cancel_fade_out (&self) {
+        info! ("Cancelling fade out of overlay on {:?}", self.target);
+        self.fading.clear();
+        self.apply_color_effect (self.effect.get());
+    }
+    pub(super) fn is_faded_out (&self) -> bool {
+        self.fading.is_set() && !self.anim.is_animating()
+    }
+
     fn apply_effect_cycled (&self, forward: bool) -> ColorEffect {
+        self.fading.clear();
+        // ^^ changing the effect of an overlay that was fading out retargets the fade, so it should stay on after all
         let effect = self.effect.cycle (forward);
-        info! ("Setting Color Effect on {:?} to : {:?}", self.target, effect.name());
+        info! ("Setting Color Effect on {:?} to : {:?}", self.target, effect.label());
         self.apply_color_effect (effect.get());
         effect
     }
     pub(super) fn apply_effect_next (&self) -> ColorEffect { self.apply_effect_cycled (true) }
     pub(super) fn apply_effect_prev (&self) -> ColorEffect { self.apply_effect_cycled (false) }
 
+    pub(super) fn apply_intensity_stepped (&self, up: bool) -> ColorEffect {
+        self.fading.clear();
+        let effect = self.effect.step_intensity (up);
+        info! ("Setting Color Effect on {:?} to : {:?}", self.target, effect.label());
+        self.apply_color_effect (effect.get());
+        effect
+    }
+
 }
@@ -1,10 +1,12 @@
 
 
-use std::sync::OnceLock;
+use std::sync::{OnceLock, RwLock};
+use std::time::Instant;
 use tracing::{error, info};
 use windows::Win32::Foundation::GetLastError;
 use windows::Win32::UI::Magnification::{MagSetFullscreenColorEffect, MAGCOLOREFFECT};
-use crate::effects::{ColorEffect, ColorEffectAtomic, ColorEffects, COLOR_EFF__IDENTITY};
+use crate::effects::transition::EffectAnimator;
+use crate::effects::{ColorEffect, ColorEffectAtomic, ColorEffects, ColorMatrix, COLOR_EFF__IDENTITY};
 use crate::types::Flag;
 
 
@@ -15,6 +17,9 @@ pub struct FullScreenOverlay {
     pub enabled : Flag,
     pub active  : Flag,
     pub effect  : ColorEffectAtomic,
+    pub anim    : EffectAnimator,
+    pub gamma_fallback : RwLock <Option <ColorMatrix>>,
+    // ^^ gamma presets as a color matrix, for when hardware gamma ramps cant be set .. applied after any effect
 }
 
 
@@ -28,6 +33,8 @@ impl FullScreenOverlay {
                 enabled : Flag::default(),
                 active  : Flag::default(),
                 effect  : ColorEffectAtomic::new (ColorEffects::instance().default),
+                anim    : EffectAnimator::default(),
+                gamma_fallback : RwLock::new (None),
             }
         )
     }
@@ -76,20 +83,57 @@ impl FullScreenOverlay {
         Some (prior)
     }
 
-    fn apply_color_effect (&self, effect: MAGCOLOREFFECT) { unsafe {
+    fn apply_color_effect (&self, effect: MAGCOLOREFFECT) {
+        // with transitions enabled, this only sets the target .. the timer-driven step_transition then steps us there
+        let matrix = self.anim.start (effect.into(), ColorEffects::instance().transition, Instant::now());
+        self.set_mag_color_effect (matrix.into());
+    }
+    fn set_mag_color_effect (&self, effect: MAGCOLOREFFECT) { unsafe {
+        // any gamma fallback goes on top of the effect (as hardware gamma would, it being applied at the display output)
+        let effect = match *self.gamma_fallback.read().unwrap() {
+            Some (gamma) => ColorMatrix::from (effect) .then (&gamma) .into(),
+            None => effect,
+        };
         if ! MagSetFullscreenColorEffect (&effect) .as_bool() {
             error! ("Error settting Fullscreen Color Effect : {:?}", GetLastError());
         }
     } }
 
+    /// Sets (or clears) the gamma fallback matrix, and re-applies whatever effect is currently shown along with it
+    pub(super) fn set_gamma_fallback (&self, gamma: Option <ColorMatrix>) {
+        let prior = std::mem::replace (&mut *self.gamma_fallback.write().unwrap(), gamma);
+        if prior == gamma { return }
+        info! ("Setting Full Screen gamma fallback matrix to : {:?}", gamma);
+        self.set_mag_color_effect (self.anim.shown().into());
+    }
+    pub(super) fn has_gamma_fallback (&self) -> bool {
+        self.gamma_fallback.read().unwrap() .is_some()
+    }
+
+    pub(super) fn step_transition (&self) {
+        if let Some (matrix) = self.anim.step (Instant::now()) {
+            self.set_mag_color_effect (matrix.into());
+        }
+    }
+    pub(super) fn in_transition (&self) -> bool {
+        self.anim.is_animating()
+    }
+
     pub(super) fn apply_effect_cycled (&self, forward: Option<bool>) -> ColorEffect {
         let effect = if let Some(forward) = forward { self.effect.cycle (forward) } else { (&self.effect).into() };
-        info! ("Setting Full Screen Overlay Color Effect to : {:?}", effect.name());
+        info! ("Setting Full Screen Overlay Color Effect to : {:?}", effect.label());
         self.apply_color_effect (effect.get());
         self.active.set();
         effect
     }
 
+    pub(super) fn apply_intensity_stepped (&self, up: bool) -> ColorEffect {
+        let effect = self.effect.step_intensity (up);
+        info! ("Setting Full Screen Overlay Color Effect to : {:?}", effect.label());
+        self.apply_color_effect (effect.get());
+        effect
+    }
+
 }
 
 
@@ -3,11 +3,23 @@ use itertools::Itertools;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{OnceLock};
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 use windows::Win32::UI::Magnification::MAGCOLOREFFECT;
 
 use crate::*;
 
+pub mod compose;
+pub mod cvd;
+pub mod fit;
+pub mod lint;
+pub mod matrix;
+pub mod preview;
+pub mod spec;
+pub mod transition;
+
+pub use matrix::ColorMatrix;
+
 
 
 
@@ -39,6 +51,10 @@ pub struct ColorEffects {
     // ^^ can add back if we need by-name lookup .. for now, we'll just put the data into cycle-order vec itself
     pub cycle_order : Vec <(String, MAGCOLOREFFECT)>,
     pub default     : ColorEffect,
+    pub lint_issues : Vec <lint::LintIssue>,
+    // ^^ diagnostics from analysing the configured effects at load (we'll hold on to them for reporting in tray etc)
+    pub transition  : Duration,
+    // ^^ duration for animated transitions between effects (zero means effect changes just snap as before)
 }
 
 
@@ -54,10 +70,14 @@ impl ColorEffects {
 
     pub fn init (conf: &config::Config) -> &'static ColorEffects {
 
-        // lets load all the color-effects specified in conf first
+        // lets load all the color-effects specified in conf first (compiling any composed ones into transforms)
+        let (specs, malformed) = conf.get_color_effects();
         let mut effects : HashMap <String, MAGCOLOREFFECT> = HashMap::new();
-        for effect in conf.get_color_effects() {
-            let _ = effects .insert ( effect.name, MAGCOLOREFFECT { transform: effect.transform } );
+        for (name, compiled) in compose::compile_effects (&specs) {
+            match compiled {
+                Ok (matrix) => { let _ = effects .insert ( name, matrix.into() ); }
+                Err (e) => { warn! ("Ignoring color-effect {:?} .. {}", name, e); }
+            }
         }
         // if no effects were defined, we'll at least populate with a default (simple inversion)
         if effects.is_empty() {
@@ -80,17 +100,32 @@ impl ColorEffects {
 
         let default_effect = &conf.get_effects_default();
         let default_id = cycle_order .iter().find_position (|(s,_)| s == default_effect) .map (|(idx, _)| idx) .unwrap_or(0);
-        let default = ColorEffect (default_id);
+        let default = ColorEffect::new (default_id);
         info! ("loaded default color-effect as : {:?}", (default, &default_effect));
 
-        COLOR_EFFECTS .get_or_init ( || ColorEffects { cycle_order, default } )
+        // finally we'll run diagnostics on the loaded effects, and log any problems found
+        let lint_issues = lint::lint_effects (&specs, &malformed, &conf.get_effects_cycle_order());
+        for issue in lint_issues.iter() {
+            if issue.level == lint::LintLevel::Note { info! ("color-effects check : {}", issue) }
+            else { warn! ("color-effects check : {}", issue) }
+        }
+
+        let transition = Duration::from_millis (conf.get_effects_transition__duration_ms() as u64);
+        info! ("color-effects transition duration : {:?}", transition);
+
+        COLOR_EFFECTS .get_or_init ( || ColorEffects { cycle_order, default, lint_issues, transition } )
 
     }
 
+    /// Count of effect diagnostics that are worth user attention (i.e. warnings or errors, but not notes)
+    pub fn lint_problems_count (&self) -> usize {
+        self.lint_issues .iter() .filter (|i| i.level > lint::LintLevel::Note) .count()
+    }
+
     pub fn find_by_name (&self, name: &str) -> ColorEffect {
         // if the conf specifies a valid default effect, we'll use that, else we'll use the first entry in cycle order
         let idx = self.cycle_order .iter() .find_position (|(s,_)| s == name) .map (|(idx, _)| idx) .unwrap_or_default();
-        ColorEffect (idx)
+        ColorEffect::new (idx)
     }
 
 }
@@ -99,22 +134,78 @@ impl ColorEffects {
 
 
 
+impl From <ColorMatrix> for MAGCOLOREFFECT {
+    fn from (m: ColorMatrix) -> Self { MAGCOLOREFFECT { transform: m.0 } }
+}
+impl From <MAGCOLOREFFECT> for ColorMatrix {
+    fn from (m: MAGCOLOREFFECT) -> Self { ColorMatrix (m.transform) }
+}
+
+
+
+
+
+/// Effect intensity as a percentage .. the applied matrix is a linear blend from identity (0%) to the full effect (100%)
+#[derive (Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EffectIntensity (pub u8);
+
+impl EffectIntensity {
+    pub const FULL : EffectIntensity = EffectIntensity (100);
+    pub const STEP : u8 = 10;
+    pub const MIN  : u8 = 10;
+    // ^^ we wont go all the way down to zero, as an overlay doing nothing at all is better just toggled off
+
+    pub fn stepped (&self, up: bool) -> EffectIntensity {
+        let pct = if up { self.0.saturating_add (Self::STEP) } else { self.0.saturating_sub (Self::STEP) };
+        EffectIntensity (pct .clamp (Self::MIN, Self::FULL.0))
+    }
+    pub fn is_full (&self) -> bool { self.0 >= Self::FULL.0 }
+    pub fn fraction (&self) -> f32 { self.0.min (Self::FULL.0) as f32 / 100.0 }
+}
+
+impl Default for EffectIntensity {
+    fn default() -> Self { EffectIntensity::FULL }
+}
+
+
+
+/// The effect selection (an index into cycle-order) along with the intensity it is to be applied at
 #[derive (Debug, Default, Copy, Clone, PartialEq, Eq)]
-pub struct ColorEffect (pub usize);
+pub struct ColorEffect (pub usize, pub EffectIntensity);
 
 #[derive (Debug, Default)]
 pub struct ColorEffectAtomic (AtomicUsize);
+// ^^ we'll pack both the idx and intensity into the atomic (see to_bits/from_bits) so updates to the pair stay atomic
 
 
 impl ColorEffect {
+    pub fn new (idx: usize) -> ColorEffect {
+        ColorEffect (idx, EffectIntensity::FULL)
+    }
+    pub fn with_intensity (&self, intensity: EffectIntensity) -> ColorEffect {
+        ColorEffect (self.0, intensity)
+    }
+    pub fn to_bits (self) -> usize {
+        (self.0 << 8) | self.1.0 as usize
+    }
+    pub fn from_bits (bits: usize) -> ColorEffect {
+        ColorEffect (bits >> 8, EffectIntensity ((bits & 0xFF) as u8))
+    }
+
     pub fn get (&self) -> MAGCOLOREFFECT {
         let cycler = &ColorEffects::instance().cycle_order;
-        cycler .get (self.0 % cycler.len()) .map (|(_,v)| *v) .unwrap_or (COLOR_EFF__FALLBACK_DEFAULT)
+        let effect = cycler .get (self.0 % cycler.len()) .map (|(_,v)| *v) .unwrap_or (COLOR_EFF__FALLBACK_DEFAULT);
+        if self.1.is_full() { return effect }
+        ColorMatrix::lerp (&ColorMatrix::IDENTITY, &effect.into(), self.1.fraction()) .into()
     }
     pub fn name (&self) -> &'static str {
         let cycler = &ColorEffects::instance().cycle_order;
         cycler .get (self.0 % cycler.len()) .map (|(s,_)| s.as_str()) .unwrap_or("")
     }
+    /// Effect name for display, along with the intensity if it isnt at full
+    pub fn label (&self) -> String {
+        if self.1.is_full() { self.name().to_string() } else { format! ("{} @ {}%", self.name(), self.1.0) }
+    }
     pub fn is_identity (&self) -> bool {
         self.get() == COLOR_EFF__IDENTITY
     }
@@ -122,17 +213,17 @@ impl ColorEffect {
 
 impl From <&ColorEffectAtomic> for ColorEffect {
     fn from (eff: &ColorEffectAtomic) -> Self {
-        ColorEffect (eff.0 .load(Ordering::Relaxed))
+        ColorEffect::from_bits (eff.0 .load(Ordering::Relaxed))
     }
 }
 
 impl ColorEffectAtomic {
 
     pub fn new (effect : ColorEffect) -> ColorEffectAtomic {
-        ColorEffectAtomic (AtomicUsize::new (effect.0))
+        ColorEffectAtomic (AtomicUsize::new (effect.to_bits()))
     }
     pub fn store (&self, effect : ColorEffect) {
-        self.0.store (effect.0, Ordering::Release);
+        self.0.store (effect.to_bits(), Ordering::Release);
     }
 
     pub fn get (&self) -> MAGCOLOREFFECT {
@@ -140,15 +231,54 @@ impl ColorEffectAtomic {
     }
 
     pub fn cycle (&self, forward: bool) -> ColorEffect {
+        // we cycle the effect idx, but the intensity carries over to the next effect
         let cyc_len = ColorEffects::instance().cycle_order.len();
         let incr = if forward { cyc_len + 1 } else { cyc_len -1 };
-        let update_fn = |cur| Some ((cur + incr) % cyc_len);
-        let prior_idx = self.0.fetch_update (Ordering::AcqRel, Ordering::Acquire, update_fn);
-        let new_idx = update_fn (prior_idx.unwrap_or_else(|e| e)) .unwrap();
-        ColorEffect (new_idx)
+        let update_fn = |cur| {
+            let ColorEffect (idx, intensity) = ColorEffect::from_bits (cur);
+            Some (ColorEffect ((idx + incr) % cyc_len, intensity) .to_bits())
+        };
+        let prior = self.0.fetch_update (Ordering::AcqRel, Ordering::Acquire, update_fn);
+        ColorEffect::from_bits (update_fn (prior.unwrap_or_else(|e| e)) .unwrap())
     }
 
+    pub fn step_intensity (&self, up: bool) -> ColorEffect {
+        let update_fn = |cur| {
+            let effect = ColorEffect::from_bits (cur);
+            Some (effect .with_intensity (effect.1.stepped (up)) .to_bits())
+        };
+        let prior = self.0.fetch_update (Ordering::AcqRel, Ordering::Acquire, update_fn);
+        ColorEffect::from_bits (update_fn (prior.unwrap_or_else(|e| e)) .unwrap())
+    }
 
 }
 
 
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effect_intensity () {
+        assert_eq! (EffectIntensity::default(), EffectIntensity::FULL);
+        assert_eq! (EffectIntensity::FULL.stepped (true), EffectIntensity::FULL);
+        assert_eq! (EffectIntensity (70).stepped (false), EffectIntensity (60));
+        assert_eq! (EffectIntensity (10).stepped (false), EffectIntensity (EffectIntensity::MIN));
+        assert_eq! (EffectIntensity (95).stepped (true), EffectIntensity::FULL);
+        assert_eq! (EffectIntensity (70).fraction(), 0.7);
+    }
+
+    #[test]
+    fn test_color_effect_bits () {
+        for eff in [ColorEffect::new(0), ColorEffect (3, EffectIntensity (70)), ColorEffect (1234, EffectIntensity (10))] {
+            assert_eq! (ColorEffect::from_bits (eff.to_bits()), eff);
+        }
+        let atomic = ColorEffectAtomic::new (ColorEffect (2, EffectIntensity (50)));
+        assert_eq! (atomic.step_intensity (true), ColorEffect (2, EffectIntensity (60)));
+        assert_eq! (ColorEffect::from (&atomic), ColorEffect (2, EffectIntensity (60)));
+    }
+
+}
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::effects::cvd::{self, CvdKind};
+use crate::effects::fit;
+use crate::effects::matrix::ColorMatrix;
+use crate::effects::spec::{ColorEffectDef, ColorEffectSpec};
+
+
+
+// Effects in config can be defined either as raw 25-float transforms, or as a chain of named primitives like :
+//    compose = [ "Smart Inversion V3", "saturate 1.2", "hue-rotate 10" ]
+// .. where each entry is applied in order (left to right), and any entry that isnt a known primitive is a reference to
+// another defined effect (by name). The chain is compiled into a single transform when effects are loaded.
+// Effects whose names start w a primitive's name (e.g. "Grayscale" or "Contrast Boost") can be referenced explicitly
+// as e.g. "effect:Grayscale" instead.
+
+
+
+#[derive (Debug, Clone, PartialEq)]
+pub enum EffectOp {
+    Invert,
+    HueRotate   (f32),
+    Saturate    (f32),
+    Brightness  (f32),
+    Contrast    (f32),
+    Sepia       (f32),
+    Grayscale   (f32),
+    Tint        ([f32; 3]),
+    ChannelSwap ([usize; 3]),
+    Simulate    (CvdKind, f32),
+    Daltonize   (CvdKind, f32),
+    Named       (String),
+}
+
+
+
+fn parse_num (op: &str, arg: Option<&str>) -> Result <f32, String> {
+    let arg = arg .ok_or_else (|| format! ("'{op}' requires a numeric argument"))?;
+    f32::from_str (arg) .map_err (|_| format! ("'{op}' has invalid numeric argument : {arg:?}"))
+}
+
+pub(crate) fn parse_hex_color (s: &str) -> Option <[f32; 3]> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 { return None }
+    let v = u32::from_str_radix (hex, 16) .ok()?;
+    Some ( [(v >> 16) & 0xff, (v >> 8) & 0xff, v & 0xff] .map (|c| c as f32 / 255.0) )
+}
+
+/// The effect name from an explicit reference like 'effect:Grayscale' (if it is one)
+fn explicit_effect_ref (s: &str) -> Option <&str> {
+    let (prefix, name) = s.trim() .split_once (':')?;
+    prefix.trim() .eq_ignore_ascii_case ("effect") .then_some (name.trim())
+}
+
+fn parse_channel_order (s: &str) -> Option <[usize; 3]> {
+    let idxs : Vec<usize> = s .to_ascii_lowercase() .chars() .map (|c| "rgb".find(c)) .collect::<Option<_>>()?;
+    idxs .try_into() .ok()
+}
+
+
+
+impl FromStr for EffectOp {
+    type Err = String;
+
+    fn from_str (s: &str) -> Result <EffectOp, String> {
+        let s = s.trim();
+        if let Some (name) = explicit_effect_ref (s) {
+            if name.is_empty() { return Err (format! ("missing effect name in {s:?}")) }
+            return Ok ( EffectOp::Named (name.to_string()) )
+        }
+        let mut words = s.split_whitespace();
+        let op = words.next() .unwrap_or_default() .to_ascii_lowercase() .replace ('_', "-");
+        let args : Vec<&str> = words.collect();
+        let arg = args.first().copied();
+
+        let parsed = match op.as_str() {
+            "invert"                    => EffectOp::Invert,
+            "hue-rotate" | "hue"        => EffectOp::HueRotate  (parse_num (&op, arg)?),
+            "saturate"   | "saturation" => EffectOp::Saturate   (parse_num (&op, arg)?),
+            "brightness"                => EffectOp::Brightness (parse_num (&op, arg)?),
+            "contrast"                  => EffectOp::Contrast   (parse_num (&op, arg)?),
+            "sepia"                     => EffectOp::Sepia      (arg .map_or (Ok(1.0), |_| parse_num (&op, arg))?),
+            "grayscale"  | "greyscale"  => EffectOp::Grayscale  (arg .map_or (Ok(1.0), |_| parse_num (&op, arg))?),
+            "tint" => {
+                let rgb = match args.as_slice() {
+                    [hex]     => parse_hex_color (hex),
+                    [r, g, b] => [r, g, b] .map (|v| f32::from_str(v).ok()) .into_iter() .collect::<Option<Vec<_>>>()
+                                    .map (|v| [v[0], v[1], v[2]]),
+                    _ => None,
+                };
+                EffectOp::Tint ( rgb .ok_or_else (|| format! ("'tint' expects '#rrggbb' or 'r g b' floats, got : {:?}", args.join(" ")))? )
+            }
+            "channel-swap" | "swap" => {
+                let order = arg .and_then (parse_channel_order);
+                EffectOp::ChannelSwap ( order .ok_or_else (|| format! ("'channel-swap' expects an order like 'bgr', got : {arg:?}"))? )
+            }
+            "simulate" | "daltonize" => {
+                let kind = CvdKind::from_str ( arg .ok_or_else (|| format! ("'{op}' requires a type : protan, deutan or tritan"))? )?;
+                let severity = args.get(1) .map_or (Ok(1.0), |s| parse_num (&op, Some(s)))?;
+                if op == "simulate" { EffectOp::Simulate (kind, severity) } else { EffectOp::Daltonize (kind, severity) }
+            }
+            _ => {
+                if s.is_empty() { return Err ("empty effect op".into()) }
+                return Ok ( EffectOp::Named (s.to_string()) )
+            }
+        };
+        // we'll not silently drop surplus args for primitives (likely a typo or a mis-split effect name)
+        let n_args_max = match parsed {
+            EffectOp::Invert => 0,
+            EffectOp::Tint(_) => 3,
+            EffectOp::Simulate(..) | EffectOp::Daltonize(..) => 2,
+            _ => 1
+        };
+        if args.len() > n_args_max {
+            return Err (format! ("'{op}' given too many arguments : {:?}", args.join(" ")))
+        }
+        Ok (parsed)
+    }
+}
+
+
+impl EffectOp {
+    /// Returns the matrix for primitive ops .. (named references need resolving against other effects instead)
+    pub fn to_matrix (&self) -> Option <ColorMatrix> {
+        match self {
+            EffectOp::Invert           => Some (ColorMatrix::invert()),
+            EffectOp::HueRotate (d)    => Some (ColorMatrix::hue_rotate (*d)),
+            EffectOp::Saturate (s)     => Some (ColorMatrix::saturate (*s)),
+            EffectOp::Brightness (b)   => Some (ColorMatrix::brightness (*b)),
+            EffectOp::Contrast (c)     => Some (ColorMatrix::contrast (*c)),
+            EffectOp::Sepia (a)        => Some (ColorMatrix::sepia (*a)),
+            EffectOp::Grayscale (a)    => Some (ColorMatrix::grayscale (*a)),
+            EffectOp::Tint (rgb)       => Some (ColorMatrix::tint (*rgb)),
+            EffectOp::ChannelSwap (o)  => Some (ColorMatrix::channel_swap (*o)),
+            EffectOp::Simulate (k, s)  => Some (cvd::simulation (*k, *s)),
+            EffectOp::Daltonize (k, s) => Some (cvd::daltonization (*k, *s)),
+            EffectOp::Named (_)        => None,
+        }
+    }
+}
+
+
+
+
+
+struct Resolver <'a> {
+    defs     : HashMap <&'a str, &'a ColorEffectDef>,
+    resolved : HashMap <&'a str, Result <ColorMatrix, String>>,
+    visiting : HashSet <&'a str>,
+}
+
+impl <'a> Resolver <'a> {
+
+    fn resolve (&mut self, name: &'a str) -> Result <ColorMatrix, String> {
+        if let Some (res) = self.resolved.get (name) {
+            return res.clone()
+        }
+        let Some (&def) = self.defs.get (name) else {
+            return Err (format! ("reference to undefined effect {name:?}"))
+        };
+        if !self.visiting.insert (name) {
+            return Err (format! ("cyclic reference involving effect {name:?}"))
+        }
+        let res = match def {
+            ColorEffectDef::Transform (t) => Ok (ColorMatrix (*t)),
+            ColorEffectDef::Compose (ops) => self.compile_chain (ops),
+            ColorEffectDef::Fit (pairs)   => fit::parse_color_pairs (pairs) .and_then (|ps| fit::fit_color_matrix (&ps)) .map (|f| f.matrix),
+        };
+        self.visiting.remove (name);
+        self.resolved.insert (name, res.clone());
+        res
+    }
+
+    fn compile_chain (&mut self, ops: &'a [String]) -> Result <ColorMatrix, String> {
+        let mut acc = ColorMatrix::IDENTITY;
+        for op_str in ops {
+            let op = EffectOp::from_str (op_str)?;
+            let m = match op.to_matrix() {
+                Some (m) => m,
+                None => self.resolve (explicit_effect_ref (op_str) .unwrap_or (op_str.trim()))?,
+            };
+            acc = acc.then (&m);
+        }
+        Ok (acc)
+    }
+}
+
+
+
+/// Compiles all effect specs into transform matrices, resolving composed chains and references between effects. <br>
+/// Results are returned in spec order (with per-effect errors), and for duplicate names, the last definition wins.
+pub fn compile_effects (specs: &[ColorEffectSpec]) -> Vec <(String, Result <ColorMatrix, String>)> {
+    let defs = specs .iter() .map (|s| (s.name.as_str(), &s.def)) .collect();
+    let mut resolver = Resolver { defs, resolved: HashMap::new(), visiting: HashSet::new() };
+    specs .iter() .map (|s| (s.name.clone(), resolver.resolve (&s.name))) .collect()
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_t (name: &str, t: [f32; 25]) -> ColorEffectSpec {
+        ColorEffectSpec { name: name.into(), def: ColorEffectDef::Transform(t) }
+    }
+    fn spec_c (name: &str, ops: &[&str]) -> ColorEffectSpec {
+        ColorEffectSpec { name: name.into(), def: ColorEffectDef::Compose (ops.iter().map(|s| s.to_string()).collect()) }
+    }
+
+    const SMART_INVERSION_V3 : [f32; 25] = [
+         0.39,  -0.62,  -0.62,  0.0,  0.0,
+        -1.21,  -0.22,  -1.22,  0.0,  0.0,
+        -0.16,  -0.16,   0.84,  0.0,  0.0,
+          0.0,    0.0,    0.0,  1.0,  0.0,
+          1.0,    1.0,    1.0,  0.0,  1.0,
+    ];
+
+    #[test]
+    fn test_parse_ops () {
+        assert_eq! (EffectOp::from_str("invert"), Ok (EffectOp::Invert));
+        assert_eq! (EffectOp::from_str(" Hue_Rotate  90 "), Ok (EffectOp::HueRotate(90.0)));
+        assert_eq! (EffectOp::from_str("saturate 1.2"), Ok (EffectOp::Saturate(1.2)));
+        assert_eq! (EffectOp::from_str("sepia"), Ok (EffectOp::Sepia(1.0)));
+        assert_eq! (EffectOp::from_str("grayscale 0.5"), Ok (EffectOp::Grayscale(0.5)));
+        assert_eq! (EffectOp::from_str("tint #ff8000"), Ok (EffectOp::Tint([1.0, 128.0/255.0, 0.0])));
+        assert_eq! (EffectOp::from_str("tint 1 0.9 0.8"), Ok (EffectOp::Tint([1.0, 0.9, 0.8])));
+        assert_eq! (EffectOp::from_str("channel-swap BGR"), Ok (EffectOp::ChannelSwap([2, 1, 0])));
+        assert_eq! (EffectOp::from_str("simulate deuteranopia"), Ok (EffectOp::Simulate(CvdKind::Deutan, 1.0)));
+        assert_eq! (EffectOp::from_str("daltonize protan 0.6"), Ok (EffectOp::Daltonize(CvdKind::Protan, 0.6)));
+        assert_eq! (EffectOp::from_str("Smart Inversion V3"), Ok (EffectOp::Named("Smart Inversion V3".into())));
+        assert_eq! (EffectOp::from_str("Effect: Grayscale"), Ok (EffectOp::Named("Grayscale".into())));
+        assert_eq! (EffectOp::from_str("grayscale"), Ok (EffectOp::Grayscale(1.0)));
+
+        assert! (EffectOp::from_str("saturate").is_err());
+        assert! (EffectOp::from_str("saturate lots").is_err());
+        assert! (EffectOp::from_str("contrast 1 2").is_err());
+        assert! (EffectOp::from_str("tint #12345").is_err());
+        assert! (EffectOp::from_str("channel-swap rgx").is_err());
+        assert! (EffectOp::from_str("simulate").is_err());
+        assert! (EffectOp::from_str("simulate achroma 1.0").is_err());
+        assert! (EffectOp::from_str("  ").is_err());
+        assert! (EffectOp::from_str("effect:").is_err());
+    }
+
+    #[test]
+    fn test_compose_with_named_effect () {
+        let specs = vec! [
+            spec_t ("Smart Inversion V3", SMART_INVERSION_V3),
+            spec_c ("Vivid Smart Inversion", &["Smart Inversion V3", "saturate 1.2"]),
+            spec_t ("Contrast Boost", SMART_INVERSION_V3),
+            spec_c ("Boosted", &["effect:Contrast Boost"]),
+        ];
+        let compiled = compile_effects (&specs);
+        assert_eq! (compiled.len(), 4);
+        let (name, res) = &compiled[1];
+        assert_eq! (name, "Vivid Smart Inversion");
+
+        let expected = ColorMatrix (SMART_INVERSION_V3) .then (&ColorMatrix::saturate(1.2));
+        assert! (res.as_ref().unwrap() .approx_eq (&expected, 1e-6));
+
+        // and applying it to a color should match applying the two steps in sequence
+        let c = [0.8, 0.3, 0.1, 1.0];
+        let stepped = ColorMatrix::saturate(1.2) .apply (ColorMatrix(SMART_INVERSION_V3).apply(c));
+        let direct = res.as_ref().unwrap().apply(c);
+        for i in 0..4 { assert! ((stepped[i] - direct[i]).abs() < 1e-5) }
+
+        // an explicit reference gets the named effect, even where the name would otherwise parse as a primitive
+        assert! (compiled[3].1.as_ref().unwrap() .approx_eq (&ColorMatrix (SMART_INVERSION_V3), 1e-6));
+    }
+
+    #[test]
+    fn test_forward_refs_and_errors () {
+        let specs = vec! [
+            spec_c ("Fwd",    &["Base", "invert"]),
+            spec_c ("Base",   &["invert", "brightness 0.5"]),
+            spec_c ("CycA",   &["CycB"]),
+            spec_c ("CycB",   &["CycA"]),
+            spec_c ("Broken", &["Nonexistent"]),
+            spec_c ("BadArg", &["hue-rotate x"]),
+        ];
+        let compiled : HashMap<_,_> = compile_effects (&specs) .into_iter() .collect();
+
+        let expected = ColorMatrix::invert() .then (&ColorMatrix::brightness(0.5)) .then (&ColorMatrix::invert());
+        assert! (compiled["Fwd"].as_ref().unwrap() .approx_eq (&expected, 1e-6));
+
+        assert! (compiled["CycA"].as_ref().unwrap_err() .contains ("cyclic"));
+        assert! (compiled["CycB"].is_err());
+        assert! (compiled["Broken"].as_ref().unwrap_err() .contains ("undefined"));
+        assert! (compiled["BadArg"].is_err());
+    }
+
+}
@@ -0,0 +1,196 @@
+use std::str::FromStr;
+
+use crate::effects::matrix::*;
+
+
+
+// Color-vision-deficiency (CVD) simulation and daltonization (correction) matrices, computed rather than pasted
+//
+// Simulation follows the dichromat model of Viénot, Brettel & Mollon (1999) : we take rgb into LMS cone space,
+// replace the missing cone's response with a combination of the other two, and come back to rgb.
+// The replacement coefficients are solved (rather than hard-coded) such that white, and one anchor primary that
+// dichromats of that type see unchanged (blue for protan/deutan, red for tritan), map onto themselves.
+//
+// Anomalous trichromacy (e.g. protanomaly) is approximated by blending between identity and the full dichromat matrix
+// by the severity (0..1), and daltonization uses Fidaner et al's approach of redistributing the error (what is lost
+// in simulation) into the channels that the viewer can still distinguish.
+//
+// Note that like all our effects, these operate directly on (gamma-encoded) screen rgb, as the Mag API doesnt give us
+// any linearization step .. this is a common approximation, and the results are still useful for reviews/accessibility
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CvdKind {
+    Protan,
+    Deutan,
+    Tritan,
+}
+
+impl FromStr for CvdKind {
+    type Err = String;
+    fn from_str (s: &str) -> Result <CvdKind, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "protan" | "protanopia" | "protanomaly" | "p" => Ok (CvdKind::Protan),
+            "deutan" | "deuteranopia" | "deuteranomaly" | "d" => Ok (CvdKind::Deutan),
+            "tritan" | "tritanopia" | "tritanomaly" | "t" => Ok (CvdKind::Tritan),
+            _ => Err (format! ("unknown color-vision-deficiency type {s:?} (expected protan, deutan or tritan)")),
+        }
+    }
+}
+
+
+
+/// Linear rgb to LMS cone responses (Smith-Pokorny based, as used by Viénot et al)
+const RGB_TO_LMS : Mat3 = [
+    [ 17.8824,    43.5161,   4.11935 ],
+    [  3.45565,   27.1554,   3.86714 ],
+    [  0.0299566,  0.184309, 1.46709 ],
+];
+
+
+/// Solves for the LMS projection that replaces the missing cone response, keeping white and the anchor color invariant
+fn dichromat_lms_projection (kind: CvdKind) -> Mat3 {
+
+    let (missing, anchor_rgb) = match kind {
+        CvdKind::Protan => (0, [0.0, 0.0, 1.0]),
+        CvdKind::Deutan => (1, [0.0, 0.0, 1.0]),
+        CvdKind::Tritan => (2, [1.0, 0.0, 0.0]),
+    };
+    let (i, j) = ((missing + 1) % 3, (missing + 2) % 3);
+
+    let white  = mat3_mul_vec (&RGB_TO_LMS, [1.0, 1.0, 1.0]);
+    let anchor = mat3_mul_vec (&RGB_TO_LMS, anchor_rgb);
+
+    // we want : x[missing] = a * x[i] + b * x[j] .. to hold for both white and the anchor .. (a 2x2 solve by Cramer's rule)
+    let det = white[i] * anchor[j] - white[j] * anchor[i];
+    let a = (white[missing] * anchor[j] - white[j] * anchor[missing]) / det;
+    let b = (white[i] * anchor[missing] - white[missing] * anchor[i]) / det;
+
+    let mut proj = MAT3_IDENTITY;
+    proj[missing] = [0.0; 3];
+    proj[missing][i] = a;
+    proj[missing][j] = b;
+    proj
+}
+
+
+/// The rgb-space (column-vector form) simulation matrix for given deficiency type and severity (0..1)
+pub fn simulation_mat3 (kind: CvdKind, severity: f32) -> Mat3 {
+    let lms_to_rgb = mat3_inv (&RGB_TO_LMS) .expect ("RGB_TO_LMS must be invertible");
+    let full = mat3_mul (&lms_to_rgb, &mat3_mul (&dichromat_lms_projection (kind), &RGB_TO_LMS));
+    mat3_lerp (&MAT3_IDENTITY, &full, severity.clamp (0.0, 1.0))
+}
+
+
+/// The rgb-space (column-vector form) daltonization matrix for given deficiency type and severity (0..1)
+pub fn daltonization_mat3 (kind: CvdKind, severity: f32) -> Mat3 {
+    // the error (what the viewer loses) gets shifted into channels they can still discriminate
+    let err_shift : Mat3 = match kind {
+        CvdKind::Protan | CvdKind::Deutan => [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]],
+        CvdKind::Tritan                   => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+    };
+    let sim = simulation_mat3 (kind, severity);
+    let err : Mat3 = [0, 1, 2] .map (|r| [0, 1, 2] .map (|c| MAT3_IDENTITY[r][c] - sim[r][c]));
+    // ^^ i.e. (I - sim) .. the part of each color that the viewer doesnt perceive
+    let mut out = mat3_mul (&err_shift, &err);
+    for (r, row) in out.iter_mut().enumerate() { row[r] += 1.0; }
+    out
+}
+
+
+pub fn simulation (kind: CvdKind, severity: f32) -> ColorMatrix {
+    ColorMatrix::from_rgb_mix (simulation_mat3 (kind, severity), [0.0; 3])
+}
+
+pub fn daltonization (kind: CvdKind, severity: f32) -> ColorMatrix {
+    ColorMatrix::from_rgb_mix (daltonization_mat3 (kind, severity), [0.0; 3])
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS : f32 = 1e-4;
+    const KINDS : [CvdKind; 3] = [CvdKind::Protan, CvdKind::Deutan, CvdKind::Tritan];
+
+    fn assert_vec_eq (a: [f32; 3], b: [f32; 3], eps: f32) {
+        for c in 0..3 {
+            assert! ((a[c] - b[c]).abs() < eps, "mismatch at channel {c} : {a:?} vs {b:?}");
+        }
+    }
+
+    #[test]
+    fn test_simulation_invariants () {
+        for kind in KINDS {
+            assert! (simulation (kind, 0.0) .is_identity());
+
+            let sim = simulation_mat3 (kind, 1.0);
+            // white and the solved-for anchor must be preserved
+            assert_vec_eq (mat3_mul_vec (&sim, [1.0, 1.0, 1.0]), [1.0, 1.0, 1.0], EPS);
+            let anchor = if kind == CvdKind::Tritan { [1.0, 0.0, 0.0] } else { [0.0, 0.0, 1.0] };
+            assert_vec_eq (mat3_mul_vec (&sim, anchor), anchor, EPS);
+
+            // a dichromat projection is rank-2 and idempotent (simulating twice changes nothing more)
+            assert! (mat3_det (&sim).abs() < 1e-4, "{kind:?} det : {}", mat3_det(&sim));
+            let twice = mat3_mul (&sim, &sim);
+            for r in 0..3 { assert_vec_eq (twice[r], sim[r], 1e-3) }
+        }
+    }
+
+    #[test]
+    fn test_simulation_confusions () {
+        // the protan/deutan simulations should match the published Viénot et al matrices (first row)
+        let protan = simulation_mat3 (CvdKind::Protan, 1.0);
+        let deutan = simulation_mat3 (CvdKind::Deutan, 1.0);
+        assert_vec_eq (protan[0], [0.11238, 0.88762, 0.0], 1e-3);
+        assert_vec_eq (deutan[0], [0.29275, 0.70725, 0.0], 1e-3);
+
+        // dichromats lose an axis : protans and deutans can no longer split red from green (so r == g in the output),
+        // while tritans lose the blue-yellow split (and get g == b)
+        let tritan = simulation_mat3 (CvdKind::Tritan, 1.0);
+        for color in [[0.8, 0.2, 0.1], [0.45, 0.55, 0.1], [0.1, 0.3, 0.9]] {
+            for sim in [&protan, &deutan] {
+                let out = mat3_mul_vec (sim, color);
+                assert! ((out[0] - out[1]).abs() < EPS, "{out:?}");
+            }
+            let out = mat3_mul_vec (&tritan, color);
+            assert! ((out[1] - out[2]).abs() < EPS, "{out:?}");
+        }
+
+        // and partial severity should land in between
+        let half = simulation_mat3 (CvdKind::Deutan, 0.5);
+        for r in 0..3 { for c in 0..3 {
+            assert! ((half[r][c] - (MAT3_IDENTITY[r][c] + deutan[r][c]) / 2.0).abs() < EPS);
+        } }
+    }
+
+    #[test]
+    fn test_daltonization () {
+        for kind in KINDS {
+            assert! (daltonization (kind, 0.0) .is_identity());
+            // neutral grays have no error to redistribute, so they should come through untouched
+            let dalt = daltonization_mat3 (kind, 1.0);
+            for v in [0.0, 0.3, 1.0] {
+                assert_vec_eq (mat3_mul_vec (&dalt, [v, v, v]), [v, v, v], EPS);
+            }
+        }
+        // for protans, daltonizing red should push some of it into green/blue so they can tell it apart
+        let out = mat3_mul_vec (&daltonization_mat3 (CvdKind::Protan, 1.0), [1.0, 0.0, 0.0]);
+        assert_eq! (out[0], 1.0);
+        assert! (out[1].abs() > 0.05 || out[2].abs() > 0.05, "{out:?}");
+    }
+
+    #[test]
+    fn test_parse_kind () {
+        assert_eq! (CvdKind::from_str ("Protanopia"), Ok (CvdKind::Protan));
+        assert_eq! (CvdKind::from_str ("deutan"), Ok (CvdKind::Deutan));
+        assert_eq! (CvdKind::from_str ("T"), Ok (CvdKind::Tritan));
+        assert! (CvdKind::from_str ("achroma").is_err());
+    }
+
+}
@@ -0,0 +1,198 @@
+use crate::effects::compose::parse_hex_color;
+use crate::effects::matrix::ColorMatrix;
+
+
+
+// Solving for an effect matrix from example color mappings (e.g. white -> #1e1e1e, black -> #d0d0d0, etc)
+//
+// Each output channel is an affine function of the input rgb (3 mix weights + 1 translation), so we solve a separate
+// least-squares problem per channel over all the given pairs. The alpha column is left as plain pass-through.
+// To keep under-determined cases (fewer than 4 independent pairs, e.g. just white and black) well-behaved, we add
+// a small ridge term pulling the solution towards identity .. so the fit only moves what the examples actually pin down.
+
+
+
+/// Strength of the pull towards identity (relative to each pair's weight of 1)
+const FIT_REGULARIZATION : f64 = 1e-4;
+
+/// Residuals (in 0..255 rgb units) beyond which a fitted pair is considered poorly matched
+pub const FIT_RESIDUAL_WARN : f32 = 8.0;
+
+
+
+/// A source -> target rgb color mapping (as 0..1 floats)
+pub type ColorPair = ([f32; 3], [f32; 3]);
+
+#[derive (Debug, Clone)]
+pub struct ColorFit {
+    pub matrix    : ColorMatrix,
+    pub residuals : Vec <f32>,
+    // ^^ per pair, the distance between the target and what the fitted matrix actually produces (in 0..255 rgb units)
+}
+
+
+/// Parses '#rrggbb' source -> target pairs (as specified in config)
+pub fn parse_color_pairs (pairs: &[(String, String)]) -> Result <Vec <ColorPair>, String> {
+    pairs .iter() .map (|(src, tgt)| {
+        let parse = |s: &str| parse_hex_color (s) .ok_or_else (|| format! ("'fit' expects '#rrggbb' colors, got : {s:?}"));
+        Ok ((parse (src)?, parse (tgt)?))
+    } ) .collect()
+}
+
+
+/// Solves the least-squares affine color matrix that best maps each source color to its target
+#[allow (clippy::needless_range_loop)]
+pub fn fit_color_matrix (pairs: &[ColorPair]) -> Result <ColorFit, String> {
+
+    if pairs.is_empty() {
+        return Err ("'fit' needs at least one source -> target color pair".into())
+    }
+
+    // the normal equations : (XᵀX + λI) w = Xᵀy + λ w_identity .. where X rows are [r, g, b, 1]
+    let mut xtx = [[0.0f64; 4]; 4];
+    for (src, _) in pairs {
+        let x = [src[0] as f64, src[1] as f64, src[2] as f64, 1.0];
+        for i in 0..4 { for j in 0..4 { xtx[i][j] += x[i] * x[j]; } }
+    }
+    for i in 0..4 { xtx[i][i] += FIT_REGULARIZATION; }
+
+    let mut matrix = ColorMatrix::IDENTITY;
+    for c in 0..3 {
+        let mut rhs = [0.0f64; 4];
+        for (src, tgt) in pairs {
+            let x = [src[0] as f64, src[1] as f64, src[2] as f64, 1.0];
+            for i in 0..4 { rhs[i] += x[i] * tgt[c] as f64; }
+        }
+        rhs[c] += FIT_REGULARIZATION;
+        // ^^ the identity weight for channel c is 1 for input c, and 0 for the others (incl translation)
+
+        let w = solve_4x4 (xtx, rhs) .ok_or ("'fit' color pairs lead to a degenerate system")?;
+        for i in 0..3 { matrix.set (i, c, w[i] as f32); }
+        matrix.set (4, c, w[3] as f32);
+    }
+
+    let residuals = pairs .iter() .map (|(src, tgt)| {
+        let out = matrix.apply ([src[0], src[1], src[2], 1.0]);
+        (0..3) .map (|c| (out[c] - tgt[c]).powi(2)) .sum::<f32>() .sqrt() * 255.0
+    } ) .collect();
+
+    Ok ( ColorFit { matrix, residuals } )
+}
+
+
+/// Fits the closest affine color matrix to a (per-channel) gamma ramp .. e.g. for applying gamma presets via the
+/// full-screen color effect where hardware ramps are unavailable (RDP, some DisplayLink docks etc). <br>
+/// Affine can capture brightness, contrast and color-temp exactly, but gamma curves only approximately (see residuals)
+pub fn fit_ramp_matrix (ramp: &[[u16; 256]; 3]) -> ColorFit {
+    // ramps are per channel, so to keep the channels independent in the fit, we decorrelate the sampled levels by
+    // stepping each channel through all 256 levels in a different order (odd multipliers being permutations mod 256)
+    let level = |c: usize, i: usize| (i * [1, 97, 53][c] + [0, 85, 170][c]) % 256;
+    let pairs : Vec <ColorPair> = (0..256) .map (|i| {
+        let src = [0, 1, 2] .map (|c| level (c, i) as f32 / 255.0);
+        let tgt = [0, 1, 2] .map (|c| ramp[c][level (c, i)] as f32 / (255.0 * 256.0));
+        // ^^ the identity ramp is i*256, so that is what maps back to 1.0
+        (src, tgt)
+    } ) .collect();
+    fit_color_matrix (&pairs) .unwrap_or_else (|_| ColorFit { matrix: ColorMatrix::IDENTITY, residuals: vec![] })
+    // ^^ the decorrelated samples are never degenerate, but we'd rather not panic over it either
+}
+
+
+/// Gaussian elimination w partial pivoting
+#[allow (clippy::needless_range_loop)]
+fn solve_4x4 (mut a: [[f64; 4]; 4], mut b: [f64; 4]) -> Option <[f64; 4]> {
+    for col in 0..4 {
+        let pivot = (col..4) .max_by (|&p, &q| a[p][col].abs().total_cmp (&a[q][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 { return None }
+        a.swap (pivot, col);
+        b.swap (pivot, col);
+        for r in col+1 .. 4 {
+            let f = a[r][col] / a[col][col];
+            for c in col .. 4 { a[r][c] -= f * a[col][c]; }
+            b[r] -= f * b[col];
+        }
+    }
+    let mut x = [0.0f64; 4];
+    for r in (0..4).rev() {
+        let s : f64 = (r+1 .. 4) .map (|c| a[r][c] * x[c]) .sum();
+        x[r] = (b[r] - s) / a[r][r];
+    }
+    Some (x)
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs (strs: &[(&str, &str)]) -> Vec <ColorPair> {
+        let owned : Vec<(String, String)> = strs .iter() .map (|(a, b)| (a.to_string(), b.to_string())) .collect();
+        parse_color_pairs (&owned) .unwrap()
+    }
+
+    #[test]
+    fn test_recovers_known_matrix () {
+        // colors generated by a known affine matrix should give that matrix back (to within the tiny regularization)
+        let known = ColorMatrix::invert() .then (&ColorMatrix::saturate (1.3)) .then (&ColorMatrix::brightness (0.9));
+        let srcs = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.3, 0.6, 0.2]];
+        let ps : Vec<_> = srcs .iter() .map (|&s| {
+            let o = known.apply ([s[0], s[1], s[2], 1.0]);
+            (s, [o[0], o[1], o[2]])
+        } ) .collect();
+        let fit = fit_color_matrix (&ps) .unwrap();
+        assert! (fit.matrix .approx_eq (&known, 1e-3), "{:?}", fit.matrix);
+        assert! (fit.residuals .iter() .all (|&r| r < 0.1), "{:?}", fit.residuals);
+    }
+
+    #[test]
+    fn test_under_determined_fit () {
+        // just white and black pin down the gray axis .. both should map (nearly) exactly, and alpha stays untouched
+        let ps = pairs (&[("#ffffff", "#1e1e1e"), ("#000000", "#d0d0d0")]);
+        let fit = fit_color_matrix (&ps) .unwrap();
+        assert! (fit.residuals .iter() .all (|&r| r < 0.5), "{:?}", fit.residuals);
+        let mid = fit.matrix.apply ([0.5, 0.5, 0.5, 1.0]);
+        assert! ((mid[0] - (0x1e as f32 + 0xd0 as f32) / 2.0 / 255.0).abs() < 1e-2, "{mid:?}");
+        assert_eq! (mid[3], 1.0);
+        assert_eq! ([0, 1, 2, 3, 4] .map (|r| fit.matrix.get (r, 3)), [0.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_ramp_fit () {
+        use crate::gamma::ramp::{apply_color_temp_to_ramp, color_temp_multipliers, GBC};
+
+        // the identity ramp fits to the identity matrix
+        let fit = fit_ramp_matrix (&GBC::default().create_gamma_ramp());
+        assert! (fit.matrix .approx_eq (&ColorMatrix::IDENTITY, 1e-3), "{:?}", fit.matrix);
+
+        // brightness/contrast (w color temp on top) are affine per channel, so those fit closely, w no channel mixing
+        let mut ramp = GBC::new (1.0, -0.2, 0.8) .create_gamma_ramp();
+        apply_color_temp_to_ramp (&mut ramp, 4000, 0.0);
+        let fit = fit_ramp_matrix (&ramp);
+        let mults = color_temp_multipliers (4000, 0.0);
+        for (c, mult) in mults.into_iter().enumerate() {
+            let expected_scale = 0.8 * mult;
+            assert! ((fit.matrix.get (c, c) - expected_scale).abs() < 0.01, "{:?}", fit.matrix);
+            assert! ((0..3) .filter (|&i| i != c) .all (|i| fit.matrix.get (i, c).abs() < 1e-3), "{:?}", fit.matrix);
+        }
+        assert! (fit.residuals .iter() .all (|&r| r < 1.5), "{:?}", fit.residuals);
+
+        // while a gamma curve can only be approximated
+        let fit = fit_ramp_matrix (&GBC::new (2.0, 0.0, 1.0) .create_gamma_ramp());
+        assert! (fit.residuals .iter() .any (|&r| r > FIT_RESIDUAL_WARN));
+    }
+
+    #[test]
+    fn test_conflicting_pairs_and_errors () {
+        // the same source mapped to two different targets can at best land in between (and should report that)
+        let ps = pairs (&[("#808080", "#000000"), ("#808080", "#ffffff")]);
+        let fit = fit_color_matrix (&ps) .unwrap();
+        assert! (fit.residuals .iter() .all (|&r| r > FIT_RESIDUAL_WARN), "{:?}", fit.residuals);
+
+        assert! (fit_color_matrix (&[]) .is_err());
+        assert! (parse_color_pairs (&[("#fff".into(), "#000000".into())]) .is_err());
+    }
+
+}
@@ -0,0 +1,266 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use crate::effects::{compose, fit};
+use crate::effects::matrix::{mat3_det, ColorMatrix};
+use crate::effects::spec::{ColorEffectDef, ColorEffectSpec};
+
+
+
+// Load-time diagnostics for effect matrices .. the idea is to catch the kinds of mistakes that are easy to make when
+// hand-editing 25-float arrays (or composing them), and which would otherwise just show up as weird colors on screen.
+// This is all pure analysis over the specs, so it can be used from init, from the cli check, and from tests alike.
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Note,
+    Warning,
+    Error,
+}
+
+
+#[derive (Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    pub effect : String,
+    pub level  : LintLevel,
+    pub msg    : String,
+}
+
+impl Display for LintIssue {
+    fn fmt (&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write! (f, "[{:?}] {:?} : {}", self.level, self.effect, self.msg)
+    }
+}
+
+
+
+/// Fraction of sampled rgb-cube outputs that must clip before we'd warn (rather than just note) about it
+const CLIP_WARN_FRACTION : f32 = 0.5;
+
+/// How far outside [0,1] an output can go before we count it as clipping
+const CLIP_TOLERANCE : f32 = 1e-3;
+
+/// Grid steps per axis for sampling the rgb cube
+const CUBE_SAMPLING_STEPS : usize = 11;
+
+
+
+/// Analyses a single (compiled) effect matrix, returning any issues found
+pub fn lint_matrix (m: &ColorMatrix) -> Vec <(LintLevel, String)> {
+
+    let mut issues = vec![];
+
+    // alpha : an opaque input should stay opaque .. i.e. the alpha column should only pass alpha through
+    let alpha_col = [0, 1, 2, 3, 4] .map (|r| m.get(r, 3));
+    if alpha_col != [0.0, 0.0, 0.0, 1.0, 0.0] {
+        let corners = (0..8) .map (|i| [(i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32, 1.0]);
+        let alphas : Vec<f32> = corners .map (|c| m.apply(c)[3]) .collect();
+        let (lo, hi) = alphas .iter() .fold ((f32::MAX, f32::MIN), |(lo, hi), &a| (lo.min(a), hi.max(a)));
+        let mut msg = format! ("alpha column is {alpha_col:?} (expected [0, 0, 0, 1, 0]) .. opaque inputs get alpha in {lo:.3} .. {hi:.3}");
+        if m.get(4, 3) != 0.0 {
+            msg += &format! (" (the translation row adds {} to alpha)", m.get(4, 3));
+        }
+        if lo < 0.0 {
+            msg += " .. and can go negative";
+        }
+        issues.push ((LintLevel::Warning, msg));
+    }
+
+    // the last column is the homogeneous 'w' term .. anything other than [0,0,0,0,1] there has no meaningful effect
+    let w_col = [0, 1, 2, 3, 4] .map (|r| m.get(r, 4));
+    if w_col != [0.0, 0.0, 0.0, 0.0, 1.0] {
+        issues.push ((LintLevel::Note, format! ("last column is {w_col:?} (expected [0, 0, 0, 0, 1])")));
+    }
+
+    // out-of-range outputs over the (opaque) rgb cube
+    let n = CUBE_SAMPLING_STEPS;
+    let (mut n_clipped, mut lo, mut hi) = (0usize, f32::MAX, f32::MIN);
+    for r in 0..n { for g in 0..n { for b in 0..n {
+        let c = [r, g, b] .map (|v| v as f32 / (n - 1) as f32);
+        let out = m.apply ([c[0], c[1], c[2], 1.0]);
+        let rgb = &out[..3];
+        lo = rgb .iter() .fold (lo, |a, &v| a.min(v));
+        hi = rgb .iter() .fold (hi, |a, &v| a.max(v));
+        if rgb .iter() .any (|&v| !(-CLIP_TOLERANCE ..= 1.0 + CLIP_TOLERANCE).contains (&v)) {
+            n_clipped += 1;
+        }
+    } } }
+    let clip_frac = n_clipped as f32 / (n * n * n) as f32;
+    if n_clipped > 0 {
+        let level = if clip_frac > CLIP_WARN_FRACTION { LintLevel::Warning } else { LintLevel::Note };
+        issues.push ((level, format! (
+            "{:.0}% of the rgb cube maps outside [0,1] and will clip (outputs range {lo:.2} .. {hi:.2})", clip_frac * 100.0
+        )));
+    }
+
+    // singular matrices collapse distinct colors together .. which is expected for e.g. grayscale, but worth noting
+    let rgb_mix = [0, 1, 2] .map (|c| [0, 1, 2] .map (|i| m.get(i, c)));
+    if mat3_det (&rgb_mix).abs() < 1e-4 || m.determinant().abs() < 1e-4 {
+        issues.push ((LintLevel::Note, "matrix is non-invertible (distinct colors will collapse together)".into()));
+    }
+
+    issues
+}
+
+
+
+/// Analyses all effect specs (as loaded from config), along with the names of malformed entries that couldn't be parsed
+/// into specs (and so got dropped), and the configured cycle-order names
+pub fn lint_effects (specs: &[ColorEffectSpec], malformed: &[String], cycle_order: &[String]) -> Vec <LintIssue> {
+
+    let mut issues = malformed .iter() .map (|name| LintIssue {
+        effect: name.clone(), level: LintLevel::Error,
+        msg: "malformed [[effects]] entry, so it was dropped .. (needs a 25-float 'transform', a 'compose' list, or 'fit' color pairs)".into()
+    } ) .collect::<Vec<_>>();
+
+    let mut counts : HashMap <&str, usize> = HashMap::new();
+    specs .iter() .for_each (|s| *counts .entry (s.name.as_str()) .or_default() += 1);
+
+    let mut seen = HashSet::new();
+    for (name, compiled) in compose::compile_effects (specs) {
+        if !seen.insert (name.clone()) { continue }
+        // ^^ for duplicates, compile results are the same (last def wins), so we'll only report each name once
+
+        if counts [name.as_str()] > 1 {
+            issues.push ( LintIssue {
+                effect: name.clone(), level: LintLevel::Warning,
+                msg: format! ("defined {} times (only the last definition will be used)", counts [name.as_str()])
+            } );
+        }
+        match compiled {
+            Err (e) => issues.push ( LintIssue { effect: name.clone(), level: LintLevel::Error, msg: e } ),
+            Ok (m) => {
+                for (level, msg) in lint_matrix (&m) {
+                    issues.push ( LintIssue { effect: name.clone(), level, msg } );
+                }
+            }
+        }
+    }
+
+    // for fitted effects, we'll report how well each of the given color pairs actually got matched
+    for spec in specs {
+        let ColorEffectDef::Fit (pairs) = &spec.def else { continue };
+        let Ok (fitted) = fit::parse_color_pairs (pairs) .and_then (|ps| fit::fit_color_matrix (&ps)) else { continue };
+        // ^^ errors here would already have been reported from compiling above
+        for ((src, tgt), res) in pairs .iter() .zip (fitted.residuals) {
+            let level = if res > fit::FIT_RESIDUAL_WARN { LintLevel::Warning } else { LintLevel::Note };
+            issues.push ( LintIssue {
+                effect: spec.name.clone(), level, msg: format! ("fit residual for {src} -> {tgt} : {res:.1} (in 0..255 rgb units)")
+            } );
+        }
+    }
+
+    for name in cycle_order {
+        if !counts.contains_key (name.as_str()) {
+            issues.push ( LintIssue {
+                effect: name.clone(), level: LintLevel::Warning, msg: "listed in effects_cycle_order, but not defined".into()
+            } );
+        }
+    }
+
+    issues
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::spec::parse_color_effects;
+
+    fn spec (name: &str, t: [f32; 25]) -> ColorEffectSpec {
+        ColorEffectSpec { name: name.into(), def: ColorEffectDef::Transform(t) }
+    }
+    fn levels (issues: &[(LintLevel, String)]) -> Vec<LintLevel> {
+        issues .iter() .map (|(l,_)| *l) .collect()
+    }
+
+    #[test]
+    fn test_clean_matrices () {
+        assert! (lint_matrix (&ColorMatrix::IDENTITY) .is_empty());
+        assert! (lint_matrix (&ColorMatrix::invert()) .is_empty());
+        assert! (lint_matrix (&ColorMatrix::hue_rotate(90.0)) .iter() .all (|(l,_)| *l == LintLevel::Note));
+    }
+
+    #[test]
+    fn test_alpha_mistakes () {
+        // simple inversion with the alpha translation set to 1 (as the code fallback has it)
+        let mut m = ColorMatrix::invert();
+        m.set (4, 3, 1.0);
+        let issues = lint_matrix (&m);
+        assert_eq! (levels(&issues), vec![LintLevel::Warning]);
+        assert! (issues[0].1 .contains ("adds 1 to alpha"), "{:?}", issues);
+
+        // negative alpha contributions from rgb
+        let mut m = ColorMatrix::IDENTITY;
+        m.set (0, 3, -1.5);
+        let issues = lint_matrix (&m);
+        assert! (issues[0].1 .contains ("negative"), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_clipping_and_singular () {
+        // the 'Black and White' style threshold matrices clip most of the cube (by design, but we still flag it)
+        let bw = ColorMatrix::from_rgb_mix ([[127.0; 3]; 3], [-180.0; 3]);
+        let issues = lint_matrix (&bw);
+        assert! (issues .iter() .any (|(l, m)| *l == LintLevel::Warning && m.contains ("clip")), "{:?}", issues);
+        assert! (issues .iter() .any (|(_, m)| m.contains ("non-invertible")), "{:?}", issues);
+
+        // mild brightening should only clip a little (just the brightest slice of the cube)
+        let issues = lint_matrix (&ColorMatrix::brightness (1.1));
+        assert! (issues .iter() .any (|(l, m)| *l == LintLevel::Note && m.contains ("clip")), "{:?}", issues);
+        assert! (issues .iter() .all (|(l, _)| *l == LintLevel::Note), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_lint_effects () {
+        let specs = vec! [
+            spec ("Identity", ColorMatrix::IDENTITY.0),
+            spec ("Dup", ColorMatrix::invert().0),
+            spec ("Dup", ColorMatrix::invert().0),
+            ColorEffectSpec { name: "Broken".into(), def: ColorEffectDef::Compose (vec!["Missing".into()]) },
+        ];
+        let cycle = vec! ["Identity".to_string(), "Typo".to_string()];
+        let issues = lint_effects (&specs, &[], &cycle);
+
+        let find = |name: &str| issues .iter() .filter (|i| i.effect == name) .collect::<Vec<_>>();
+        assert! (find("Identity").is_empty());
+        assert_eq! (find("Dup").len(), 1);
+        assert! (find("Dup")[0].msg .contains ("2 times"));
+        assert_eq! (find("Broken")[0].level, LintLevel::Error);
+        assert! (find("Typo")[0].msg .contains ("cycle_order"));
+    }
+
+    #[test]
+    fn test_fit_residuals () {
+        let fit_spec = |name: &str, pairs: &[(&str, &str)]| ColorEffectSpec {
+            name: name.into(), def: ColorEffectDef::Fit (pairs .iter() .map (|(a,b)| (a.to_string(), b.to_string())) .collect())
+        };
+        let specs = vec! [
+            fit_spec ("Good", &[("#ffffff", "#1e1e1e"), ("#000000", "#d0d0d0")]),
+            fit_spec ("Conflicted", &[("#808080", "#000000"), ("#808080", "#ffffff")]),
+        ];
+        let issues = lint_effects (&specs, &[], &[]);
+        let fit_issues = |name: &str| issues .iter() .filter (|i| i.effect == name && i.msg.contains ("fit residual")) .collect::<Vec<_>>();
+        assert_eq! (fit_issues("Good").len(), 2);
+        assert! (fit_issues("Good") .iter() .all (|i| i.level == LintLevel::Note));
+        assert! (fit_issues("Conflicted") .iter() .all (|i| i.level == LintLevel::Warning));
+    }
+
+    #[test]
+    fn test_malformed_entries () {
+        // a transform w the wrong length doesnt parse into a spec, but still gets reported (rather than silently dropped)
+        let doc = "[[effects]]\neffect = \"Short\"\ntransform = [1.0, 0.0, 0.0]\n\n[[effects]]\neffect = \"Fine\"\ncompose = [\"Short\"]\n";
+        let (specs, malformed) = parse_color_effects (&doc .parse() .unwrap());
+        assert_eq! (malformed, vec! ["Short".to_string()]);
+        let issues = lint_effects (&specs, &malformed, &[]);
+        let short = issues .iter() .filter (|i| i.effect == "Short") .collect::<Vec<_>>();
+        assert_eq! (short.len(), 1);
+        assert! (short[0].level == LintLevel::Error && short[0].msg.contains ("malformed"), "{:?}", short);
+    }
+
+}
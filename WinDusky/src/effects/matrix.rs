@@ -0,0 +1,343 @@
+// Pure color-matrix math for our color effects (no win32 here, so it can be exercised anywhere)
+//
+// The conventions follow MAGCOLOREFFECT exactly : a 5x5 row-major matrix applied to a row-vector [r, g, b, a, 1]
+// .. i.e. out[c] = sum_i ( in[i] * m[i][c] ) .. so the 5th row holds the translation (offset) for each output channel
+// .. and composing 'A then B' is simply the matrix product A·B
+
+
+
+
+/// 3x3 matrix in column-vector form (i.e. out[r] = sum_c ( m[r][c] * in[c] )), used for rgb/lms mixing math
+pub type Mat3 = [[f32; 3]; 3];
+
+pub const MAT3_IDENTITY : Mat3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+pub fn mat3_mul (a: &Mat3, b: &Mat3) -> Mat3 {
+    let mut out = [[0.0f32; 3]; 3];
+    for r in 0..3 {
+        for c in 0..3 {
+            out[r][c] = (0..3) .map (|k| a[r][k] * b[k][c]) .sum();
+        }
+    }
+    out
+}
+
+pub fn mat3_mul_vec (m: &Mat3, v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2] .map (|r| (0..3) .map (|c| m[r][c] * v[c]) .sum())
+}
+
+pub fn mat3_det (m: &Mat3) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+  - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+  + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+#[allow (clippy::needless_range_loop)]
+pub fn mat3_inv (m: &Mat3) -> Option <Mat3> {
+    let det = mat3_det (m);
+    if det.abs() < 1e-9 { return None }
+    let mut out = [[0.0f32; 3]; 3];
+    for r in 0..3 {
+        for c in 0..3 {
+            // adjugate (transposed cofactors) via cyclic indexing
+            let (r1, r2) = ((c + 1) % 3, (c + 2) % 3);
+            let (c1, c2) = ((r + 1) % 3, (r + 2) % 3);
+            out[r][c] = (m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]) / det;
+        }
+    }
+    Some (out)
+}
+
+/// Linear blend between two 3x3 matrices (t=0 gives a, t=1 gives b)
+pub fn mat3_lerp (a: &Mat3, b: &Mat3, t: f32) -> Mat3 {
+    let mut out = *a;
+    for r in 0..3 {
+        for c in 0..3 {
+            out[r][c] = a[r][c] + (b[r][c] - a[r][c]) * t;
+        }
+    }
+    out
+}
+
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+pub struct ColorMatrix (pub [f32; 25]);
+
+impl Default for ColorMatrix {
+    fn default() -> ColorMatrix { ColorMatrix::IDENTITY }
+}
+
+
+
+impl ColorMatrix {
+
+    pub const IDENTITY : ColorMatrix = ColorMatrix ( [
+        1.0,  0.0,  0.0,  0.0,  0.0,
+        0.0,  1.0,  0.0,  0.0,  0.0,
+        0.0,  0.0,  1.0,  0.0,  0.0,
+        0.0,  0.0,  0.0,  1.0,  0.0,
+        0.0,  0.0,  0.0,  0.0,  1.0,
+    ] );
+
+    pub fn get (&self, row: usize, col: usize) -> f32 {
+        self.0 [row * 5 + col]
+    }
+    pub fn set (&mut self, row: usize, col: usize, v: f32) {
+        self.0 [row * 5 + col] = v
+    }
+
+    /// Builds a matrix from a 3x3 rgb mixing matrix in the (more commonly published) column-vector form <br>
+    /// i.e. out[c] = sum_i ( mix[c][i] * in[i] ) + offset[c] .. with alpha passed through untouched
+    #[allow (clippy::needless_range_loop)]
+    pub fn from_rgb_mix (mix: Mat3, offset: [f32; 3]) -> ColorMatrix {
+        let mut m = ColorMatrix::IDENTITY;
+        for c in 0..3 {
+            for i in 0..3 {
+                m.set (i, c, mix[c][i]);
+            }
+            m.set (4, c, offset[c]);
+        }
+        m
+    }
+
+    /// Composes self followed by next .. (i.e. the result applies self first, then next)
+    pub fn then (&self, next: &ColorMatrix) -> ColorMatrix {
+        let mut out = [0.0f32; 25];
+        for i in 0..5 {
+            for j in 0..5 {
+                out [i * 5 + j] = (0..5) .map (|k| self.get(i,k) * next.get(k,j)) .sum();
+            }
+        }
+        ColorMatrix (out)
+    }
+
+    /// Applies the matrix to an rgba color (0..1 floats), without any clamping of the output
+    pub fn apply (&self, rgba: [f32; 4]) -> [f32; 4] {
+        let v = [rgba[0], rgba[1], rgba[2], rgba[3], 1.0];
+        let mut out = [0.0f32; 4];
+        for (c, o) in out.iter_mut().enumerate() {
+            *o = (0..5) .map (|i| v[i] * self.get(i,c)) .sum();
+        }
+        out
+    }
+
+    /// Determinant of the full 5x5 matrix (via gaussian elimination w partial pivoting, in f64 for some headroom)
+    #[allow (clippy::needless_range_loop)]
+    pub fn determinant (&self) -> f32 {
+        let mut m = [[0.0f64; 5]; 5];
+        for r in 0..5 { for c in 0..5 { m[r][c] = self.get(r,c) as f64; } }
+        let mut det = 1.0f64;
+        for col in 0..5 {
+            let pivot = (col..5) .max_by (|&a, &b| m[a][col].abs().total_cmp (&m[b][col].abs())) .unwrap();
+            if m[pivot][col].abs() < 1e-12 { return 0.0 }
+            if pivot != col { m.swap (pivot, col); det = -det; }
+            det *= m[col][col];
+            for r in col+1 .. 5 {
+                let f = m[r][col] / m[col][col];
+                for c in col .. 5 { m[r][c] -= f * m[col][c]; }
+            }
+        }
+        det as f32
+    }
+
+    /// Linear blend between two matrices (t=0 gives a, t=1 gives b) .. e.g. lerp from identity for partial intensity
+    pub fn lerp (a: &ColorMatrix, b: &ColorMatrix, t: f32) -> ColorMatrix {
+        let mut out = a.0;
+        for (i, v) in out.iter_mut().enumerate() {
+            *v += (b.0[i] - a.0[i]) * t;
+        }
+        ColorMatrix (out)
+    }
+
+    pub fn is_identity (&self) -> bool {
+        self.approx_eq (&ColorMatrix::IDENTITY, 1e-6)
+    }
+
+    pub fn approx_eq (&self, other: &ColorMatrix, eps: f32) -> bool {
+        self.0.iter() .zip (other.0.iter()) .all (|(a,b)| (a - b).abs() <= eps)
+    }
+
+
+
+    // --- primitives ---
+
+    /// Simple inversion of r,g,b (alpha untouched)
+    pub fn invert () -> ColorMatrix {
+        ColorMatrix::from_rgb_mix ( [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]], [1.0, 1.0, 1.0] )
+    }
+
+    /// Hue rotation by given degrees (luminance preserving, as per W3C filter-effects)
+    pub fn hue_rotate (deg: f32) -> ColorMatrix {
+        let (sin, cos) = deg.to_radians().sin_cos();
+        ColorMatrix::from_rgb_mix ( [
+            [ 0.213 + cos * 0.787 - sin * 0.213,  0.715 - cos * 0.715 - sin * 0.715,  0.072 - cos * 0.072 + sin * 0.928 ],
+            [ 0.213 - cos * 0.213 + sin * 0.143,  0.715 + cos * 0.285 + sin * 0.140,  0.072 - cos * 0.072 - sin * 0.283 ],
+            [ 0.213 - cos * 0.213 - sin * 0.787,  0.715 - cos * 0.715 + sin * 0.715,  0.072 + cos * 0.928 + sin * 0.072 ],
+        ], [0.0; 3] )
+    }
+
+    /// Saturation scaling .. 0 is grayscale, 1 is unchanged, above 1 over-saturates
+    pub fn saturate (s: f32) -> ColorMatrix {
+        ColorMatrix::from_rgb_mix ( [
+            [ 0.213 + 0.787 * s,  0.715 - 0.715 * s,  0.072 - 0.072 * s ],
+            [ 0.213 - 0.213 * s,  0.715 + 0.285 * s,  0.072 - 0.072 * s ],
+            [ 0.213 - 0.213 * s,  0.715 - 0.715 * s,  0.072 + 0.928 * s ],
+        ], [0.0; 3] )
+    }
+
+    /// Linear brightness scaling of r,g,b
+    pub fn brightness (b: f32) -> ColorMatrix {
+        ColorMatrix::tint ([b, b, b])
+    }
+
+    /// Contrast scaling around mid-gray
+    pub fn contrast (c: f32) -> ColorMatrix {
+        let offset = 0.5 - 0.5 * c;
+        ColorMatrix::from_rgb_mix ( [[c, 0.0, 0.0], [0.0, c, 0.0], [0.0, 0.0, c]], [offset; 3] )
+    }
+
+    /// Sepia toning, with amount in 0..1 (as per W3C filter-effects)
+    pub fn sepia (amount: f32) -> ColorMatrix {
+        let a = 1.0 - amount.clamp (0.0, 1.0);
+        ColorMatrix::from_rgb_mix ( [
+            [ 0.393 + 0.607 * a,  0.769 - 0.769 * a,  0.189 - 0.189 * a ],
+            [ 0.349 - 0.349 * a,  0.686 + 0.314 * a,  0.168 - 0.168 * a ],
+            [ 0.272 - 0.272 * a,  0.534 - 0.534 * a,  0.131 + 0.869 * a ],
+        ], [0.0; 3] )
+    }
+
+    /// Grayscale (BT.709 luma), with amount in 0..1
+    pub fn grayscale (amount: f32) -> ColorMatrix {
+        let a = 1.0 - amount.clamp (0.0, 1.0);
+        ColorMatrix::from_rgb_mix ( [
+            [ 0.2126 + 0.7874 * a,  0.7152 - 0.7152 * a,  0.0722 - 0.0722 * a ],
+            [ 0.2126 - 0.2126 * a,  0.7152 + 0.2848 * a,  0.0722 - 0.0722 * a ],
+            [ 0.2126 - 0.2126 * a,  0.7152 - 0.7152 * a,  0.0722 + 0.9278 * a ],
+        ], [0.0; 3] )
+    }
+
+    /// Per-channel multiplication of r,g,b
+    pub fn tint (rgb: [f32; 3]) -> ColorMatrix {
+        let [r, g, b] = rgb;
+        ColorMatrix::from_rgb_mix ( [[r, 0.0, 0.0], [0.0, g, 0.0], [0.0, 0.0, b]], [0.0; 3] )
+    }
+
+    /// Channel permutation .. e.g. [2,1,0] (i.e. 'bgr') puts the source blue into red and vice versa
+    pub fn channel_swap (order: [usize; 3]) -> ColorMatrix {
+        let mut mix = [[0.0f32; 3]; 3];
+        for (c, &src) in order.iter().enumerate() {
+            mix [c] [src.min(2)] = 1.0;
+        }
+        ColorMatrix::from_rgb_mix (mix, [0.0; 3])
+    }
+
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS : f32 = 1e-4;
+
+    fn assert_rgb_eq (a: [f32; 4], b: [f32; 4]) {
+        for c in 0..4 {
+            assert! ((a[c] - b[c]).abs() < EPS, "mismatch at channel {c} : {a:?} vs {b:?}");
+        }
+    }
+
+    #[test]
+    fn test_identity_and_composition_order () {
+        let inv = ColorMatrix::invert();
+        assert! (ColorMatrix::IDENTITY.then(&inv) .approx_eq (&inv, EPS));
+        assert! (inv.then(&ColorMatrix::IDENTITY) .approx_eq (&inv, EPS));
+        assert! (inv.then(&inv) .is_identity());
+
+        // 'invert then brightness 0.5' must differ from 'brightness 0.5 then invert'
+        let half = ColorMatrix::brightness (0.5);
+        let c = [0.2, 0.4, 0.6, 1.0];
+        assert_rgb_eq (inv.then(&half).apply(c), [0.4, 0.3, 0.2, 1.0]);
+        assert_rgb_eq (half.then(&inv).apply(c), [0.9, 0.8, 0.7, 1.0]);
+        assert_rgb_eq (inv.then(&half).apply(c), half.apply (inv.apply(c)));
+    }
+
+    #[test]
+    fn test_lerp () {
+        let inv = ColorMatrix::invert();
+        assert! (ColorMatrix::lerp (&ColorMatrix::IDENTITY, &inv, 0.0) .is_identity());
+        assert! (ColorMatrix::lerp (&ColorMatrix::IDENTITY, &inv, 1.0) .approx_eq (&inv, EPS));
+        // a half-strength inversion pulls everything to mid-gray (with alpha untouched)
+        let half = ColorMatrix::lerp (&ColorMatrix::IDENTITY, &inv, 0.5);
+        assert_rgb_eq (half.apply ([0.2, 0.9, 0.0, 1.0]), [0.5, 0.5, 0.5, 1.0]);
+        let partial = ColorMatrix::lerp (&ColorMatrix::IDENTITY, &inv, 0.7);
+        assert_rgb_eq (partial.apply ([1.0, 0.0, 0.5, 1.0]), [0.3, 0.7, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_matches_mag_convention () {
+        // the default config 'Simple Inversion' matrix, written out as MAGCOLOREFFECT would hold it
+        let simple_inversion = ColorMatrix ( [
+            -1.0,  0.0,  0.0,  0.0,  0.0,
+             0.0, -1.0,  0.0,  0.0,  0.0,
+             0.0,  0.0, -1.0,  0.0,  0.0,
+             0.0,  0.0,  0.0,  1.0,  0.0,
+             1.0,  1.0,  1.0,  0.0,  1.0,
+        ] );
+        assert! (ColorMatrix::invert() .approx_eq (&simple_inversion, EPS));
+        assert_rgb_eq (simple_inversion.apply ([1.0, 0.25, 0.0, 1.0]), [0.0, 0.75, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_mat3_helpers () {
+        let m : Mat3 = [[2.0, 1.0, 0.0], [0.5, 3.0, 1.0], [0.0, 1.0, 4.0]];
+        let inv = mat3_inv (&m) .expect ("should be invertible");
+        let prod = mat3_mul (&m, &inv);
+        for r in 0..3 { for c in 0..3 {
+            assert! ((prod[r][c] - MAT3_IDENTITY[r][c]).abs() < EPS);
+        } }
+        assert! (mat3_inv (&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]]) .is_none());
+        assert_eq! (mat3_mul_vec (&m, [1.0, 1.0, 1.0]), [3.0, 4.5, 5.0]);
+
+        // the 5x5 determinant of an rgb-only mix should match its 3x3 determinant
+        assert! ((ColorMatrix::from_rgb_mix (m, [0.3; 3]) .determinant() - mat3_det(&m)).abs() < EPS);
+        assert! ((ColorMatrix::invert().determinant() + 1.0).abs() < EPS);
+        assert! (ColorMatrix::grayscale(1.0).determinant().abs() < EPS);
+    }
+
+    #[test]
+    fn test_primitives () {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        let red = [1.0, 0.0, 0.0, 1.0];
+
+        // luminance preserving ops should keep white as white
+        for m in [ColorMatrix::hue_rotate(123.0), ColorMatrix::saturate(1.7), ColorMatrix::grayscale(1.0)] {
+            assert_rgb_eq (m.apply(white), white);
+        }
+        assert! (ColorMatrix::hue_rotate(0.0).is_identity());
+        assert! (ColorMatrix::hue_rotate(360.0) .approx_eq (&ColorMatrix::IDENTITY, EPS));
+        assert! (ColorMatrix::saturate(1.0).is_identity());
+        assert! (ColorMatrix::sepia(0.0).is_identity());
+        assert! (ColorMatrix::grayscale(0.0).is_identity());
+        assert! (ColorMatrix::contrast(1.0).is_identity());
+
+        let gray = ColorMatrix::grayscale(1.0).apply(red);
+        assert_rgb_eq (gray, [0.2126, 0.2126, 0.2126, 1.0]);
+        assert_rgb_eq (ColorMatrix::saturate(0.0).apply(red), [0.213, 0.213, 0.213, 1.0]);
+
+        assert_rgb_eq (ColorMatrix::contrast(0.0).apply(red), [0.5, 0.5, 0.5, 1.0]);
+        assert_rgb_eq (ColorMatrix::contrast(2.0).apply([0.75, 0.5, 0.25, 1.0]), [1.0, 0.5, 0.0, 1.0]);
+
+        assert_rgb_eq (ColorMatrix::tint([1.0, 0.5, 0.25]).apply(white), [1.0, 0.5, 0.25, 1.0]);
+        assert_rgb_eq (ColorMatrix::channel_swap([2, 1, 0]).apply([0.1, 0.2, 0.3, 1.0]), [0.3, 0.2, 0.1, 1.0]);
+
+        // two half-turns of hue should compose back to (approx) identity
+        let h = ColorMatrix::hue_rotate(180.0);
+        assert! (h.then(&h) .approx_eq (&ColorMatrix::IDENTITY, 1e-3));
+    }
+
+}
@@ -0,0 +1,224 @@
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+
+use std::path::{Path, PathBuf};
+
+use crate::effects::compose;
+use crate::effects::matrix::ColorMatrix;
+use crate::effects::spec::ColorEffectSpec;
+
+
+
+// Offline rendering of color-effects onto images .. for designing and reviewing [[effects]] w/o a live screen.
+// The matrices are applied with the exact same semantics as MagSetColorEffect (row-vector [r,g,b,a,1] times the
+// 5x5, with the last row as translation), on 0..1 normalized channels, with outputs clamped back into range.
+// Nothing here touches any windows apis, so it can be used anywhere the effect defs themselves can be compiled ..
+// (which is how the standalone 'dusky-preview' bin gets to build and run on linux too)
+
+
+
+/// Tiles in a contact sheet get downscaled to fit within this (to keep sheets of large screenshots manageable)
+pub const SHEET_TILE_MAX_DIM : u32 = 640;
+
+/// Gap (in px) between contact sheet tiles
+const SHEET_GAP : u32 = 8;
+
+const SHEET_BACKGROUND : Rgba<u8> = Rgba ([40, 40, 40, 255]);
+
+
+
+/// Applies the color matrix to every pixel of the image
+pub fn apply_to_image (img: &RgbaImage, m: &ColorMatrix) -> RgbaImage {
+    let mut out = img.clone();
+    for px in out.pixels_mut() {
+        let rgba = px.0 .map (|v| v as f32 / 255.0);
+        let res = m.apply (rgba);
+        px.0 = res .map (|v| (v.clamp (0.0, 1.0) * 255.0).round() as u8);
+    }
+    out
+}
+
+
+/// Renders a grid of the source image through each of the given effects (the first tile is always the original)
+pub fn render_contact_sheet (img: &RgbaImage, effects: &[(String, ColorMatrix)]) -> RgbaImage {
+
+    let (w, h) = img.dimensions();
+    let tile = if w.max(h) > SHEET_TILE_MAX_DIM {
+        let scale = SHEET_TILE_MAX_DIM as f32 / w.max(h) as f32;
+        let (tw, th) = (((w as f32 * scale) as u32).max(1), ((h as f32 * scale) as u32).max(1));
+        imageops::resize (img, tw, th, FilterType::Triangle)
+    } else { img.clone() };
+    // ^^ we downscale before applying effects, as thats much cheaper (and the difference is negligible for review)
+
+    let n_tiles = effects.len() as u32 + 1;
+    let cols = (n_tiles as f32).sqrt().ceil() as u32;
+    let rows = n_tiles .div_ceil (cols);
+    let (tw, th) = tile.dimensions();
+    let mut sheet = RgbaImage::from_pixel (
+        cols * tw + (cols + 1) * SHEET_GAP,  rows * th + (rows + 1) * SHEET_GAP,  SHEET_BACKGROUND
+    );
+
+    let tiles = std::iter::once (tile.clone()) .chain (effects .iter() .map (|(_, m)| apply_to_image (&tile, m)));
+    for (i, t) in tiles.enumerate() {
+        let (c, r) = (i as u32 % cols, i as u32 / cols);
+        let (x, y) = (SHEET_GAP + c * (tw + SHEET_GAP), SHEET_GAP + r * (th + SHEET_GAP));
+        imageops::replace (&mut sheet, &t, x as i64, y as i64);
+    }
+    sheet
+}
+
+
+/// Turns an effect name into something safe to use in a file name
+pub fn file_name_for_effect (name: &str) -> String {
+    let s : String = name .chars() .map (|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }) .collect();
+    s .split ('_') .filter (|p| !p.is_empty()) .collect::<Vec<_>>() .join ("_")
+}
+
+
+
+#[derive (Debug, Default)]
+pub struct PreviewArgs {
+    pub image      : PathBuf,
+    pub effects    : Vec <String>,
+    pub out        : Option <PathBuf>,
+    pub individual : bool,
+    pub conf       : Option <String>,
+}
+
+pub fn parse_preview_args (args: &[String]) -> Result <PreviewArgs, String> {
+    let mut pargs = PreviewArgs::default();
+    let mut image = None;
+    let mut iter = args.iter();
+    while let Some (arg) = iter.next() {
+        let mut value = || iter.next() .cloned() .ok_or (format! ("Missing value for {arg:?}"));
+        match arg.as_str() {
+            "-e" | "--effect"     => pargs.effects.push (value()?),
+            "-o" | "--out"        => pargs.out = Some (PathBuf::from (value()?)),
+            "-c" | "--conf"       => pargs.conf = Some (value()?),
+            "-i" | "--individual" => pargs.individual = true,
+            _ if arg.starts_with('-') => return Err (format! ("Unknown preview option {arg:?}")),
+            _ if image.is_none()  => image = Some (PathBuf::from (arg)),
+            _ => return Err (format! ("Unexpected preview argument {arg:?}")),
+        }
+    }
+    pargs.image = image .ok_or ("No input image specified for preview")?;
+    Ok (pargs)
+}
+
+
+/// Renders the image through the requested effects (or all of them) and writes out the PNGs .. returns the exit code
+pub fn run_preview (pargs: PreviewArgs, specs: &[ColorEffectSpec]) -> i32 {
+
+    let img = match image::open (&pargs.image) {
+        Ok (img) => img.to_rgba8(),
+        Err (e) => { eprintln! ("Failed to load image {:?} : {e}", pargs.image); return 2 }
+    };
+
+    // we'll compile all the defined effects (in definition order), and then pick out the requested ones (if any)
+    let mut compiled : Vec <(String, ColorMatrix)> = vec![];
+    for (name, res) in compose::compile_effects (specs) {
+        match res {
+            Ok (m) => { if !compiled .iter() .any (|(s,_)| *s == name) { compiled.push ((name, m)) } }
+            Err (e) => eprintln! ("Skipping effect {name:?} .. {e}"),
+        }
+    }
+    let effects : Vec <(String, ColorMatrix)> = if pargs.effects.is_empty() { compiled } else {
+        let mut picked = vec![];
+        for name in pargs.effects.iter() {
+            match compiled .iter() .find (|(s,_)| s == name) {
+                Some (eff) => picked.push (eff.clone()),
+                None => {
+                    eprintln! ("Effect {name:?} is not defined .. available effects are :");
+                    compiled .iter() .for_each (|(s,_)| eprintln! ("  {s}"));
+                    return 2
+                }
+            }
+        }
+        picked
+    };
+    if effects.is_empty() {
+        eprintln! ("No (valid) effects to render!");
+        return 1
+    }
+
+    let stem = pargs.image.file_stem() .map (|s| s.to_string_lossy().to_string()) .unwrap_or ("preview".into());
+    let src_dir = pargs.image.parent() .map (Path::to_path_buf) .unwrap_or_default();
+
+    if pargs.individual {
+        let out_dir = pargs.out.unwrap_or (src_dir);
+        if let Err (e) = std::fs::create_dir_all (&out_dir) {
+            eprintln! ("Failed to create output directory {out_dir:?} : {e}");
+            return 1
+        }
+        for (name, m) in effects.iter() {
+            let path = out_dir.join (format! ("{stem}.{}.png", file_name_for_effect (name)));
+            if let Err (e) = apply_to_image (&img, m) .save (&path) {
+                eprintln! ("Failed to write {path:?} : {e}");
+                return 1
+            }
+            println! ("{name:?} -> {path:?}");
+        }
+    } else {
+        let path = pargs.out.unwrap_or (src_dir.join (format! ("{stem}.effects-preview.png")));
+        if let Err (e) = render_contact_sheet (&img, &effects) .save (&path) {
+            eprintln! ("Failed to write {path:?} : {e}");
+            return 1
+        }
+        println! ("Wrote contact sheet to {path:?} .. tiles (left-to-right, top-to-bottom) :");
+        println! ("  1. (original)");
+        effects .iter() .enumerate() .for_each (|(i, (name, _))| println! ("  {}. {name}", i + 2));
+    }
+    0
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_image () -> RgbaImage {
+        RgbaImage::from_fn (4, 2, |x, y| Rgba ([(x * 60) as u8, (y * 200) as u8, 128, 255]))
+    }
+
+    #[test]
+    fn test_apply_to_image () {
+        let img = sample_image();
+        assert_eq! (apply_to_image (&img, &ColorMatrix::IDENTITY), img);
+
+        let inv = apply_to_image (&img, &ColorMatrix::invert());
+        for (a, b) in img.pixels() .zip (inv.pixels()) {
+            assert_eq! (b.0, [255 - a.0[0], 255 - a.0[1], 255 - a.0[2], 255]);
+        }
+        // out of range outputs clamp (like they would on screen)
+        let bright = apply_to_image (&img, &ColorMatrix::brightness (10.0));
+        assert_eq! (bright.get_pixel (1, 1).0, [255, 255, 255, 255]);
+        assert_eq! (bright.get_pixel (0, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_contact_sheet_layout () {
+        let img = sample_image();
+        let effects = vec! [
+            ("Invert".to_string(), ColorMatrix::invert()),
+            ("Gray".to_string(), ColorMatrix::grayscale (1.0)),
+            ("Sepia".to_string(), ColorMatrix::sepia (1.0)),
+        ];
+        // 4 tiles (original + 3) go into a 2x2 grid
+        let sheet = render_contact_sheet (&img, &effects);
+        assert_eq! (sheet.dimensions(), (2 * 4 + 3 * SHEET_GAP, 2 * 2 + 3 * SHEET_GAP));
+        assert_eq! (sheet.get_pixel (SHEET_GAP, SHEET_GAP), img.get_pixel (0, 0));
+        let inv_px = sheet.get_pixel (SHEET_GAP * 2 + 4, SHEET_GAP);
+        assert_eq! (inv_px.0, [255, 255, 127, 255]);
+        assert_eq! (sheet.get_pixel (0, 0), &SHEET_BACKGROUND);
+    }
+
+    #[test]
+    fn test_file_names () {
+        assert_eq! (file_name_for_effect ("Smart Inversion V3 Vivid"), "Smart_Inversion_V3_Vivid");
+        assert_eq! (file_name_for_effect ("CVD Simulation: Protanopia"), "CVD_Simulation_Protanopia");
+    }
+
+}
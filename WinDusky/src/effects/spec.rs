@@ -0,0 +1,69 @@
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+
+
+// The [[effects]] definitions as specified in config .. parsed here w/o anything else from the config (or any windows
+// apis), so the offline tools (e.g. the preview bin) can load effect definitions straight from a conf file too.
+
+
+
+#[derive (Debug, Clone)]
+pub struct ColorEffectSpec {
+    pub name : String,
+    pub def  : ColorEffectDef,
+}
+
+#[derive (Debug, Clone, PartialEq)]
+pub enum ColorEffectDef {
+    Transform ([f32; 25]),
+    Compose (Vec<String>),
+    // ^^ a chain of named primitives (or other effect names) that gets compiled into a transform at load
+    Fit (Vec <(String, String)>),
+    // ^^ source -> target '#rrggbb' color pairs, that the transform gets least-squares fitted to at load
+}
+
+
+pub fn parse_color_effect (table : &Table) -> Option <ColorEffectSpec> {
+    if let Some (name) = table .get("effect") .and_then (|s| s.as_str() .map (|s| s.to_string())) {
+        if let Some (Item::Value (Value::Array(arr))) = table .get("transform") {
+            let matrix: Vec<f32> = arr.iter() .filter_map (|v|
+                v.as_float() .or_else (|| v.as_integer() .map (|i| i as f64)) .map (|f| f as f32)
+            ) .collect();
+            if matrix.len() == 25 {
+                let mut transform = [0.0f32; 25];
+                transform.copy_from_slice (&matrix);
+                return Some ( ColorEffectSpec { name, def: ColorEffectDef::Transform (transform) } )
+            }
+        }
+        if let Some (Item::Value (Value::Array(arr))) = table .get("compose") {
+            let ops : Vec<String> = arr.iter() .filter_map (|v| v.as_str() .map (|s| s.trim().to_string())) .collect();
+            if !ops.is_empty() {
+                return Some ( ColorEffectSpec { name, def: ColorEffectDef::Compose (ops) } )
+            }
+        }
+        if let Some (Item::Value (Value::Array(arr))) = table .get("fit") {
+            let pairs : Option <Vec<(String, String)>> = arr.iter() .map (|v| {
+                let pair = v.as_array()?;
+                if pair.len() != 2 { return None }
+                Some (( pair.get(0)?.as_str()?.trim().to_string(), pair.get(1)?.as_str()?.trim().to_string() ))
+            } ) .collect();
+            if let Some (pairs) = pairs .filter (|p| !p.is_empty()) {
+                return Some ( ColorEffectSpec { name, def: ColorEffectDef::Fit (pairs) } )
+            }
+        }
+    }
+    None
+}
+
+/// Parses all the [[effects]] tables in a conf doc .. returns the parsed specs, and the names of any malformed entries
+pub fn parse_color_effects (doc: &DocumentMut) -> (Vec <ColorEffectSpec>, Vec <String>) {
+    let (mut specs, mut malformed) = (vec![], vec![]);
+    let tables = doc .get ("effects") .and_then (|t| t.as_array_of_tables());
+    for tbl in tables .iter() .flat_map (|t| t.iter()) {
+        match parse_color_effect (tbl) {
+            Some (spec) => specs.push (spec),
+            None => malformed.push (tbl .get("effect") .and_then (|s| s.as_str()) .unwrap_or("<unnamed>") .to_string()),
+        }
+    }
+    (specs, malformed)
+}
@@ -0,0 +1,140 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::effects::matrix::ColorMatrix;
+
+
+
+// Animated transitions between effect matrices .. rather than snapping to a new effect, we step through interpolated
+// matrices on the dusky timer ticks until the target is reached. Since every effect is just a 5x5, interpolating the
+// matrices (with some easing) gives a smooth cross-fade between any two effects (incl to/from identity for on/off).
+
+
+
+#[derive (Debug, Copy, Clone)]
+pub struct EffectTransition {
+    pub from     : ColorMatrix,
+    pub to       : ColorMatrix,
+    pub start    : Instant,
+    pub duration : Duration,
+}
+
+impl EffectTransition {
+
+    /// Linear progress (0..1) of the transition at given time
+    pub fn progress (&self, now: Instant) -> f32 {
+        if self.duration.is_zero() { return 1.0 }
+        (now.saturating_duration_since (self.start) .as_secs_f32() / self.duration.as_secs_f32()) .clamp (0.0, 1.0)
+    }
+
+    /// The interpolated matrix at given time (eased, so it starts and ends gently)
+    pub fn matrix_at (&self, now: Instant) -> ColorMatrix {
+        let t = self.progress (now);
+        let eased = t * t * (3.0 - 2.0 * t);
+        // ^^ smoothstep
+        ColorMatrix::lerp (&self.from, &self.to, eased)
+    }
+
+    pub fn is_done (&self, now: Instant) -> bool {
+        self.progress (now) >= 1.0
+    }
+}
+
+
+
+#[derive (Debug, Default)]
+struct AnimatorState {
+    shown      : ColorMatrix,
+    transition : Option <EffectTransition>,
+}
+
+/// Tracks what matrix is currently shown (for an overlay or full-screen), and any transition in progress from it
+#[derive (Debug, Default)]
+pub struct EffectAnimator (Mutex <AnimatorState>);
+
+
+impl EffectAnimator {
+
+    /// Starts a transition to the target (from whatever is shown now, even if mid-transition), returning the matrix to apply now
+    pub fn start (&self, target: ColorMatrix, duration: Duration, now: Instant) -> ColorMatrix {
+        let mut state = self.0.lock().unwrap();
+        if duration.is_zero() || state.shown == target {
+            state.shown = target;
+            state.transition = None;
+            return target
+        }
+        state.transition = Some ( EffectTransition { from: state.shown, to: target, start: now, duration } );
+        state.shown
+    }
+
+    /// Advances any transition in progress, returning the updated matrix to apply (if there was one in progress)
+    pub fn step (&self, now: Instant) -> Option <ColorMatrix> {
+        let mut state = self.0.lock().unwrap();
+        let tr = state.transition?;
+        state.shown = tr.matrix_at (now);
+        if tr.is_done (now) {
+            state.shown = tr.to;
+            state.transition = None;
+        }
+        Some (state.shown)
+    }
+
+    /// The matrix currently shown (i.e. mid-transition, this is where we are along the way)
+    pub fn shown (&self) -> ColorMatrix {
+        self.0.lock().unwrap() .shown
+    }
+
+    pub fn is_animating (&self) -> bool {
+        self.0.lock().unwrap() .transition.is_some()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_interpolation () {
+        let start = Instant::now();
+        let tr = EffectTransition {
+            from: ColorMatrix::IDENTITY, to: ColorMatrix::invert(), start, duration: Duration::from_millis (200)
+        };
+        assert! (tr.matrix_at (start) .is_identity());
+        assert! (tr.matrix_at (start + Duration::from_millis (100)) .approx_eq (&ColorMatrix::lerp (&tr.from, &tr.to, 0.5), 1e-6));
+        assert! (tr.matrix_at (start + Duration::from_millis (500)) .approx_eq (&tr.to, 1e-6));
+        assert! (!tr.is_done (start + Duration::from_millis (150)));
+        assert! (tr.is_done (start + Duration::from_millis (200)));
+        // eased, so the first quarter covers less than a quarter of the way
+        assert! (tr.matrix_at (start + Duration::from_millis (50)) .get (0, 0) > 0.5);
+    }
+
+    #[test]
+    fn test_animator () {
+        let anim = EffectAnimator::default();
+        let t0 = Instant::now();
+        let dur = Duration::from_millis (100);
+
+        // w/o a duration, it just snaps
+        assert! (anim.start (ColorMatrix::sepia (1.0), Duration::ZERO, t0) .approx_eq (&ColorMatrix::sepia (1.0), 1e-6));
+        assert! (!anim.is_animating());
+        assert! (anim.step (t0) .is_none());
+
+        // otherwise, the current matrix stays until stepped, and we land exactly on the target at the end
+        let shown = anim.start (ColorMatrix::invert(), dur, t0);
+        assert! (shown .approx_eq (&ColorMatrix::sepia (1.0), 1e-6));
+        assert! (anim.is_animating());
+        let mid = anim.step (t0 + dur / 2) .unwrap();
+        assert! (!mid .approx_eq (&ColorMatrix::invert(), 1e-3));
+
+        // re-targeting mid-way starts from whatever is currently shown
+        let shown = anim.start (ColorMatrix::IDENTITY, dur, t0 + dur / 2);
+        assert! (shown .approx_eq (&mid, 1e-6));
+        assert! (anim.step (t0 + dur * 2) .unwrap() .is_identity());
+        assert! (!anim.is_animating());
+    }
+
+}
@@ -1,28 +1,32 @@
 use std::ffi::OsString;
+use std::fs;
 use std::mem::zeroed;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
 use tracing::{info, warn};
 use windows::core::{BOOL, PCWSTR};
-use windows::Win32::Foundation::{LPARAM, RECT};
-use windows::Win32::Graphics::Gdi::{CreateDCW, DeleteDC, EnumDisplayMonitors, GetDC, GetMonitorInfoW, ReleaseDC, HDC, HMONITOR, MONITORINFOEXW};
+use windows::Win32::Foundation::{LPARAM, POINT, RECT};
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD};
+use windows::Win32::Graphics::Gdi::{CreateDCW, DeleteDC, EnumDisplayMonitors, GetDC, GetMonitorInfoW, MonitorFromPoint, MonitorFromWindow, ReleaseDC, HDC, HMONITOR, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST};
 use windows::Win32::UI::ColorSystem::{GetDeviceGammaRamp, SetDeviceGammaRamp};
 
+use crate::effects::fit::{fit_ramp_matrix, ColorFit};
+use crate::types::Hwnd;
+use crate::win_utils::wide_string;
 
+pub mod blackbody;
+pub mod calfile;
+pub mod limits;
+pub mod ramp;
+pub mod restore;
+pub mod schedule;
+pub mod tune;
 
-
-
-#[derive (Debug, Copy, Clone, PartialEq, PartialOrd)]
-pub struct GBC {
-    gamma    : f32,   // default 1.0 .. expectation 0.0 and up
-    bright   : f32,   // default 0.0 .. expectation -1.0 to 1.0
-    contrast : f32,   // default 1.0 .. expectation  0.0 and up
-    // todo .. ^^ update these comments with the clamp values we use
-}
-impl Default for GBC {
-    fn default() -> GBC {
-        GBC { gamma : 1.0, bright : 0.0, contrast : 1.0 }
-    }
-}
+pub use ramp::{calc_gbct_ramp, GBC, NEUTRAL_TEMP};
+pub use restore::{GammaRamp, SCREEN_DC_KEY};
+use limits::{RampLimits, GAMMA_RANGE_REG_KEY, GAMMA_RANGE_REG_VALUE, GAMMA_RANGE_REMEDY};
 
 
 
@@ -38,99 +42,18 @@ pub struct MonitorInfo {
 
 
 
-impl GBC {
-
-    pub fn new (gamma: f32, bright: f32, contrast: f32) -> GBC {
-        GBC { gamma, bright, contrast }
-    }
-
-    /// Gamma ramp are arrays of 256 mapping values for each channel (r,g,b)
-    pub fn create_gamma_ramp (&self) -> [[u16; 256]; 3] {
-        let max_gamma = 4.4f32;
-        let min_gamma = 0.3f32;
-        let gamma = self.gamma.clamp(min_gamma, max_gamma);
-
-        let max_bright = 1.0f32;
-        let min_bright = -1.0f32;
-        let bright = self.bright.clamp(min_bright, max_bright);
-
-        let max_contrast = 100.0f32;
-        let min_contrast = 0.1f32;
-        let contrast = self.contrast.clamp(min_contrast, max_contrast);
-
-        let inv_gamma = 1.0 / gamma as f64;
-        let norm = 255.0f64.powf(inv_gamma - 1.0);
-        let mut ramp = [[0u16; 256]; 3];
-        for i in 0..256 {
-            let mut val = i as f64 * contrast as f64 - (contrast as f64 - 1.0) * 127.0;
-            if (gamma - 1.0).abs() > 1e-6 {
-                val = val.powf(inv_gamma) / norm;
-            }
-            val += bright as f64 * 128.0;
-            let v = (val * 256.0).round() as i32;
-            let v = v.clamp(0, 65535) as u16;
-            (ramp[0][i], ramp[1][i], ramp[2][i]) = (v, v, v);
-        }
-        ramp
-    }
-}
-
-
-
-
-
-/// Color temperature to RGB (0..1 floats)
-pub fn color_temp_to_rgb (kelvin: u32) -> [f32; 3] {
-    let temp = kelvin.clamp (2000, 10000) as f32 / 100.0;
-    let mut r: f32;
-    let mut g: f32;
-    let mut b: f32;
-    if temp <= 66.0 {
-        r = 255.0;
-    } else {
-        r = 329.70 * (temp - 60.0) .powf (-0.133);
-        r = r .clamp (0.0, 255.0);
-    }
-    if temp <= 66.0 {
-        g = 99.47 * temp.ln() - 161.12;
-        g = g .clamp (0.0, 255.0);
-    } else {
-        g = 288.12 * (temp - 60.0) .powf (-0.075);
-        g = g .clamp (0.0, 255.0);
-    }
-    if temp >= 66.0 {
-        b = 255.0;
-    } else if temp <= 19.0 {
-        b = 0.0;
-    } else {
-        b = 138.5 * (temp - 10.0).ln() - 305.05;
-        b = b .clamp (0.0, 255.0);
+/// Get the info (device name, rect etc) for a given monitor handle
+pub fn get_monitor_info (hmonitor: HMONITOR) -> Option <MonitorInfo> { unsafe {
+    let mut mi: MONITORINFOEXW = zeroed();
+    mi.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+    if !GetMonitorInfoW (hmonitor, &mut mi.monitorInfo as *mut _ as *mut _) .as_bool() {
+        return None
     }
-    [r / 255.0, g / 255.0, b / 255.0]
-}
-
-
-
-/// Blend/apply color temperature to a gamma ramp
-pub fn apply_color_temp_to_ramp (ramp: &mut [[u16; 256]; 3], color_temp: u32) {
-    let rgb_std = color_temp_to_rgb (6500);
-    let rgb_target = color_temp_to_rgb (color_temp);
-    for c in 0..3 {
-        let mult = rgb_target[c] / rgb_std[c].max(1e-6);
-        for i in 0..256 {
-            let v = ( ramp[c][i] as f32 * mult ) .round() as i32;
-            ramp[c][i] = v .clamp (0, 65535) as u16;
-        }
-    }
-}
-
-
-
-
+    let name = OsString::from_wide(&mi.szDevice) .to_string_lossy() .trim_end_matches('\0') .to_string();
+    Some ( MonitorInfo { hmonitor, device_name: name, rect: mi.monitorInfo.rcMonitor } )
+} }
 
-/// Enumerate all monitors and return their info <br>
-/// (For now, just a stub until we add per-monitor support)
-#[allow (dead_code)]
+/// Enumerate all monitors and return their info (in the OS enumeration order)
 pub fn enumerate_monitors() -> Vec<MonitorInfo> {
 
     let mut monitors = Vec::new();
@@ -139,11 +62,7 @@ pub fn enumerate_monitors() -> Vec<MonitorInfo> {
         hmonitor: HMONITOR, _hdc: HDC, _lprc: *mut RECT, lparam: LPARAM,
     ) -> BOOL {
         let monitors = &mut *(lparam.0 as *mut Vec<MonitorInfo>);
-        let mut mi: MONITORINFOEXW = zeroed();
-        mi.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
-        if GetMonitorInfoW (hmonitor, &mut mi.monitorInfo as *mut _ as *mut _) .as_bool() {
-            let name = OsString::from_wide(&mi.szDevice) .to_string_lossy() .trim_end_matches('\0') .to_string();
-            let info = MonitorInfo { hmonitor, device_name: name, rect: mi.monitorInfo.rcMonitor };
+        if let Some (info) = get_monitor_info (hmonitor) {
             monitors.push (info);
         }
         true.into()
@@ -157,6 +76,17 @@ pub fn enumerate_monitors() -> Vec<MonitorInfo> {
 }
 
 
+/// The device name of the monitor that contains (or is nearest to) the given screen point
+pub fn get_monitor_device_at_point (point: POINT) -> Option <String> { unsafe {
+    get_monitor_info (MonitorFromPoint (point, MONITOR_DEFAULTTONEAREST)) .map (|mi| mi.device_name)
+} }
+
+/// The device name of the monitor that has the largest intersection with the given window
+pub fn get_monitor_device_for_hwnd (hwnd: Hwnd) -> Option <String> { unsafe {
+    get_monitor_info (MonitorFromWindow (hwnd.into(), MONITOR_DEFAULTTONEAREST)) .map (|mi| mi.device_name)
+} }
+
+
 /// Get a device context for the entire screen
 pub fn get_screen_dc () -> Option <HDC> { unsafe {
     let hdc = GetDC (None);
@@ -168,12 +98,16 @@ pub fn get_screen_dc () -> Option <HDC> { unsafe {
 } }
 
 
-/// Get a device context for a given device name (monitor)
-#[allow (dead_code)]
+/// Get a device context for a given device name (monitor) .. (these must be freed w delete_dc rather than release_dc)
 pub fn get_monitor_dc (device_name: &str) -> Option<HDC> { unsafe {
     let device: Vec<u16> = OsString::from (device_name) .encode_wide() .chain([0]) .collect();
-    let hdc = CreateDCW (PCWSTR::default(), PCWSTR::from_raw (device.as_ptr()), PCWSTR::default(), None);
-    (!hdc.is_invalid()) .then_some (hdc)
+    let hdc = CreateDCW (PCWSTR::from_raw (device.as_ptr()), PCWSTR::from_raw (device.as_ptr()), PCWSTR::default(), None);
+    // ^^ for display devices, the driver can be the device name itself (and then the device must be null or the same name)
+    if hdc.is_invalid() {
+        warn! ("Failed to get monitor DC for {:?}", device_name);
+        return None;
+    }
+    Some (hdc)
 } }
 
 
@@ -193,7 +127,6 @@ pub fn get_current_gamma_ramp (hdc: HDC) -> Option<[[u16; 256]; 3]> { unsafe {
 } }
 
 
-#[allow (dead_code)]
 pub fn delete_dc (hdc: HDC) { unsafe {
     let _ = DeleteDC (hdc);
 } }
@@ -203,31 +136,253 @@ pub fn release_dc (hdc:HDC) { unsafe {
 } }
 
 
-pub fn calc_gbct_ramp (gbc: &GBC, t: u32) -> [[u16; 256]; 3] {
-    let mut ramp = gbc.create_gamma_ramp();
-    apply_color_temp_to_ramp (&mut ramp, t);
-    ramp
+/// What came of applying a gamma ramp
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RampApplied {
+    Exact,
+    Clamped,   // <- applied, but only after clamping into what Windows accepts (see limits.rs)
+    Failed,
+}
+impl RampApplied {
+    pub fn succeeded (&self) -> bool { *self != RampApplied::Failed }
+    pub fn is_clamped (&self) -> bool { *self == RampApplied::Clamped }
+}
+
+
+static RAMP_LIMITS : OnceLock <RampLimits> = OnceLock::new();
+
+/// The OS gamma ramp acceptance limits, per the GdiIcmGammaRange policy (read once, as it only applies after a re-login anyway)
+pub fn get_ramp_limits () -> RampLimits {
+    *RAMP_LIMITS .get_or_init (|| {
+        let reg_value = read_gamma_range_reg_value();
+        let limits = RampLimits::from_gamma_range (reg_value);
+        info! ("Gamma ramp acceptance range : ±{} levels from identity (GdiIcmGammaRange : {:?})", limits.max_deviation, reg_value);
+        limits
+    } )
+}
+
+fn read_gamma_range_reg_value () -> Option <u32> { unsafe {
+    let (key, value) = (wide_string (GAMMA_RANGE_REG_KEY), wide_string (GAMMA_RANGE_REG_VALUE));
+    let (mut data, mut size) = (0u32, size_of::<u32>() as u32);
+    RegGetValueW (
+        HKEY_LOCAL_MACHINE, PCWSTR::from_raw (key.as_ptr()), PCWSTR::from_raw (value.as_ptr()),
+        RRF_RT_REG_DWORD, None, Some (&mut data as *mut u32 as *mut _), Some (&mut size)
+    ) .is_ok() .then_some (data)
+} }
+
+// An optional base ramp (e.g. a display calibration from ArgyllCMS) that presets get layered on, instead of identity
+static BASE_RAMP : RwLock <Option <GammaRamp>> = RwLock::new (None);
+
+/// Loads the base ramp from a .cal or CSV file (see calfile.rs) .. or clears it if no file is given
+pub fn load_base_ramp (file: Option <&Path>) -> Result <(), String> {
+    let ramp = match file {
+        Some (f) => {
+            let s = fs::read_to_string (f) .map_err (|e| format! ("Failed to read base gamma ramp {f:?} : {e}"))?;
+            let parsed = if calfile::is_cal_file (f) { calfile::parse_cal (&s) } else { calfile::parse_csv (&s) };
+            Some (parsed .map_err (|e| format! ("Failed to parse base gamma ramp {f:?} : {e}"))?)
+        }
+        None => None,
+    };
+    info! ("Setting base gamma ramp from : {:?}", file);
+    *BASE_RAMP.write().unwrap() = ramp;
+    Ok(())
+}
+
+/// The computed ramp for the given values, layered on the base ramp if there is one
+pub fn calc_layered_ramp (gbc: &GBC, t: u32, tint: f32) -> GammaRamp {
+    let ramp = calc_gbct_ramp (gbc, t, tint);
+    match BASE_RAMP.read().unwrap().as_ref() {
+        Some (base) => calfile::layer_on_base (&ramp, base),
+        None => ramp,
+    }
+}
+
+/// The ramp we'd actually apply for the given values .. ie clamped into the OS acceptance limits if necessary
+pub fn calc_acceptable_ramp (gbc: &GBC, t: u32, tint: f32) -> (GammaRamp, Option <limits::RampClamp>) {
+    let ramp = calc_layered_ramp (gbc, t, tint);
+    match get_ramp_limits() .clamp (&ramp) {
+        Some ((clamped, report)) => (clamped, Some (report)),
+        None => (ramp, None),
+    }
+}
+
+/// The closest color matrix to the given values .. for when hardware ramps cant be applied (see effects::fit)
+pub fn calc_gbct_matrix (gbc: &GBC, t: u32, tint: f32) -> ColorFit {
+    fit_ramp_matrix (&calc_layered_ramp (gbc, t, tint))
+    // ^^ no OS acceptance limits to clamp to here, so we can use the full ramp
 }
 
-pub fn set_screen_ramp_gbct (gbc: &GBC, t: u32) -> bool {
-    let Some(hdc) = get_screen_dc() else { return false };
-    info! ("Applying Gamma Ramp for screen to {:?}, temp: {:?}", &gbc, t);
-    let ramp = calc_gbct_ramp (gbc, t);
-    let succeeded = set_gamma_ramp_for_dc (hdc, &ramp);
-    if !succeeded { warn! ("Failure setting gamma ramp {:?} t:{:?} for screen DC", &gbc, t) }
+fn apply_acceptable_ramp (hdc: HDC, target: &str, gbc: &GBC, t: u32, tint: f32) -> RampApplied {
+    let (ramp, clamp) = calc_acceptable_ramp (gbc, t, tint);
+    let limits = get_ramp_limits();
+    if let Some (c) = clamp.as_ref() {
+        warn! ( "Gamma ramp {:?} t:{:?} tint:{:?} for {target} strays beyond what Windows accepts (±{} levels from identity) .. \
+                clamped {} entries by up to {:.1} levels. To allow it fully, {GAMMA_RANGE_REMEDY}",
+                &gbc, t, tint, limits.max_deviation, c.n_adjusted, c.max_adjust );
+    }
+    if !set_gamma_ramp_for_dc (hdc, &ramp) {
+        warn! ("Failure setting gamma ramp {:?} t:{:?} for {target}", &gbc, t);
+        if !limits.is_unlimited() {
+            warn! ("(Windows silently refuses ramps it considers too far from identity .. if that's the cause, {GAMMA_RANGE_REMEDY})");
+        }
+        return RampApplied::Failed
+    }
+    if clamp.is_some() { RampApplied::Clamped } else { RampApplied::Exact }
+}
+
+
+pub fn set_screen_ramp_gbct (gbc: &GBC, t: u32, tint: f32) -> RampApplied {
+    let Some(hdc) = get_screen_dc() else { return RampApplied::Failed };
+    info! ("Applying Gamma Ramp for screen to {:?}, temp: {:?}, tint: {:?}", &gbc, t, tint);
+    let applied = apply_acceptable_ramp (hdc, "screen DC", gbc, t, tint);
     release_dc (hdc);
-    succeeded
+    applied
 }
 pub fn reset_screen_ramp () {
-    set_screen_ramp_gbct (&GBC::default(), 6500);
+    if let Some (ramp) = get_original_ramp (SCREEN_DC_KEY) {
+        if let Some (hdc) = get_screen_dc() {
+            let succeeded = set_gamma_ramp_for_dc (hdc, &ramp);
+            release_dc (hdc);
+            if succeeded { return }
+        }
+    }
+    set_screen_ramp_gbct (&GBC::default(), NEUTRAL_TEMP, 0.0);
 }
 
-pub fn check_active_gamma_match (gbc: &GBC, t: u32) -> Option <bool> {
+pub fn check_active_gamma_match (gbc: &GBC, t: u32, tint: f32) -> Option <bool> {
     let hdc = get_screen_dc()?;
-    let cur_ramp = get_current_gamma_ramp(hdc)?;
-    let expected_ramp = calc_gbct_ramp (gbc, t);
+    let cur_ramp = get_current_gamma_ramp(hdc);
     release_dc (hdc);
-    Some (cur_ramp == expected_ramp)
+    let (expected_ramp, _) = calc_acceptable_ramp (gbc, t, tint);
+    Some (cur_ramp? == expected_ramp)
+}
+
+
+pub fn set_monitor_ramp_gbct (device_name: &str, gbc: &GBC, t: u32, tint: f32) -> RampApplied {
+    let Some(hdc) = get_monitor_dc (device_name) else { return RampApplied::Failed };
+    info! ("Applying Gamma Ramp for monitor {:?} to {:?}, temp: {:?}, tint: {:?}", device_name, &gbc, t, tint);
+    let applied = apply_acceptable_ramp (hdc, &format! ("monitor {device_name:?}"), gbc, t, tint);
+    delete_dc (hdc);
+    applied
+}
+pub fn reset_monitor_ramp (device_name: &str) -> bool {
+    if let Some (ramp) = get_original_ramp (device_name) {
+        if let Some (hdc) = get_monitor_dc (device_name) {
+            let succeeded = set_gamma_ramp_for_dc (hdc, &ramp);
+            delete_dc (hdc);
+            if succeeded { return true }
+        }
+    }
+    set_monitor_ramp_gbct (device_name, &GBC::default(), NEUTRAL_TEMP, 0.0) .succeeded()
+}
+
+/// Resets gamma ramps on all monitors (and the screen DC, for good measure)
+pub fn reset_all_ramps () {
+    for mon in enumerate_monitors() {
+        reset_monitor_ramp (&mon.device_name);
+    }
+    reset_screen_ramp();
+}
+
+pub fn check_monitor_gamma_match (device_name: &str, gbc: &GBC, t: u32, tint: f32) -> Option <bool> {
+    let hdc = get_monitor_dc (device_name)?;
+    let cur_ramp = get_current_gamma_ramp(hdc);
+    delete_dc (hdc);
+    Some (cur_ramp? == calc_acceptable_ramp (gbc, t, tint) .0)
+}
+
+
+
+
+// The original ramps (as found at startup, or as persisted by a prior run that didnt exit cleanly) .. see restore.rs
+static ORIGINAL_RAMPS : RwLock <Vec <(String, GammaRamp)>> = RwLock::new (Vec::new());
+static RESTORE_FILE   : OnceLock <Option <PathBuf>> = OnceLock::new();
+static RESTORE_ENDED  : AtomicBool = AtomicBool::new (false);
+
+fn get_original_ramp (key: &str) -> Option <GammaRamp> {
+    ORIGINAL_RAMPS.read().unwrap() .iter() .find (|(k, _)| k == key) .map (|(_, ramp)| *ramp)
+}
+
+/// Captures (and persists) the original gamma ramps at startup .. or if a prior run left its capture behind, restores that
+pub fn init_gamma_restore (restore_file: Option <PathBuf>) -> bool {
+    let file = RESTORE_FILE .get_or_init (|| restore_file);
+
+    if let Some (prior) = file .as_ref() .and_then (|f| fs::read_to_string (f).ok()) {
+        match restore::parse_ramps (&prior) {
+            Ok (ramps) if !ramps.is_empty() => {
+                warn! ("Previous WinDusky run did not exit cleanly .. restoring its saved original gamma ramps");
+                *ORIGINAL_RAMPS.write().unwrap() = ramps;
+                restore_original_ramps();
+                return true
+            }
+            Ok (_)  => { }
+            Err (e) => { warn! ("Ignoring unreadable gamma restore file : {e}") }
+        }
+    }
+
+    let mut ramps = vec![];
+    for mon in enumerate_monitors() {
+        let Some (hdc) = get_monitor_dc (&mon.device_name) else { continue };
+        if let Some (ramp) = get_current_gamma_ramp (hdc) { ramps.push ((mon.device_name, ramp)); }
+        delete_dc (hdc);
+    }
+    if let Some (hdc) = get_screen_dc() {
+        if let Some (ramp) = get_current_gamma_ramp (hdc) { ramps.push ((SCREEN_DC_KEY.to_string(), ramp)); }
+        release_dc (hdc);
+    }
+    info! ("Captured original gamma ramps for : {:?}", ramps .iter() .map (|(k, _)| k) .collect::<Vec<_>>());
+
+    if let Some (f) = file.as_ref() {
+        if let Err (e) = fs::write (f, restore::serialize_ramps (&ramps)) {
+            warn! ("Failed to write gamma restore file {f:?} : {e}");
+        }
+    }
+    *ORIGINAL_RAMPS.write().unwrap() = ramps;
+    false
+}
+
+/// The ramp that 'resetting' a monitor (or the screen DC) puts back .. the original one if we have it, else computed 'Normal'
+pub fn get_reset_ramp (key: &str) -> GammaRamp {
+    get_original_ramp (key) .unwrap_or_else (|| calc_layered_ramp (&GBC::default(), NEUTRAL_TEMP, 0.0))
+}
+
+/// Sets a ramp for a monitor device name, or for the screen DC (via SCREEN_DC_KEY)
+pub fn set_ramp_for_key (key: &str, ramp: &GammaRamp) -> bool {
+    if key == SCREEN_DC_KEY {
+        get_screen_dc() .map (|hdc| { let s = set_gamma_ramp_for_dc (hdc, ramp); release_dc (hdc); s }) .unwrap_or_default()
+    } else {
+        get_monitor_dc (key) .map (|hdc| { let s = set_gamma_ramp_for_dc (hdc, ramp); delete_dc (hdc); s }) .unwrap_or_default()
+    }
+}
+
+/// Gets the current ramp for a monitor device name, or for the screen DC (via SCREEN_DC_KEY)
+pub fn get_ramp_for_key (key: &str) -> Option <GammaRamp> {
+    if key == SCREEN_DC_KEY {
+        get_screen_dc() .and_then (|hdc| { let r = get_current_gamma_ramp (hdc); release_dc (hdc); r })
+    } else {
+        get_monitor_dc (key) .and_then (|hdc| { let r = get_current_gamma_ramp (hdc); delete_dc (hdc); r })
+    }
+}
+
+/// Puts back all the original ramps we have (the per-monitor ones first, then the whole-screen one)
+pub fn restore_original_ramps () {
+    let ramps = ORIGINAL_RAMPS.read().unwrap().clone();
+    for (key, ramp) in ramps.iter() {
+        if !set_ramp_for_key (key, ramp) { warn! ("Failed to restore original gamma ramp for {:?}", key) }
+    }
+}
+
+/// For exit (clean or otherwise) .. optionally restores original ramps, then clears the persisted capture
+pub fn end_gamma_restore (restore: bool) {
+    if RESTORE_ENDED .swap (true, Ordering::AcqRel) { return }
+    // ^^ only the first call counts (e.g. a panic after a clean shutdown, or a restarted instance owning the file by then)
+    if restore {
+        if ORIGINAL_RAMPS.read().unwrap().is_empty() { reset_all_ramps() } else { restore_original_ramps() }
+        // ^^ if we couldnt capture anything at startup, computed 'Normal' ramps are the best we can do
+    }
+    if let Some (f) = RESTORE_FILE .get() .and_then (|f| f.as_ref()) {
+        let _ = fs::remove_file (f);
+    }
 }
 
 
@@ -237,27 +392,28 @@ pub fn check_active_gamma_match (gbc: &GBC, t: u32) -> Option <bool> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::ramp::apply_color_temp_to_ramp;
 
     fn test_dc_gamma (hdc: HDC) {
         println!("Setting gamma ramp to GBC (1.1, -0.25, 0.9) .. ");
-        let ramp = GBC { gamma: 1.1, bright: -0.25, contrast: 0.9 }.create_gamma_ramp();
+        let ramp = GBC::new (1.1, -0.25, 0.9) .create_gamma_ramp();
         assert! (set_gamma_ramp_for_dc (hdc, &ramp), "Failed to set gamma ramp to custom GBC");
         std::thread::sleep (std::time::Duration::from_millis(2000));
 
         println!("Restoring gamma ramp to default GBC (1,0,1)");
-        let ramp = GBC { gamma: 1.0, bright: 0.0, contrast: 1.0 }.create_gamma_ramp();
+        let ramp = GBC::new (1.0, 0.0, 1.0) .create_gamma_ramp();
         assert! (set_gamma_ramp_for_dc (hdc, &ramp), "Failed to set gamma ramp to default GBC");
         //std::thread::sleep (std::time::Duration::from_millis(1000));
 
         println!("Setting gamma ramp to color temp 5000 .. ");
         let mut ramp = GBC::default().create_gamma_ramp();
-        apply_color_temp_to_ramp (&mut ramp, 5000);
+        apply_color_temp_to_ramp (&mut ramp, 5000, 0.0);
         assert! (set_gamma_ramp_for_dc (hdc, &ramp), "Failed to set gamma ramp to color temp 6000");
         std::thread::sleep (std::time::Duration::from_millis(2000));
 
         println!("Restoring gamma ramp to color temp 6500");
         let mut ramp = GBC::default().create_gamma_ramp();
-        apply_color_temp_to_ramp (&mut ramp, 6500);
+        apply_color_temp_to_ramp (&mut ramp, 6500, 0.0);
         assert! (set_gamma_ramp_for_dc (hdc, &ramp), "Failed to set gamma ramp to color temp 6500");
         //std::thread::sleep (std::time::Duration::from_millis(3000));
     }
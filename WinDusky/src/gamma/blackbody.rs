@@ -0,0 +1,113 @@
+// Color temperature (w an optional tint) to rgb, via the CIE Planckian (blackbody) locus.
+//
+// We use Krystek's rational approximation of the locus in CIE 1960 (u,v) space, which is accurate to well within
+// perceptible limits from 1000K to 15000K. Tint is the Duv offset perpendicular to the locus (positive towards green,
+// negative towards magenta), as in the usual CCT/Duv convention. The resulting chromaticity is converted to linear
+// sRGB (whose white point is D65, so D65 itself maps to equal r,g,b), and normalized so the brightest channel is 1.
+
+
+
+pub const TEMP_RANGE : (u32, u32) = (1000, 15000);
+
+/// Tints beyond this are way off the locus (and stop being meaningful as 'white' at all)
+pub const TINT_MAX : f32 = 0.05;
+
+/// CIE XYZ to linear sRGB
+const XYZ_TO_SRGB : [[f64; 3]; 3] = [
+    [ 3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660,  1.8760108,  0.0415560],
+    [ 0.0556434, -0.2040259,  1.0572252],
+];
+
+
+
+/// The (u,v) chromaticity (CIE 1960) of a blackbody at the given temperature
+pub fn planckian_uv (kelvin: f64) -> (f64, f64) {
+    let t = kelvin .clamp (TEMP_RANGE.0 as f64, TEMP_RANGE.1 as f64);
+    let u = (0.860117757 + 1.54118254e-4 * t + 1.28641212e-7 * t * t) / (1.0 + 8.42420235e-4 * t + 7.08145163e-7 * t * t);
+    let v = (0.317398726 + 4.22806245e-5 * t + 4.20481691e-8 * t * t) / (1.0 - 2.89741816e-5 * t + 1.61456053e-7 * t * t);
+    (u, v)
+}
+
+/// The CIE 1931 xy chromaticity for a correlated color temp, offset by the given tint (Duv) perpendicular to the locus
+pub fn cct_to_xy (kelvin: u32, tint: f32) -> (f64, f64) {
+    let t = kelvin .clamp (TEMP_RANGE.0, TEMP_RANGE.1) as f64;
+    let (u, v) = planckian_uv (t);
+
+    // the locus tangent (towards higher temps) runs down-left in uv .. so its left-hand normal points up towards green
+    let (u2, v2) = planckian_uv (t + 1.0);
+    let (du, dv) = (u2 - u, v2 - v);
+    let len = (du * du + dv * dv) .sqrt();
+    let duv = tint .clamp (-TINT_MAX, TINT_MAX) as f64;
+    let (u, v) = (u + duv * dv / len,  v - duv * du / len);
+
+    let d = 2.0 * u - 8.0 * v + 4.0;
+    (3.0 * u / d,  2.0 * v / d)
+}
+
+/// Linear sRGB (D65 white) for the given xy chromaticity, normalized to a max channel of 1 (and clipped to >= 0)
+pub fn xy_to_linear_rgb (x: f64, y: f64) -> [f64; 3] {
+    let xyz = [x / y, 1.0, (1.0 - x - y) / y];
+    let rgb = XYZ_TO_SRGB .map (|row| (0..3) .map (|i| row[i] * xyz[i]) .sum::<f64>() .max (0.0));
+    let max = rgb .iter() .cloned() .fold (1e-9, f64::max);
+    rgb .map (|c| c / max)
+}
+
+/// Color temperature (w tint) to linear rgb (0..1 floats, D65 white)
+pub fn color_temp_to_rgb (kelvin: u32, tint: f32) -> [f32; 3] {
+    let (x, y) = cct_to_xy (kelvin, tint);
+    xy_to_linear_rgb (x, y) .map (|c| c as f32)
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CIE 1931 xy of the D65 white point
+    const D65_XY : (f64, f64) = (0.31271, 0.32902);
+
+    fn assert_xy (kelvin: u32, tint: f32, expected: (f64, f64), tol: f64) {
+        let (x, y) = cct_to_xy (kelvin, tint);
+        assert! ((x - expected.0).abs() < tol && (y - expected.1).abs() < tol, "{kelvin}K : got {:?}, expected {:?}", (x, y), expected);
+    }
+
+    #[test]
+    fn test_planckian_locus () {
+        // reference blackbody chromaticities (CIE 1931 xy, from the CIE/Wyszecki-Stiles tables)
+        assert_xy (1000,  0.0, (0.6528, 0.3444), 5e-4);
+        assert_xy (2000,  0.0, (0.5267, 0.4133), 5e-4);
+        assert_xy (2856,  0.0, (0.4476, 0.4074), 5e-4);   // ~ illuminant A
+        assert_xy (4000,  0.0, (0.3805, 0.3768), 5e-4);
+        assert_xy (5000,  0.0, (0.3451, 0.3516), 5e-4);
+        assert_xy (6500,  0.0, (0.3135, 0.3236), 5e-4);
+        assert_xy (10000, 0.0, (0.2807, 0.2884), 5e-4);
+    }
+
+    #[test]
+    fn test_tint () {
+        // D65 sits at ~6504K, slightly on the green side of the locus (Duv ~ +0.0032)
+        assert_xy (6504, 0.0032, D65_XY, 1e-3);
+        // and a negative tint moves towards magenta (less green, relative to the others)
+        let (neutral, magenta) = (color_temp_to_rgb (5000, 0.0), color_temp_to_rgb (5000, -0.01));
+        assert! (magenta[1] / magenta[0] < neutral[1] / neutral[0]);
+    }
+
+    #[test]
+    fn test_rgb_normalization () {
+        // D65 is the sRGB white point, so it comes out neutral
+        let d65 = xy_to_linear_rgb (D65_XY.0, D65_XY.1);
+        assert! (d65 .iter() .all (|&c| (c - 1.0).abs() < 2e-3), "{d65:?}");
+
+        // low temps are red-heavy, w blue falling off to nothing around 1000K .. and high temps lean blue
+        let warm = color_temp_to_rgb (2700, 0.0);
+        assert! (warm[0] == 1.0 && warm[1] < 0.7 && warm[2] < 0.35, "{warm:?}");
+        assert! (color_temp_to_rgb (1000, 0.0)[2] < 0.01);
+        let cool = color_temp_to_rgb (10000, 0.0);
+        assert! (cool[2] == 1.0 && cool[0] < 0.9, "{cool:?}");
+    }
+
+}
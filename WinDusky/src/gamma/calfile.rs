@@ -0,0 +1,224 @@
+use crate::gamma::restore::GammaRamp;
+
+
+
+// Import/export of gamma ramps as ArgyllCMS calibration (.cal) files, or as plain CSV .. so that a display calibration
+// (e.g. from dispcal) can be loaded as the base ramp that presets get layered on, and so ramps can be taken out for
+// inspection, or for loading into other tools.
+//
+// .cal files are CGATS text : some keyword lines, then a data-format line naming the fields (RGB_I RGB_R RGB_G RGB_B),
+// and then the data rows, all as 0..1 floats. CSV is 'index,red,green,blue' rows w 16-bit values (as we export), though
+// we also accept plain 'red,green,blue' rows, and 0..1 floats. Either way, any number of rows gets resampled to 256.
+
+
+
+/// Whether a file path is for an ArgyllCMS .cal file (else we treat it as CSV)
+pub fn is_cal_file (path: &std::path::Path) -> bool {
+    path.extension() .is_some_and (|e| e.eq_ignore_ascii_case ("cal"))
+}
+
+
+#[allow (clippy::needless_range_loop)]
+pub fn serialize_cal (ramp: &GammaRamp) -> String {
+    let mut out = String::from ("\
+CAL
+
+DESCRIPTOR \"Argyll Device Calibration State\"
+ORIGINATOR \"WinDusky\"
+KEYWORD \"DEVICE_CLASS\"
+DEVICE_CLASS \"DISPLAY\"
+KEYWORD \"COLOR_REP\"
+COLOR_REP \"RGB\"
+
+KEYWORD \"RGB_I\"
+NUMBER_OF_FIELDS 4
+BEGIN_DATA_FORMAT
+RGB_I RGB_R RGB_G RGB_B
+END_DATA_FORMAT
+
+NUMBER_OF_SETS 256
+BEGIN_DATA
+");
+    for i in 0..256 {
+        let [r, g, b] = [0, 1, 2] .map (|c| ramp[c][i] as f64 / 65535.0);
+        out += &format! ("{:.7} {r:.7} {g:.7} {b:.7}\n", i as f64 / 255.0);
+        // ^^ 7 decimals is plenty to get the exact 16-bit values back
+    }
+    out += "END_DATA\n";
+    out
+}
+
+
+pub fn parse_cal (s: &str) -> Result <GammaRamp, String> {
+    let mut tokens = s .split_whitespace();
+    let mut fields : Vec <&str> = vec![];
+    let mut data   : Vec <f64>  = vec![];
+
+    while let Some (tok) = tokens.next() {
+        match tok {
+            "BEGIN_DATA_FORMAT" => {
+                fields = tokens .by_ref() .take_while (|t| *t != "END_DATA_FORMAT") .collect();
+            }
+            "BEGIN_DATA" => {
+                data = tokens .by_ref() .take_while (|t| *t != "END_DATA")
+                    .map (|t| t.parse::<f64>() .map_err (|e| format! ("Bad .cal data value {t:?} : {e}")))
+                    .collect::<Result <_, _>>()?;
+                break
+                // ^^ we only take the first table (multi-table .cal files are rare, and the first is the display calibration)
+            }
+            _ => { }
+        }
+    }
+    if fields.is_empty() { return Err ("No BEGIN_DATA_FORMAT section in .cal file".into()) }
+    if data.is_empty()   { return Err ("No data in .cal file".into()) }
+
+    let col = |name: &str| fields .iter() .position (|f| *f == name) .ok_or (format! ("No {name} field in .cal data format"));
+    let cols = [col ("RGB_I")?, col ("RGB_R")?, col ("RGB_G")?, col ("RGB_B")?];
+    if !data.len() .is_multiple_of (fields.len()) {
+        return Err (format! ("Malformed .cal data .. {} values isn't a multiple of the {} fields", data.len(), fields.len()))
+    }
+    let points = data .chunks (fields.len()) .map (|row| (row[cols[0]], [1, 2, 3] .map (|k| row[cols[k]]))) .collect::<Vec<_>>();
+    resample (points, 1.0)
+}
+
+
+
+#[allow (clippy::needless_range_loop)]
+pub fn serialize_csv (ramp: &GammaRamp) -> String {
+    let mut out = String::from ("index,red,green,blue\n");
+    for i in 0..256 {
+        out += &format! ("{i},{},{},{}\n", ramp[0][i], ramp[1][i], ramp[2][i]);
+    }
+    out
+}
+
+
+pub fn parse_csv (s: &str) -> Result <GammaRamp, String> {
+    let mut rows : Vec <Vec <f64>> = vec![];
+    for line in s .lines() .map (str::trim) .filter (|l| !l.is_empty() && !l.starts_with ('#')) {
+        let vals = line .split ([',', ';', '\t']) .map (|v| v.trim() .parse::<f64>()) .collect::<Result <Vec<_>, _>>();
+        match vals {
+            Ok (vals) if vals.len() == 3 || vals.len() == 4 => rows.push (vals),
+            Ok (vals) => return Err (format! ("Expected 3 or 4 values per CSV ramp row, got {} in : {line:.40}", vals.len())),
+            Err (_) if rows.is_empty() => { }
+            // ^^ header row(s)
+            Err (e) => return Err (format! ("Bad CSV ramp value in {line:.40} : {e}")),
+        }
+    }
+    let Some (n_cols) = rows.first() .map (|r| r.len()) else { return Err ("No ramp rows in CSV".into()) };
+    if rows .iter() .any (|r| r.len() != n_cols) { return Err ("Inconsistent number of values across CSV ramp rows".into()) }
+
+    let n = rows.len();
+    let points = rows .iter() .enumerate() .map (|(i, r)| match n_cols {
+        4 => (r[0], [r[1], r[2], r[3]]),
+        _ => (i as f64, [r[0], r[1], r[2]]),
+    } ) .collect::<Vec<_>>();
+
+    // output values can be 16-bit (as we export), or 0..1 floats .. and inputs are either indices, or 0..1 too
+    let out_scale = if points .iter() .flat_map (|(_, v)| v) .all (|v| *v <= 1.0) { 1.0 } else { 65535.0 };
+    let in_max = points .iter() .map (|(x, _)| *x) .fold (0.0, f64::max);
+    let in_scale = if in_max <= 1.0 { 1.0 } else { (n - 1) .max (in_max as usize) as f64 };
+    let points = points .into_iter() .map (|(x, v)| (x / in_scale, v)) .collect();
+    resample (points, out_scale)
+}
+
+
+
+/// Resamples (input, [r,g,b]) points (inputs 0..1, outputs 0..out_scale) into a 256 entry ramp, interpolating linearly
+#[allow (clippy::needless_range_loop)]
+fn resample (mut points: Vec <(f64, [f64; 3])>, out_scale: f64) -> Result <GammaRamp, String> {
+    if points.len() < 2 { return Err (format! ("Need at least 2 ramp points, got {}", points.len())) }
+    if points .iter() .any (|(x, v)| !x.is_finite() || v .iter() .any (|v| !v.is_finite())) {
+        return Err ("Non-finite ramp values".into())
+    }
+    points .sort_by (|a, b| a.0 .total_cmp (&b.0));
+
+    let mut ramp = [[0u16; 256]; 3];
+    for i in 0..256 {
+        let x = i as f64 / 255.0;
+        let k = points .partition_point (|(px, _)| *px < x) .clamp (1, points.len() - 1);
+        let ((x0, v0), (x1, v1)) = (points[k-1], points[k]);
+        let t = if x1 > x0 { ((x - x0) / (x1 - x0)) .clamp (0.0, 1.0) } else { 1.0 };
+        for c in 0..3 {
+            let v = (v0[c] + (v1[c] - v0[c]) * t) / out_scale;
+            ramp[c][i] = (v * 65535.0) .round() .clamp (0.0, 65535.0) as u16;
+        }
+    }
+    Ok (ramp)
+}
+
+
+/// Layers a ramp on top of a base ramp (e.g. a display calibration) .. ie the ramp's output is fed through the base,
+/// so a neutral (identity) ramp leaves just the base calibration, and an identity base leaves the ramp as is
+pub fn layer_on_base (ramp: &GammaRamp, base: &GammaRamp) -> GammaRamp {
+    std::array::from_fn (|c| ramp[c] .map (|v| {
+        let pos = v as f32 / 256.0;
+        // ^^ position along the base ramp (identity ramps have entry i at i*256)
+        let i = (pos.floor() as usize) .min (254);
+        let t = (pos - i as f32) .min (1.0);
+        (base[c][i] as f32 + (base[c][i+1] as f32 - base[c][i] as f32) * t) .round() .clamp (0.0, 65535.0) as u16
+    } ))
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_ramp () -> GammaRamp {
+        std::array::from_fn (|c| std::array::from_fn (|i| {
+            ((i as f64 / 255.0) .powf (1.0 + c as f64 * 0.1) * 65535.0 * (1.0 - c as f64 * 0.07)) as u16
+        } ))
+    }
+    fn identity () -> GammaRamp {
+        [0, 1, 2] .map (|_| std::array::from_fn (|i| (i * 256) as u16))
+    }
+
+    #[test]
+    fn test_round_trips () {
+        let ramp = test_ramp();
+        assert_eq! (parse_cal (&serialize_cal (&ramp)), Ok (ramp));
+        assert_eq! (parse_csv (&serialize_csv (&ramp)), Ok (ramp));
+        assert_eq! (parse_cal (&serialize_cal (&[[65535; 256]; 3])), Ok ([[65535; 256]; 3]));
+    }
+
+    #[test]
+    fn test_argyll_cal () {
+        // a (trimmed) dispcal style file, w fewer sets and extra keywords .. gets interpolated out to 256 entries
+        let cal = "CAL    \n\nDESCRIPTOR \"Argyll Device Calibration State\"\nORIGINATOR \"Argyll dispcal\"\n\
+            CREATED \"Tue Mar 04 10:11:12 2025\"\nKEYWORD \"DEVICE_CLASS\"\nDEVICE_CLASS \"DISPLAY\"\n\
+            KEYWORD \"VIDEO_LUT_CALIBRATION_POSSIBLE\"\nVIDEO_LUT_CALIBRATION_POSSIBLE \"YES\"\n\n\
+            KEYWORD \"RGB_I\"\nNUMBER_OF_FIELDS 4\nBEGIN_DATA_FORMAT\nRGB_I RGB_R RGB_G RGB_B\nEND_DATA_FORMAT\n\n\
+            NUMBER_OF_SETS 3\nBEGIN_DATA\n0.0 0.0 0.0 0.0\n0.5 0.5 0.45 0.4\n1.0 1.0 0.9 0.8\nEND_DATA\n";
+        let ramp = parse_cal (cal) .unwrap();
+        assert_eq! ([ramp[0][0], ramp[0][255], ramp[1][255], ramp[2][255]], [0, 65535, 58982, 52428]);
+        assert! (ramp .iter() .all (|ch| ch.windows (2) .all (|w| w[0] <= w[1])));
+
+        assert! (parse_cal ("CAL\nBEGIN_DATA\n0 0 0 0\nEND_DATA\n") .is_err());
+        assert! (parse_cal (&cal.replace ("RGB_G", "RGB_X")) .is_err());
+        assert! (parse_cal (&cal.replace ("0.5 0.5 0.45 0.4", "0.5 0.5 0.45")) .is_err());
+    }
+
+    #[test]
+    fn test_csv_variants () {
+        // plain r,g,b rows of 0..1 floats, w no index column, and a header
+        let csv = "r,g,b\n0,0,0\n0.5,0.5,0.25\n1,1,0.5\n";
+        let ramp = parse_csv (csv) .unwrap();
+        assert_eq! ([ramp[0][255], ramp[2][255], ramp[0][0]], [65535, 32768, 0]);
+        assert! (parse_csv ("index,red\n") .is_err());
+        assert! (parse_csv ("0,0,0\n1,2\n") .is_err());
+        assert! (parse_csv ("0,0,0\nx,1,1\n") .is_err());
+    }
+
+    #[test]
+    fn test_layering () {
+        let (ramp, id) = (test_ramp(), identity());
+        assert_eq! (layer_on_base (&id, &ramp), ramp);
+        assert_eq! (layer_on_base (&ramp, &id), ramp .map (|ch| ch .map (|v| v .min (255 * 256))));
+        // ^^ (an identity base tops out at 255*256, so the ramp's very top gets capped there)
+    }
+
+}
@@ -0,0 +1,145 @@
+use crate::gamma::restore::GammaRamp;
+
+
+
+// Windows' acceptance limits for gamma ramps .. SetDeviceGammaRamp silently refuses ramps that stray too far from
+// identity (to stop apps from making the screen unusable), unless the 'GdiIcmGammaRange' policy value is raised.
+//
+// Our model (which matches observed behaviour) : every entry must stay within N 8-bit levels of the identity ramp,
+// where N is 128 by default, or the GdiIcmGammaRange value if set (256 lifting the limit entirely). Ramps must also
+// be non-decreasing. So e.g. color-temps much below ~3300K, or very dark presets, get refused w the default range.
+// To still apply something, we clamp each entry into the allowed band (which is the closest acceptable ramp).
+
+
+
+/// Default allowed deviation from identity (in 8-bit levels) when GdiIcmGammaRange isnt set
+pub const DEFAULT_GAMMA_RANGE : u32 = 128;
+
+/// The GdiIcmGammaRange value that lifts the limits entirely
+pub const UNLIMITED_GAMMA_RANGE : u32 = 256;
+
+pub const GAMMA_RANGE_REG_KEY   : &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\ICM";
+pub const GAMMA_RANGE_REG_VALUE : &str = "GdiIcmGammaRange";
+
+/// How to lift the limits (for the log and tray)
+pub const GAMMA_RANGE_REMEDY : &str = r"set HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion\ICM : GdiIcmGammaRange (DWORD) = 256, then sign out and back in";
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RampLimits {
+    pub max_deviation : u32,
+    // ^^ in 8-bit levels from identity
+}
+
+/// How much a ramp had to be compromised to fit the limits
+#[derive (Debug, Copy, Clone, PartialEq)]
+pub struct RampClamp {
+    pub max_adjust : f32,
+    // ^^ the largest single-entry change (in 8-bit levels)
+    pub n_adjusted : usize,
+    // ^^ how many entries (across all channels) were changed
+}
+
+
+impl Default for RampLimits {
+    fn default() -> Self { RampLimits { max_deviation: DEFAULT_GAMMA_RANGE } }
+}
+
+impl RampLimits {
+
+    /// Limits per the GdiIcmGammaRange registry value (if any)
+    pub fn from_gamma_range (reg_value: Option <u32>) -> RampLimits {
+        RampLimits { max_deviation: reg_value .unwrap_or (DEFAULT_GAMMA_RANGE) .min (UNLIMITED_GAMMA_RANGE) }
+    }
+
+    pub fn is_unlimited (&self) -> bool {
+        self.max_deviation >= UNLIMITED_GAMMA_RANGE
+    }
+
+    /// The allowed (lo, hi) 16-bit values for a given ramp entry
+    pub fn bounds (&self, i: usize) -> (u16, u16) {
+        if self.is_unlimited() { return (0, u16::MAX) }
+        let (identity, dev) = (i as i32 * 256,  self.max_deviation as i32 * 256);
+        ((identity - dev) .clamp (0, 65535) as u16,  (identity + dev) .clamp (0, 65535) as u16)
+    }
+
+    pub fn is_acceptable (&self, ramp: &GammaRamp) -> bool {
+        ramp .iter() .all (|ch| {
+            ch .iter() .enumerate() .all (|(i, &v)| { let (lo, hi) = self.bounds (i); v >= lo && v <= hi })
+                && ch .windows (2) .all (|w| w[0] <= w[1])
+        } )
+    }
+
+    /// Clamps the ramp into the allowed band (and keeps it non-decreasing) .. returns None if it was already acceptable
+    pub fn clamp (&self, ramp: &GammaRamp) -> Option <(GammaRamp, RampClamp)> {
+        if self.is_acceptable (ramp) { return None }
+        let mut out = *ramp;
+        let mut report = RampClamp { max_adjust: 0.0, n_adjusted: 0 };
+        for ch in out.iter_mut() {
+            let mut prev = 0u16;
+            for (i, cur) in ch.iter_mut().enumerate() {
+                let (lo, hi) = self.bounds (i);
+                let v = (*cur) .clamp (lo, hi) .max (prev);
+                // ^^ since both bounds rise w i, taking the running max never pushes us back out of the band
+                if v != *cur {
+                    report.n_adjusted += 1;
+                    report.max_adjust = report.max_adjust .max ((v as f32 - *cur as f32).abs() / 256.0);
+                }
+                *cur = v;
+                prev = v;
+            }
+        }
+        Some ((out, report))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity () -> GammaRamp {
+        [0, 1, 2] .map (|_| std::array::from_fn (|i| (i * 256) as u16))
+    }
+    fn scaled (ramp: &GammaRamp, mults: [f32; 3]) -> GammaRamp {
+        std::array::from_fn (|c| ramp[c] .map (|v| (v as f32 * mults[c]) as u16))
+    }
+
+    #[test]
+    fn test_limit_model () {
+        let limits = RampLimits::default();
+        assert! (limits.is_acceptable (&identity()));
+        // halving blue still fits (it deviates by at most ~128 levels at the top), but going much darker doesnt
+        assert! (limits.is_acceptable (&scaled (&identity(), [1.0, 1.0, 0.5])));
+        assert! (!limits.is_acceptable (&scaled (&identity(), [1.0, 1.0, 0.4])));
+        // non-monotonic ramps are refused regardless
+        let mut bumpy = identity();
+        bumpy[1][100] = bumpy[1][101] + 1;
+        assert! (!limits.is_acceptable (&bumpy));
+
+        // and the registry policy widens (or lifts) the band
+        assert! (RampLimits::from_gamma_range (Some (200)) .is_acceptable (&scaled (&identity(), [1.0, 1.0, 0.4])));
+        assert! (RampLimits::from_gamma_range (Some (256)) .is_acceptable (&scaled (&identity(), [0.0, 0.0, 0.0])));
+        assert! (RampLimits::from_gamma_range (Some (999)) .is_unlimited());
+    }
+
+    #[test]
+    fn test_clamping () {
+        let limits = RampLimits::default();
+        assert! (limits.clamp (&identity()) .is_none());
+
+        let dark = scaled (&identity(), [0.9, 0.6, 0.2]);
+        let (clamped, report) = limits.clamp (&dark) .unwrap();
+        assert! (limits.is_acceptable (&clamped));
+        // red was fine and stays untouched, and the rest only get raised to the edge of the band
+        assert_eq! (clamped[0], dark[0]);
+        assert_eq! (clamped[2][255], limits.bounds (255) .0);
+        assert! (clamped[2] .iter() .zip (dark[2].iter()) .all (|(c, d)| c >= d));
+        assert! (report.n_adjusted > 0 && report.max_adjust > 70.0 && report.max_adjust < 80.0, "{report:?}");
+    }
+
+}
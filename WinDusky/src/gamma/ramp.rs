@@ -0,0 +1,223 @@
+use crate::gamma::blackbody::color_temp_to_rgb;
+
+
+
+// Pure gamma-ramp generation .. from gamma/brightness/contrast (GBC) values and a color temperature (w tint).
+// Each of the GBC values can be set per channel (r,g,b), which is useful for correcting panel tints, or building
+// custom night modes beyond what color-temp alone can do. (Shared values just set the same for all three channels.)
+
+
+
+const GAMMA_RANGE    : (f32, f32) = (0.3, 4.4);
+const BRIGHT_RANGE   : (f32, f32) = (-1.0, 1.0);
+const CONTRAST_RANGE : (f32, f32) = (0.1, 100.0);
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct GBC {
+    gamma    : [f32; 3],   // default 1.0 .. clamped to 0.3 to 4.4
+    bright   : [f32; 3],   // default 0.0 .. clamped to -1.0 to 1.0
+    contrast : [f32; 3],   // default 1.0 .. clamped to 0.1 to 100.0
+    // ^^ each as per-channel r,g,b values
+}
+impl Default for GBC {
+    fn default() -> GBC {
+        GBC::new (1.0, 0.0, 1.0)
+    }
+}
+
+
+
+impl GBC {
+
+    pub fn new (gamma: f32, bright: f32, contrast: f32) -> GBC {
+        GBC { gamma: [gamma; 3], bright: [bright; 3], contrast: [contrast; 3] }
+    }
+
+    pub fn with_channels (gamma: [f32; 3], bright: [f32; 3], contrast: [f32; 3]) -> GBC {
+        GBC { gamma, bright, contrast }
+    }
+
+    /// The per-channel (gamma, brightness, contrast) values
+    pub fn channels (&self) -> ([f32; 3], [f32; 3], [f32; 3]) {
+        (self.gamma, self.bright, self.contrast)
+    }
+
+    /// Shifts each of the values on all channels by the given deltas (kept within their valid ranges)
+    pub fn shifted (&self, d_gamma: f32, d_bright: f32, d_contrast: f32) -> GBC {
+        let shift = |x: [f32; 3], d: f32, range: (f32, f32)| x .map (|v| (((v + d) * 1000.0).round() / 1000.0) .clamp (range.0, range.1));
+        // ^^ rounding keeps repeated nudges from accumulating float noise (which would otherwise end up in saved presets)
+        GBC {
+            gamma    : shift (self.gamma,    d_gamma,    GAMMA_RANGE),
+            bright   : shift (self.bright,   d_bright,   BRIGHT_RANGE),
+            contrast : shift (self.contrast, d_contrast, CONTRAST_RANGE),
+        }
+    }
+
+    /// Linear interpolation between two GBC values (at t=0 we get a, and at t=1 we get b)
+    pub fn lerp (a: &GBC, b: &GBC, t: f32) -> GBC {
+        let t = t.clamp (0.0, 1.0);
+        let mix = |x: [f32; 3], y: [f32; 3]| [0, 1, 2] .map (|c| x[c] + (y[c] - x[c]) * t);
+        GBC { gamma: mix (a.gamma, b.gamma), bright: mix (a.bright, b.bright), contrast: mix (a.contrast, b.contrast) }
+    }
+
+    /// Gamma ramp are arrays of 256 mapping values for each channel (r,g,b)
+    pub fn create_gamma_ramp (&self) -> [[u16; 256]; 3] {
+        [0, 1, 2] .map (|c| calc_channel_ramp (self.gamma[c], self.bright[c], self.contrast[c]))
+    }
+}
+
+
+fn calc_channel_ramp (gamma: f32, bright: f32, contrast: f32) -> [u16; 256] {
+    let gamma    = gamma    .clamp (GAMMA_RANGE.0,    GAMMA_RANGE.1);
+    let bright   = bright   .clamp (BRIGHT_RANGE.0,   BRIGHT_RANGE.1);
+    let contrast = contrast .clamp (CONTRAST_RANGE.0, CONTRAST_RANGE.1);
+
+    let inv_gamma = 1.0 / gamma as f64;
+    let norm = 255.0f64.powf(inv_gamma - 1.0);
+    let mut ramp = [0u16; 256];
+    for (i, out) in ramp.iter_mut().enumerate() {
+        let mut val = i as f64 * contrast as f64 - (contrast as f64 - 1.0) * 127.0;
+        if (gamma - 1.0).abs() > 1e-6 {
+            val = val.max(0.0).powf(inv_gamma) / norm;
+            // ^^ high contrast pushes the low end negative, which we floor for the pow (else NaN) .. otherwise the
+            // final clamp (after brightness) does the flooring
+        }
+        val += bright as f64 * 128.0;
+        let v = (val * 256.0).round() as i32;
+        *out = v.clamp(0, 65535) as u16;
+    }
+    ramp
+}
+
+
+
+/// Color temp (and tint) treated as neutral for ramps .. so presets at 6500K w no tint leave colors unchanged
+pub const NEUTRAL_TEMP : u32 = 6500;
+
+/// The ramps work on encoded (not linear) values, so light-level multipliers get converted with this (~sRGB) gamma
+const ENCODING_GAMMA : f32 = 2.2;
+
+
+/// The per-channel ramp multipliers for a color temp (w tint), relative to neutral (and scaled so none exceed 1)
+pub fn color_temp_multipliers (color_temp: u32, tint: f32) -> [f32; 3] {
+    let (target, neutral) = (color_temp_to_rgb (color_temp, tint), color_temp_to_rgb (NEUTRAL_TEMP, 0.0));
+    let rel = [0, 1, 2] .map (|c| target[c] / neutral[c].max (1e-6));
+    let max = rel .iter() .cloned() .fold (1e-6, f32::max);
+    rel .map (|m| (m / max) .powf (1.0 / ENCODING_GAMMA))
+}
+
+/// Blend/apply color temperature (w tint) to a gamma ramp
+pub fn apply_color_temp_to_ramp (ramp: &mut [[u16; 256]; 3], color_temp: u32, tint: f32) {
+    if color_temp == NEUTRAL_TEMP && tint == 0.0 { return }
+    let mults = color_temp_multipliers (color_temp, tint);
+    for (ch, mult) in ramp.iter_mut().zip (mults) {
+        for v in ch.iter_mut() {
+            *v = ( *v as f32 * mult ) .round() .clamp (0.0, 65535.0) as u16;
+        }
+    }
+}
+
+
+pub fn calc_gbct_ramp (gbc: &GBC, t: u32, tint: f32) -> [[u16; 256]; 3] {
+    let mut ramp = gbc.create_gamma_ramp();
+    apply_color_temp_to_ramp (&mut ramp, t, tint);
+    ramp
+}
+
+/// Interpolates between two ramps, entry by entry (at t=0 we get a, and at t=1 we get b) .. e.g. for fading between presets <br>
+/// (Note that this keeps ramps monotonic, and within the OS acceptance limits if both ends are, as those are convex)
+pub fn lerp_ramps (a: &[[u16; 256]; 3], b: &[[u16; 256]; 3], t: f32) -> [[u16; 256]; 3] {
+    let t = t.clamp (0.0, 1.0);
+    std::array::from_fn (|c| std::array::from_fn (|i| {
+        (a[c][i] as f32 + (b[c][i] as f32 - a[c][i] as f32) * t) .round() as u16
+    } ))
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_monotonic (ch: &[u16; 256]) -> bool {
+        ch.windows(2) .all (|w| w[0] <= w[1])
+    }
+
+    #[test]
+    fn test_default_ramp () {
+        // the default is (very nearly) the identity ramp, the same on all channels
+        let ramp = GBC::default().create_gamma_ramp();
+        assert_eq! (ramp[0], ramp[1]);
+        assert_eq! (ramp[1], ramp[2]);
+        assert! ((0..256) .all (|i| ramp[0][i] == (i * 256) as u16));
+        assert_eq! (calc_gbct_ramp (&GBC::default(), NEUTRAL_TEMP, 0.0), ramp);
+    }
+
+    #[test]
+    fn test_ramps_are_monotonic () {
+        for gamma in [0.1, 0.5, 1.0, 2.2, 10.0] {
+            for bright in [-2.0, -0.5, 0.0, 0.3, 2.0] {
+                for contrast in [0.0, 0.5, 1.0, 3.0, 500.0] {
+                    let ramp = calc_gbct_ramp (&GBC::new (gamma, bright, contrast), 4500, 0.01);
+                    assert! (ramp .iter() .all (is_monotonic), "non-monotonic ramp for {:?}", (gamma, bright, contrast));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_clamping () {
+        // out of range values behave like the clamp limits
+        assert_eq! (GBC::new (10.0, 5.0, 0.0).create_gamma_ramp(), GBC::new (4.4, 1.0, 0.1).create_gamma_ramp());
+        assert_eq! (GBC::new (0.0, -5.0, 1e6).create_gamma_ramp(), GBC::new (0.3, -1.0, 100.0).create_gamma_ramp());
+        // and the extremes saturate rather than wrap
+        assert! (GBC::new (1.0, 1.0, 1.0).create_gamma_ramp()[0][255] == 65535);
+        assert! (GBC::new (1.0, -1.0, 1.0).create_gamma_ramp()[0][0] == 0);
+        // w high contrast, the low end only floors after brightness is added
+        let ramp = GBC::new (1.0, 0.5, 3.0).create_gamma_ramp();
+        assert_eq! ([ramp[0][50], ramp[0][100]], [0, 110 * 256]);
+    }
+
+    #[test]
+    fn test_color_temp_multipliers () {
+        assert_eq! (color_temp_multipliers (NEUTRAL_TEMP, 0.0), [1.0, 1.0, 1.0]);
+        // warm temps dim green and blue (more so for blue), and cool temps dim red instead .. neither ever boosts
+        let warm = color_temp_multipliers (3000, 0.0);
+        assert! (warm[0] == 1.0 && warm[1] < 1.0 && warm[2] < warm[1], "{warm:?}");
+        let cool = color_temp_multipliers (9000, 0.0);
+        assert! (cool[2] == 1.0 && cool[0] < 1.0, "{cool:?}");
+        // and a green tint dims red and blue relative to green
+        let green = color_temp_multipliers (NEUTRAL_TEMP, 0.01);
+        assert! (green[1] == 1.0 && green[0] < 1.0 && green[2] < 1.0, "{green:?}");
+    }
+
+    #[test]
+    fn test_lerp_ramps () {
+        let (a, b) = (GBC::default().create_gamma_ramp(), calc_gbct_ramp (&GBC::new (1.1, -0.25, 0.9), 5000, 0.0));
+        assert_eq! (lerp_ramps (&a, &b, 0.0), a);
+        assert_eq! (lerp_ramps (&a, &b, 1.0), b);
+        assert_eq! (lerp_ramps (&a, &b, 7.0), b);
+        let mid = lerp_ramps (&a, &b, 0.5);
+        assert! (mid .iter() .all (is_monotonic));
+        assert! ((0..3) .all (|c| (0..256) .all (|i| mid[c][i] >= a[c][i].min (b[c][i]) && mid[c][i] <= a[c][i].max (b[c][i]))));
+    }
+
+    #[test]
+    fn test_per_channel () {
+        // per-channel values only affect their own channel
+        let gbc = GBC::with_channels ([1.0, 1.0, 1.4], [0.0, -0.1, 0.0], [1.0, 1.0, 1.0]);
+        let (ramp, base) = (gbc.create_gamma_ramp(), GBC::default().create_gamma_ramp());
+        assert_eq! (ramp[0], base[0]);
+        assert! ((1..255) .all (|i| ramp[1][i] < base[1][i]));
+        assert! ((1..255) .all (|i| ramp[2][i] > base[2][i]));
+
+        // and lerp works per channel too
+        let mid = GBC::lerp (&GBC::default(), &gbc, 0.5);
+        assert_eq! (mid, GBC::with_channels ([1.0, 1.0, 1.2], [0.0, -0.05, 0.0], [1.0, 1.0, 1.0]));
+    }
+
+}
@@ -0,0 +1,73 @@
+// Persisted copy of the original (pre-WinDusky) gamma ramps .. so that we can put back the user's actual calibration
+// (rather than a computed 'Normal' ramp) on exit, and even after a crash or kill on the next startup.
+// The file is written at startup and deleted on clean exit, so if it still exists at startup, the prior run died.
+// The format is just a line per device, with the device name and the 3x256 ramp values as hex.
+
+
+
+pub type GammaRamp = [[u16; 256]; 3];
+
+/// Key used for the whole-screen DC ramp (device names are always like '\\.\DISPLAY1', so this cant clash)
+pub const SCREEN_DC_KEY : &str = "*";
+
+const RAMP_SEP : &str = " | ";
+
+const FILE_HEADER : &str = "\
+# WinDusky original gamma ramps .. written at startup, and removed on clean exit
+# (if this is still here when WinDusky starts, the previous run didnt exit cleanly, and these get restored)
+";
+
+
+
+pub fn serialize_ramps (ramps: &[(String, GammaRamp)]) -> String {
+    let mut out = FILE_HEADER.to_string();
+    for (device, ramp) in ramps {
+        let vals : Vec<String> = ramp .iter() .flatten() .map (|v| format! ("{v:04x}")) .collect();
+        out += &format! ("{device}{RAMP_SEP}{}\n", vals.join (","));
+    }
+    out
+}
+
+
+pub fn parse_ramps (s: &str) -> Result <Vec <(String, GammaRamp)>, String> {
+    s .lines() .map (str::trim) .filter (|l| !l.is_empty() && !l.starts_with ('#')) .map (|line| {
+        let (device, vals) = line .split_once (RAMP_SEP) .ok_or_else (|| format! ("Malformed gamma ramp line : {line:.40}"))?;
+        let vals = vals .split (',') .map (|v| u16::from_str_radix (v.trim(), 16))
+            .collect::<Result <Vec<u16>, _>>() .map_err (|e| format! ("Bad gamma ramp value for {device:?} : {e}"))?;
+        if vals.len() != 3 * 256 {
+            return Err (format! ("Expected {} gamma ramp values for {device:?}, got {}", 3 * 256, vals.len()))
+        }
+        let mut ramp = [[0u16; 256]; 3];
+        for (c, chunk) in vals.chunks (256) .enumerate() { ramp[c].copy_from_slice (chunk); }
+        Ok ((device.to_string(), ramp))
+    } ) .collect()
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramps_round_trip () {
+        let mut ramp = [[0u16; 256]; 3];
+        for (c, ch) in ramp.iter_mut().enumerate() { for (i, v) in ch.iter_mut().enumerate() { *v = (i as u16 * 257) .saturating_sub (c as u16 * 300); } }
+        let ramps = vec! [ (r"\\.\DISPLAY1".to_string(), ramp), (SCREEN_DC_KEY.to_string(), [[0xffff; 256]; 3]) ];
+
+        let parsed = parse_ramps (&serialize_ramps (&ramps)) .unwrap();
+        assert_eq! (parsed, ramps);
+        assert! (parse_ramps (FILE_HEADER) .unwrap() .is_empty());
+    }
+
+    #[test]
+    fn test_malformed_ramps () {
+        assert! (parse_ramps ("DISPLAY1 0000,0101").is_err());
+        assert! (parse_ramps ("DISPLAY1 | 0000,0101").is_err());
+        let bad_val = format! ("DISPLAY1 | zz{}", ",0000".repeat (767));
+        assert! (parse_ramps (&bad_val).is_err());
+    }
+
+}
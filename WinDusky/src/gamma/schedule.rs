@@ -0,0 +1,204 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use time::{Date, OffsetDateTime};
+
+
+
+// Solar schedule for automating gamma presets through the day .. computed entirely offline from a configured location.
+// We use the standard sunrise equation (as in NOAA's simplified solar calcs), which is accurate to within a minute or
+// two at most latitudes .. plenty for fading gamma over dawn/dusk. Everything here works in utc, so we dont need to
+// know the local timezone at all (the local solar date is derived from the longitude itself).
+
+
+
+/// Sun altitude (in degrees) at which we consider it risen/set (accounts for refraction and the solar disc radius)
+const SUNRISE_ALTITUDE_DEG : f64 = -0.833;
+
+/// Julian date of the J2000 epoch, and of the unix epoch
+const JD_J2000 : f64 = 2451545.0;
+const JD_UNIX  : f64 = 2440587.5;
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+pub enum SunEvents {
+    Normal { sunrise: f64, sunset: f64 },
+    // ^^ as unix timestamps (secs)
+    PolarDay,
+    PolarNight,
+}
+
+/// The phases of the schedule .. manual overrides last until the phase changes
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SolarPhase { Night, Dawn, Day, Dusk }
+
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+pub struct SolarSchedule {
+    pub latitude  : f64,
+    pub longitude : f64,
+    // ^^ in degrees, w north and east as positive
+    pub dawn      : Duration,
+    pub dusk      : Duration,
+    // ^^ lengths of the morning and evening transitions (centered on sunrise and sunset)
+}
+
+
+
+/// Computes sunrise and sunset (utc) for the given date at the given location
+pub fn sun_events (date: Date, latitude: f64, longitude: f64) -> SunEvents {
+    let n = date.to_julian_day() as f64 - JD_J2000 + 0.0008;
+    let j_star = n - longitude / 360.0;
+    // ^^ mean solar time (in days since J2000) of local solar noon
+
+    let m = (357.5291 + 0.98560028 * j_star) .rem_euclid (360.0);
+    let c = 1.9148 * sin_d (m) + 0.0200 * sin_d (2.0 * m) + 0.0003 * sin_d (3.0 * m);
+    let lambda = (m + c + 180.0 + 102.9372) .rem_euclid (360.0);
+    let j_transit = JD_J2000 + j_star + 0.0053 * sin_d (m) - 0.0069 * sin_d (2.0 * lambda);
+    // ^^ mean anomaly, equation of center, ecliptic longitude, and the julian date of solar noon
+
+    let sin_decl = sin_d (lambda) * sin_d (23.4397);
+    let cos_decl = (1.0 - sin_decl * sin_decl) .sqrt();
+
+    let cos_w0 = (sin_d (SUNRISE_ALTITUDE_DEG) - sin_d (latitude) * sin_decl) / (cos_d (latitude) * cos_decl);
+    if cos_w0 >  1.0 { return SunEvents::PolarNight }
+    if cos_w0 < -1.0 { return SunEvents::PolarDay }
+
+    let w0 = cos_w0.acos() * 180.0 / PI;
+    let to_unix = |jd: f64| (jd - JD_UNIX) * 86400.0;
+    SunEvents::Normal { sunrise: to_unix (j_transit - w0 / 360.0),  sunset: to_unix (j_transit + w0 / 360.0) }
+}
+
+
+fn sin_d (deg: f64) -> f64 { (deg * PI / 180.0).sin() }
+fn cos_d (deg: f64) -> f64 { (deg * PI / 180.0).cos() }
+
+
+/// Blends color temps by interpolating in mired space (which is much closer to perceptually even than kelvin)
+pub fn mix_color_temp (day: u32, night: u32, day_frac: f32) -> u32 {
+    let (m_day, m_night) = (1e6 / day.max(1) as f32, 1e6 / night.max(1) as f32);
+    (1e6 / (m_night + (m_day - m_night) * day_frac.clamp (0.0, 1.0))) .round() as u32
+}
+
+
+
+impl SolarSchedule {
+
+    /// Sunrise and sunset for the local solar day containing the given time
+    pub fn sun_events_at (&self, now: OffsetDateTime) -> SunEvents {
+        let solar_date = (now + time::Duration::seconds_f64 (self.longitude * 240.0)) .date();
+        // ^^ 4 mins per degree of longitude .. so the date flips near local solar midnight, rather than at utc midnight
+        sun_events (solar_date, self.latitude, self.longitude)
+    }
+
+    /// How far into 'day' we are (0 at night, 1 during the day, and ramping linearly through dawn and dusk)
+    pub fn day_fraction (&self, now: OffsetDateTime) -> f32 {
+        let ts = now.unix_timestamp() as f64;
+        match self.sun_events_at (now) {
+            SunEvents::PolarDay   => 1.0,
+            SunEvents::PolarNight => 0.0,
+            SunEvents::Normal { sunrise, sunset } => {
+                let ramp = |center: f64, len: Duration| {
+                    if len.is_zero() { return if ts >= center { 1.0 } else { 0.0 } }
+                    ((ts - (center - len.as_secs_f64() / 2.0)) / len.as_secs_f64()) .clamp (0.0, 1.0)
+                };
+                ramp (sunrise, self.dawn) .min (1.0 - ramp (sunset, self.dusk)) as f32
+                // ^^ taking the min keeps things sane even if the transitions are configured longer than the day
+            }
+        }
+    }
+
+    pub fn phase (&self, now: OffsetDateTime) -> SolarPhase {
+        let ts = now.unix_timestamp() as f64;
+        match self.sun_events_at (now) {
+            SunEvents::PolarDay   => SolarPhase::Day,
+            SunEvents::PolarNight => SolarPhase::Night,
+            SunEvents::Normal { sunrise, sunset } => {
+                let (half_dawn, half_dusk) = (self.dawn.as_secs_f64() / 2.0, self.dusk.as_secs_f64() / 2.0);
+                if      ts <  sunrise - half_dawn { SolarPhase::Night }
+                else if ts <  sunrise + half_dawn { SolarPhase::Dawn }
+                else if ts <  sunset  - half_dusk { SolarPhase::Day }
+                else if ts <  sunset  + half_dusk { SolarPhase::Dusk }
+                else                              { SolarPhase::Night }
+            }
+        }
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Month, PrimitiveDateTime, Time};
+
+    fn date (y: i32, m: Month, d: u8) -> Date {
+        Date::from_calendar_date (y, m, d) .unwrap()
+    }
+    fn utc (d: Date, h: u8, min: u8) -> OffsetDateTime {
+        PrimitiveDateTime::new (d, Time::from_hms (h, min, 0) .unwrap()) .assume_utc()
+    }
+    fn assert_near (ts: f64, expected: OffsetDateTime, tol_mins: f64) {
+        let diff_mins = (ts - expected.unix_timestamp() as f64) / 60.0;
+        assert! (diff_mins.abs() <= tol_mins, "off by {diff_mins:.1} mins from {expected}");
+    }
+
+    #[test]
+    fn test_sun_events () {
+        // london at the summer solstice : sunrise ~03:43 utc, sunset ~20:21 utc
+        let d = date (2024, Month::June, 21);
+        let SunEvents::Normal { sunrise, sunset } = sun_events (d, 51.5074, -0.1278) else { panic!() };
+        assert_near (sunrise, utc (d, 3, 43), 3.0);
+        assert_near (sunset,  utc (d, 20, 21), 3.0);
+
+        // new york at the winter solstice : sunrise ~12:16 utc, sunset ~21:32 utc
+        let d = date (2024, Month::December, 21);
+        let SunEvents::Normal { sunrise, sunset } = sun_events (d, 40.7128, -74.0060) else { panic!() };
+        assert_near (sunrise, utc (d, 12, 16), 3.0);
+        assert_near (sunset,  utc (d, 21, 32), 3.0);
+
+        // sydney (southern hemisphere, east of utc) in january : sunrise ~18:59 utc (prev day), sunset ~09:09 utc
+        let d = date (2024, Month::January, 15);
+        let SunEvents::Normal { sunrise, sunset } = sun_events (d, -33.8688, 151.2093) else { panic!() };
+        assert_near (sunrise, utc (date (2024, Month::January, 14), 18, 59), 5.0);
+        assert_near (sunset,  utc (d, 9, 9), 5.0);
+
+        // and tromso gets the midnight sun, and the polar night
+        assert_eq! (sun_events (date (2024, Month::June, 21), 69.6492, 18.9553), SunEvents::PolarDay);
+        assert_eq! (sun_events (date (2024, Month::December, 21), 69.6492, 18.9553), SunEvents::PolarNight);
+    }
+
+    #[test]
+    fn test_day_fraction_and_phases () {
+        let sched = SolarSchedule {
+            latitude: 51.5074, longitude: -0.1278, dawn: Duration::from_secs (3600), dusk: Duration::from_secs (2 * 3600)
+        };
+        let d = date (2024, Month::June, 21);
+        let SunEvents::Normal { sunrise, sunset } = sched.sun_events_at (utc (d, 12, 0)) else { panic!() };
+        let at = |ts: f64| OffsetDateTime::from_unix_timestamp (ts as i64) .unwrap();
+
+        assert_eq! (sched.day_fraction (utc (d, 1, 0)), 0.0);
+        assert_eq! (sched.phase (utc (d, 1, 0)), SolarPhase::Night);
+        assert! ((sched.day_fraction (at (sunrise)) - 0.5).abs() < 0.01);
+        assert_eq! (sched.phase (at (sunrise)), SolarPhase::Dawn);
+        assert_eq! (sched.day_fraction (utc (d, 12, 0)), 1.0);
+        assert_eq! (sched.phase (utc (d, 12, 0)), SolarPhase::Day);
+        assert! ((sched.day_fraction (at (sunset + 1800.0)) - 0.25).abs() < 0.01);
+        assert_eq! (sched.phase (at (sunset + 1800.0)), SolarPhase::Dusk);
+        assert_eq! (sched.day_fraction (utc (d, 23, 30)), 0.0);
+        assert_eq! (sched.phase (utc (d, 23, 30)), SolarPhase::Night);
+    }
+
+    #[test]
+    fn test_mix_color_temp () {
+        assert_eq! (mix_color_temp (6500, 3400, 1.0), 6500);
+        assert_eq! (mix_color_temp (6500, 3400, 0.0), 3400);
+        // the mired midpoint sits well below the kelvin midpoint (which is where the eye perceives 'halfway')
+        let mid = mix_color_temp (6500, 3400, 0.5);
+        assert! (mid > 3400 && mid < 4950, "{mid}");
+    }
+
+}
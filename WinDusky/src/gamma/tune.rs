@@ -0,0 +1,82 @@
+use crate::gamma::blackbody::TEMP_RANGE;
+use crate::gamma::ramp::GBC;
+
+
+
+// Live tuning of gamma presets .. nudging one parameter at a time in small steps (via hotkeys), so presets can be
+// dialed in by eye, and then saved back to config. Nudges shift all channels together, so per-channel offsets persist.
+
+
+
+#[derive (Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TuneParam {
+    Brightness,
+    Contrast,
+    Gamma,
+    ColorTemp,
+}
+
+const BRIGHTNESS_STEP : f32 = 0.02;
+const CONTRAST_STEP   : f32 = 0.02;
+const GAMMA_STEP      : f32 = 0.05;
+const TEMP_STEP       : u32 = 100;
+
+/// Suffix for presets saved from tuning (so we dont overwrite the preset the tuning started from)
+pub const TUNED_SUFFIX : &str = " (tuned)";
+
+
+
+/// Nudges the given parameter a step up or down, returning the updated (gbc, color_temp)
+pub fn nudge (gbc: &GBC, color_temp: u32, param: TuneParam, up: bool) -> (GBC, u32) {
+    let sign = if up { 1.0 } else { -1.0 };
+    match param {
+        TuneParam::Brightness => (gbc.shifted (0.0, sign * BRIGHTNESS_STEP, 0.0), color_temp),
+        TuneParam::Contrast   => (gbc.shifted (0.0, 0.0, sign * CONTRAST_STEP), color_temp),
+        TuneParam::Gamma      => (gbc.shifted (sign * GAMMA_STEP, 0.0, 0.0), color_temp),
+        TuneParam::ColorTemp  => {
+            let t = if up { color_temp + TEMP_STEP } else { color_temp .saturating_sub (TEMP_STEP) };
+            (*gbc, t .clamp (TEMP_RANGE.0, TEMP_RANGE.1))
+        }
+    }
+}
+
+/// The name to save a tuned preset under .. (re-tuning an already tuned preset saves over it, rather than stacking suffixes)
+pub fn tuned_preset_name (base: &str) -> String {
+    format! ("{}{TUNED_SUFFIX}", base .strip_suffix (TUNED_SUFFIX) .unwrap_or (base))
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nudges () {
+        let base = GBC::with_channels ([1.0, 1.0, 1.2], [0.0; 3], [1.0; 3]);
+        let (gbc, t) = nudge (&base, 6500, TuneParam::Gamma, true);
+        assert_eq! ((gbc, t), (GBC::with_channels ([1.05, 1.05, 1.25], [0.0; 3], [1.0; 3]), 6500));
+        // ^^ per-channel differences are kept
+
+        let (gbc, _) = nudge (&base, 6500, TuneParam::Brightness, false);
+        assert_eq! (gbc, GBC::with_channels ([1.0, 1.0, 1.2], [-0.02; 3], [1.0; 3]));
+        let (gbc, _) = nudge (&base, 6500, TuneParam::Contrast, true);
+        assert_eq! (gbc, GBC::with_channels ([1.0, 1.0, 1.2], [0.0; 3], [1.02; 3]));
+
+        // and the values stay within their valid ranges
+        assert_eq! (nudge (&base, 6500, TuneParam::ColorTemp, false), (base, 6400));
+        assert_eq! (nudge (&base, TEMP_RANGE.0, TuneParam::ColorTemp, false) .1, TEMP_RANGE.0);
+        assert_eq! (nudge (&base, TEMP_RANGE.1, TuneParam::ColorTemp, true) .1, TEMP_RANGE.1);
+        let dim = GBC::new (1.0, -1.0, 1.0);
+        assert_eq! (nudge (&dim, 6500, TuneParam::Brightness, false) .0, dim);
+    }
+
+    #[test]
+    fn test_tuned_names () {
+        assert_eq! (tuned_preset_name ("Dark"), "Dark (tuned)");
+        assert_eq! (tuned_preset_name ("Dark (tuned)"), "Dark (tuned)");
+    }
+
+}
@@ -581,6 +581,7 @@ impl VKey {
             "TAB"     => VKey::Tab,
             "CLEAR"   => VKey::Clear,
             "RETURN"  => VKey::Return,
+            "ENTER"   => VKey::Return,
 
             "SHIFT"    => VKey::Shift,
             "CTRL"     => VKey::Ctrl,
@@ -595,6 +596,8 @@ impl VKey {
             "SPACE"    => VKey::Space,
             "PAGEUP"   => VKey::PgUp,
             "PAGEDOWN" => VKey::PgDown,
+            "PGUP"     => VKey::PgUp,
+            "PGDOWN"   => VKey::PgDown,
             "END"      => VKey::End,
             "HOME"     => VKey::Home,
             "LEFT"     => VKey::Left,
@@ -999,6 +1002,8 @@ mod tests {
         assert_eq!(VKey::from_keyname("Back").unwrap(), VKey::Back);
         assert_eq!(VKey::from_keyname("VK_BACK").unwrap(), VKey::Back);
         assert_eq!(VKey::from_keyname("RETURN").unwrap(), VKey::Return);
+        assert_eq!(VKey::from_keyname("Enter").unwrap(), VKey::Return);
+        assert_eq!(VKey::from_keyname("PgUp").unwrap(), VKey::PgUp);
         assert_eq!(
             VKey::from_keyname("0x29").unwrap(),
             VKey::CustomKeyCode(0x29)
@@ -11,13 +11,16 @@ use std::time::Duration;
 
 
 mod types;
+mod cli;
+#[cfg_attr (test, allow (clippy::bool_assert_comparison))]
 mod keys;
-mod dusky;    // <- sub-mods: hooks, hotkeys, overlay_effect, overlay_fs_effect, overlay_mag
+// ^^ (its tests predate our clippy gates, and are kept as is)
+mod dusky;    // <- sub-mods: gamma_fade, gamma_watch, hooks, hotkeys, overlay_effect, overlay_fs_effect, overlay_mag
 mod config;
-mod effects;
+mod effects;  // <- sub-mods: compose, cvd, fit, lint, matrix, preview, transition
 mod presets;
-mod gamma;
-mod auto;
+mod gamma;    // <- sub-mods: blackbody, calfile, limits, ramp, restore, schedule, tune
+mod auto;     // <- sub-mods: cache, explain, remembered, resample, rules, titles
 mod luminance;
 mod occlusion;
 mod tray;
@@ -27,6 +30,11 @@ mod win_utils;
 
 fn main() {
 
+    // if we were launched with a cli command (e.g. to check configs), we'll just handle that and exit
+    if let Some (code) = cli::run_cli_if_requested() {
+        std::process::exit (code);
+    }
+
     let conf = config::Config::instance();
 
     // first we want to load/init the config, then get the non-blocking log-appender guard here in main
@@ -4,10 +4,12 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{LazyLock, OnceLock};
 
 use itertools::Itertools;
-use tracing::info;
+use time::OffsetDateTime;
+use tracing::{info, warn};
 
 use crate::config::{self, GammaPresetSpec};
-use crate::gamma;
+use crate::gamma::{self, GBC};
+use crate::gamma::schedule::{self, SolarSchedule};
 
 
 
@@ -16,7 +18,8 @@ pub static PRESET_NORMAL : LazyLock <GammaPresetSpec> = LazyLock::new ( ||
     GammaPresetSpec {
         preset: "Normal".into(),
         gbc: gamma::GBC::default(),
-        color_temp: 6500,
+        color_temp: gamma::NEUTRAL_TEMP,
+        tint: 0.0,
     }
 );
 
@@ -29,6 +32,7 @@ pub struct GammaPresets {
     // ^^ can add back if we need by-name lookup .. for now, we'll just put the data into cycle-order vec itself
     pub cycle_order : Vec <(String, GammaPresetSpec)>,
     pub default     : GammaPreset,
+    pub schedule    : Option <GammaSchedule>,
 }
 
 
@@ -73,7 +77,22 @@ impl GammaPresets {
         let default = GammaPreset (default_idx);
         info! ("loaded default gamma-preset as : {:?}", (default, &default_preset));
 
-        GAMMA_PRESETS .get_or_init ( || GammaPresets { cycle_order, default })
+        let schedule = conf .get_gamma_schedule() .and_then (|spec| {
+            let find = |name: &str| {
+                let idx = cycle_order .iter() .position (|(s,_)| s == name) .map (GammaPreset);
+                if idx.is_none() { warn! ("Gamma schedule preset {:?} is not in the gamma presets cycle-order .. disabling schedule", name) }
+                idx
+            };
+            let (day, night) = (find (&spec.day_preset)?, find (&spec.night_preset)?);
+            info! ("loaded gamma schedule : {:?}", spec);
+            Some ( GammaSchedule { schedule: spec.schedule, day, night } )
+        } );
+
+        GAMMA_PRESETS .get_or_init ( || GammaPresets { cycle_order, default, schedule })
+    }
+
+    pub fn find_by_name (&self, name: &str) -> Option <GammaPreset> {
+        self.cycle_order .iter() .position (|(s,_)| s == name) .map (GammaPreset)
     }
 
 }
@@ -99,6 +118,11 @@ impl GammaPreset {
         let cycler = &GammaPresets::instance().cycle_order;
         cycler .get (self.0 % cycler.len()) .map (|(name, _)| name.as_str()) .unwrap_or ("")
     }
+    pub fn cycled (&self, forward: bool) -> GammaPreset {
+        let cyc_len = GammaPresets::instance().cycle_order.len();
+        let incr = if forward { cyc_len + 1 } else { cyc_len - 1 };
+        GammaPreset ((self.0 + incr) % cyc_len)
+    }
 }
 
 impl From <&GammaPresetAtomic> for GammaPreset {
@@ -128,3 +152,69 @@ impl GammaPresetAtomic {
     }
 
 }
+
+
+
+
+
+/// Name reported (e.g. to the tray) for the blended preset applied by the solar schedule
+pub const SCHEDULED_PRESET_NAME : &str = "Solar Schedule";
+
+/// Name reported for the live-tuned (and not yet saved) preset
+pub const TUNED_PRESET_NAME : &str = "Tuned (unsaved)";
+
+/// Blends between a day and a night gamma preset based on where the sun is (see gamma::schedule)
+#[derive (Debug, Clone)]
+pub struct GammaSchedule {
+    pub schedule : SolarSchedule,
+    pub day      : GammaPreset,
+    pub night    : GammaPreset,
+}
+
+impl GammaSchedule {
+    pub fn spec_at (&self, now: OffsetDateTime) -> GammaPresetSpec {
+        let frac = self.schedule.day_fraction (now);
+        let (day, night) = (self.day.get(), self.night.get());
+        GammaPresetSpec {
+            preset     : SCHEDULED_PRESET_NAME.into(),
+            gbc        : GBC::lerp (&night.gbc, &day.gbc, frac),
+            color_temp : schedule::mix_color_temp (day.color_temp, night.color_temp, frac),
+            tint       : night.tint + (day.tint - night.tint) * frac,
+        }
+    }
+
+    /// The preset the schedule is mostly showing at a given time .. the day preset from mid-dawn to mid-dusk, else the night one
+    pub fn nearest_preset_at (&self, now: OffsetDateTime) -> GammaPreset {
+        if self.schedule.day_fraction (now) >= 0.5 { self.day } else { self.night }
+    }
+}
+
+
+
+
+
+/// Per-monitor gamma state .. where unset, the monitor simply follows the global gamma toggle and preset
+#[derive (Debug, Clone)]
+pub struct MonitorGamma {
+    pub device : String,
+    pub index  : usize,
+    // ^^ 1-based, in OS enumeration order (which is what we show in the tray, and what conf assignments can refer to)
+
+    pub active : Option <bool>,
+    pub preset : Option <GammaPreset>,
+
+    pub tuned : Option <GammaPresetSpec>,
+    // ^^ live-tuned values for the monitor's own preset (if nudged, and not yet saved)
+}
+
+impl MonitorGamma {
+    pub fn effective_active (&self, global_active: bool) -> bool {
+        self.active .unwrap_or (global_active)
+    }
+    pub fn effective_preset (&self, global_preset: GammaPreset) -> GammaPreset {
+        self.preset .unwrap_or (global_preset)
+    }
+    pub fn label (&self) -> String {
+        format! ("Monitor {} ({})", self.index, self.device .trim_start_matches (['\\', '.']))
+    }
+}
@@ -4,25 +4,26 @@
 use image::{ImageFormat, ImageReader};
 
 use std::io::Cursor;
-use std::os::windows::process::CommandExt;
-use std::process::Command;
 use std::sync::OnceLock;
 use std::thread;
-use std::time::Duration;
 
 use tao::event::Event;
 use tao::event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy};
 use tao::platform::windows::EventLoopBuilderExtWindows;
 
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
-use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
 use tray_icon::{Icon, TrayIconBuilder};
 
-use windows::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, CREATE_NO_WINDOW, DETACHED_PROCESS};
+use windows::core::PCWSTR;
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONWARNING, MB_OK};
 
+use crate::auto::cache::CacheStats;
 use crate::dusky::{self, WinDusky, MagEffect, MAG_EFFECT_IDENTITY};
 use crate::effects::{ColorEffect};
+use crate::gamma;
+use crate::win_utils::wide_string;
 
 
 
@@ -47,9 +48,24 @@ pub enum DuskyEvent {
     AutoOverlayEnable (bool),
     OverlayUpdate { n_active : usize },
     OverridesUpdate { n_overrides : usize },
+    RememberedOverrides { entries : Vec <(String, String)> },
+    EvalCache { n_entries: usize, capacity: usize, stats: CacheStats },
     FullScreenMode { enabled: bool, effect: Option <ColorEffect>},
     MagLevel { level: Option <MagEffect>},
-    GammaState { applied: bool, succeeded: bool, preset: Option <&'static str>},
+    GammaState { applied: bool, succeeded: bool, clamped: bool, fallback: bool, preset: Option <&'static str>},
+    MonitorGamma { monitors: Vec <MonitorGammaState> },
+    GammaWatchdog { n_reapplied: usize },
+    EffectsCheck { n_problems: usize },
+}
+
+#[derive (Debug)]
+pub struct MonitorGammaState {
+    pub device    : String,
+    pub label     : String,
+    pub preset    : Option <&'static str>,
+    pub succeeded : bool,
+    pub clamped   : bool,
+    pub fallback  : bool,
 }
 
 
@@ -82,9 +98,35 @@ pub fn update_tray__overrides_count (n_overrides: usize) {
         let _ = proxy.send_event ( DuskyEvent::OverridesUpdate { n_overrides } );
     }
 }
-pub fn update_tray__gamma_state (applied:bool, succeeded:bool, preset: Option <&'static str>) {
+pub fn update_tray__remembered_overrides (entries: Vec <(String, String)>) {
+    // ^^ (id, label) pairs for each remembered per-app override
+    if let Some (proxy) = tray_events_proxy.get() {
+        let _ = proxy.send_event ( DuskyEvent::RememberedOverrides { entries } );
+    }
+}
+pub fn update_tray__eval_cache (n_entries: usize, capacity: usize, stats: CacheStats) {
+    if let Some (proxy) = tray_events_proxy.get() {
+        let _ = proxy.send_event ( DuskyEvent::EvalCache { n_entries, capacity, stats } );
+    }
+}
+pub fn update_tray__gamma_state (applied:bool, succeeded:bool, clamped:bool, fallback:bool, preset: Option <&'static str>) {
     if let Some(proxy) = tray_events_proxy.get() {
-        let _ = proxy.send_event ( DuskyEvent::GammaState { applied, succeeded, preset } );
+        let _ = proxy.send_event ( DuskyEvent::GammaState { applied, succeeded, clamped, fallback, preset } );
+    }
+}
+pub fn update_tray__monitor_gamma (monitors: Vec <MonitorGammaState>) {
+    if let Some(proxy) = tray_events_proxy.get() {
+        let _ = proxy.send_event ( DuskyEvent::MonitorGamma { monitors } );
+    }
+}
+pub fn update_tray__gamma_watchdog (n_reapplied: usize) {
+    if let Some(proxy) = tray_events_proxy.get() {
+        let _ = proxy.send_event ( DuskyEvent::GammaWatchdog { n_reapplied } );
+    }
+}
+pub fn update_tray__effects_check (n_problems: usize) {
+    if let Some(proxy) = tray_events_proxy.get() {
+        let _ = proxy.send_event ( DuskyEvent::EffectsCheck { n_problems } );
     }
 }
 
@@ -94,10 +136,16 @@ const MENU_ELEVATED         : &str = "is_elevated";
 const MENU_AUTO_OV_ENABLED  : &str = "auto_overlay_enabled";
 const MENU_ACTIVE_OVERLAYS  : &str = "active_overlays";
 const MENU_USER_OVERRIDES   : &str = "user_overrides";
+const MENU_REMEMBERED       : &str = "remembered_overrides";
+const MENU_EVAL_CACHE       : &str = "eval_cache";
 const MENU_FULL_SCREEN_MODE : &str = "full_screen_mode";
 const MENU_FULL_SCREEN_EFF  : &str = "full_screen_effect";
 const MENU_MAG_LEVEL        : &str = "mag_level";
 const MENU_GAMMA_PRESET     : &str = "gamma_preset";
+const MENU_MONITOR_GAMMA    : &str = "monitor_gamma";
+const MENU_GAMMA_WATCHDOG   : &str = "gamma_watchdog";
+const MENU_GAMMA_LIMITS     : &str = "gamma_limits";
+const MENU_EFFECTS_CHECK    : &str = "effects_check";
 const MENU_EDIT_CONF        : &str = "edit_conf";
 const MENU_RESET_CONF       : &str = "reset_conf";
 const MENU_RESTART          : &str = "restart";
@@ -110,10 +158,15 @@ fn menu_disp_str (id:&str) -> &str {
         MENU_AUTO_OV_ENABLED  => "Auto Overlay Enabled",
         MENU_ACTIVE_OVERLAYS  => "Overlays : 0",
         MENU_USER_OVERRIDES   => "User Overrides : 0",
+        MENU_REMEMBERED       => "Remembered Overrides : 0",
+        MENU_EVAL_CACHE       => "Eval Cache : Empty",
         MENU_FULL_SCREEN_MODE => "Enable Full Screen Effect",
         MENU_FULL_SCREEN_EFF  => "(Effect: None)",
         MENU_MAG_LEVEL        => "Magnification Level : None",
         MENU_GAMMA_PRESET     => "Gamma Preset: None",
+        MENU_MONITOR_GAMMA    => "Monitor Gamma",
+        MENU_GAMMA_WATCHDOG   => "Gamma Watchdog : No Drift",
+        MENU_EFFECTS_CHECK    => "Effects Check : OK",
         MENU_EDIT_CONF        => "Edit Config",
         MENU_RESET_CONF       => "Reset Config",
         MENU_RESTART          => "Restart",
@@ -122,6 +175,20 @@ fn menu_disp_str (id:&str) -> &str {
     }
 }
 
+/// Gamma range line .. explains (w the registry remedy) when Windows' ramp acceptance limits got in the way
+fn gamma_limits_disp_str (succeeded: bool, clamped: bool) -> String {
+    let limits = gamma::get_ramp_limits();
+    if limits.is_unlimited() { return "Gamma Range : Unlimited (GdiIcmGammaRange)".to_string() }
+    let range = limits.max_deviation;
+    if !succeeded {
+        format! ("❌ Gamma Range : ±{range} .. ramp refused? Set GdiIcmGammaRange=256 (see log)")
+    } else if clamped {
+        format! ("⚠ Gamma Range : ±{range} .. preset clamped, set GdiIcmGammaRange=256 (see log)")
+    } else {
+        format! ("Gamma Range : ±{range} levels from identity")
+    }
+}
+
 fn exec_menu_action (id: &str) {
 
     // Reminder that since tray runs in a separate thread, direct actions to dusky host/mag hwnds cant be triggered from here!!
@@ -138,11 +205,21 @@ fn exec_menu_action (id: &str) {
         MENU_FULL_SCREEN_EFF  => { wd.post_req__toggle_fs_eff(); }
         MENU_MAG_LEVEL        => { wd.post_req__toggle_mag_level(); }
         MENU_GAMMA_PRESET     => { wd.toggle_gamma_active(); }
+        MENU_EFFECTS_CHECK    => { show_effects_check_report (wd); }
         MENU_EDIT_CONF        => { wd.conf.trigger_config_file_edit(); }
         MENU_RESET_CONF       => { wd.conf.trigger_config_file_reset(); }
         MENU_RESTART          => { handle_restart_request(wd); }
         MENU_QUIT             => { wd.post_req__quit(); }
-        _ => { }
+        _ => {
+            // per-monitor gamma entries are dynamic, and have ids like "monitor_gamma:\\.\DISPLAY1"
+            if let Some (device) = id .strip_prefix (MENU_MONITOR_GAMMA) .and_then (|s| s.strip_prefix (':')) {
+                wd.toggle_monitor_gamma (Some (device.to_string()));
+            }
+            // .. and so are remembered overrides, which get forgotten when clicked
+            else if let Some (app_id) = id .strip_prefix (MENU_REMEMBERED) .and_then (|s| s.strip_prefix (':')) {
+                wd.auto.forget_remembered_override (app_id);
+            }
+        }
     };
 
 }
@@ -164,6 +241,8 @@ pub fn start_system_tray_monitor() {
 
     let active    = make_menu_check (MENU_ACTIVE_OVERLAYS, true, false);
     let overrides = make_menu_check (MENU_USER_OVERRIDES, true, false);
+    let remembered = Submenu::with_id (MENU_REMEMBERED, menu_disp_str (MENU_REMEMBERED), false);
+    let eval_cache = make_menu_item (MENU_EVAL_CACHE, false);
 
     let full_screen_mode = make_menu_check (MENU_FULL_SCREEN_MODE, true, false);
     let full_screen_eff  = make_menu_check (MENU_FULL_SCREEN_EFF, false, false);
@@ -171,6 +250,11 @@ pub fn start_system_tray_monitor() {
     let mag_level = make_menu_check (MENU_MAG_LEVEL, true, false);
 
     let gamma_preset  = make_menu_check (MENU_GAMMA_PRESET, true, false);
+    let monitor_gamma = Submenu::with_id (MENU_MONITOR_GAMMA, menu_disp_str (MENU_MONITOR_GAMMA), false);
+    let gamma_watchdog = make_menu_item (MENU_GAMMA_WATCHDOG, false);
+    let gamma_limits   = MenuItem::with_id (MENU_GAMMA_LIMITS, gamma_limits_disp_str (true, false), false, None);
+
+    let effects_check = make_menu_item (MENU_EFFECTS_CHECK, false);
 
     let edit_conf  = make_menu_item (MENU_EDIT_CONF, true);
     let reset_conf = make_menu_item (MENU_RESET_CONF, true);
@@ -183,11 +267,11 @@ pub fn start_system_tray_monitor() {
     let tray_menu = Menu::new();
     tray_menu .append_items ( &[
         &elevated, &sep,
-        &auto_ov_enabled, &active, &overrides, &sep,
+        &auto_ov_enabled, &active, &overrides, &remembered, &eval_cache, &sep,
         &full_screen_mode, &full_screen_eff, &sep,
         &mag_level, &sep,
-        &gamma_preset, &sep,
-        &edit_conf ,&reset_conf, &sep,
+        &gamma_preset, &monitor_gamma, &gamma_watchdog, &gamma_limits, &sep,
+        &effects_check, &edit_conf ,&reset_conf, &sep,
         &restart, &quit
     ] );
 
@@ -235,6 +319,19 @@ pub fn start_system_tray_monitor() {
             DuskyEvent::OverridesUpdate { n_overrides } => {
                 update_overrides_counts (n_overrides, &overrides);
             }
+            DuskyEvent::RememberedOverrides { entries } => {
+                while remembered.remove_at (0) .is_some() { }
+                for (app_id, label) in entries.iter() {
+                    let item = MenuItem::with_id (format! ("{MENU_REMEMBERED}:{app_id}"), format! ("Forget : {label:.80}"), true, None);
+                    let _ = remembered.append (&item);
+                }
+                remembered.set_text (format! ("Remembered Overrides : {}", entries.len()));
+                remembered.set_enabled (!entries.is_empty());
+            }
+            DuskyEvent::EvalCache { n_entries, capacity, stats } => {
+                // (this is just informational .. the hit/miss counts are as of the last time an entry was added or dropped)
+                eval_cache.set_text (format! ("Eval Cache : {n_entries}/{capacity} .. {stats}"));
+            }
             DuskyEvent::AutoOverlayEnable (enabled) => {
                 auto_ov_enabled.set_checked (enabled);
                 auto_ov_enabled.set_text (if enabled {"Auto Overlay Enabled"} else {"Enable Auto Overlay"})
@@ -243,7 +340,7 @@ pub fn start_system_tray_monitor() {
                 full_screen_mode.set_checked (enabled);
                 full_screen_eff .set_enabled (enabled);
                 full_screen_eff .set_checked (enabled && effect.is_some());
-                full_screen_eff .set_text (format! ("(Effect: {:.50})", effect .map (|e| e.name()) .unwrap_or("None")));
+                full_screen_eff .set_text (format! ("(Effect: {:.50})", effect .map (|e| e.label()) .unwrap_or("None".into())));
                 full_screen_mode.set_text (if enabled {"Full Screen Effect Enabled"} else {"Enable Full Screen Effect"});
                 for menu in [&auto_ov_enabled, &active, &overrides] {
                     menu.set_enabled (!enabled)
@@ -254,10 +351,34 @@ pub fn start_system_tray_monitor() {
                 mag_level.set_checked (level.0 > 0);
                 mag_level.set_text (format! ("Magnification Level : {:?}  ({:.2}x)", level.0, level.get()));
             }
-            DuskyEvent::GammaState {applied, succeeded, preset} => {
+            DuskyEvent::GammaState {applied, succeeded, clamped, fallback, preset} => {
                 gamma_preset.set_checked (applied);
-                let prefix = if succeeded {""} else {"❌ <- "};
+                let prefix = if fallback {"≈ <- "} else if !succeeded {"❌ <- "} else if clamped {"⚠ <- "} else {""};
                 gamma_preset .set_text (format! ("{prefix}Gamma Preset: {:.50}", preset.unwrap_or("None")));
+                gamma_limits .set_text ( if fallback { "≈ Gamma Range : no hardware ramps .. using color-matrix fallback".to_string() }
+                    else { gamma_limits_disp_str (succeeded, clamped) } );
+            }
+            DuskyEvent::MonitorGamma { monitors } => {
+                // the monitor set can change (hot-plugging etc), so we just rebuild the entries every time
+                while monitor_gamma.remove_at (0) .is_some() { }
+                for mon in monitors.iter() {
+                    let prefix = if mon.fallback {"≈ <- "} else if !mon.succeeded {"❌ <- "} else if mon.clamped {"⚠ <- "} else {""};
+                    let text = format! ("{prefix}{} : {:.50}", mon.label, mon.preset.unwrap_or("None"));
+                    let id = format! ("{MENU_MONITOR_GAMMA}:{}", mon.device);
+                    let item = CheckMenuItem::with_id (id, text, true, mon.preset.is_some(), None);
+                    let _ = monitor_gamma.append (&item);
+                }
+                monitor_gamma.set_enabled (!monitors.is_empty());
+            }
+            DuskyEvent::GammaWatchdog { n_reapplied } => {
+                // (this is just informational, the details of each re-application are in the log)
+                gamma_watchdog.set_text (format! ("Gamma Watchdog : Re-applied {n_reapplied}x"));
+            }
+            DuskyEvent::EffectsCheck { n_problems } => {
+                // we'll enable this (to open the conf for editing) only if there are problems to look at (details are in the log)
+                effects_check.set_enabled (n_problems > 0);
+                effects_check.set_text ( if n_problems == 0 { "Effects Check : OK".to_string() }
+                    else { format! ("⚠ Effects Check : {n_problems} problem(s) .. see log") } );
             }
         }
     };
@@ -281,27 +402,26 @@ pub fn start_system_tray_monitor() {
 
 
 
-fn handle_restart_request (wd: &'static WinDusky) {
-
-    thread::spawn (|| {
-
-        warn! ("Attempting to Restart WinDusky !!");
-
-        wd.post_req__un_register_hotkeys();
-        thread::sleep (Duration::from_millis(100));
-
-        let mut cmd = Command::new (std::env::current_exe().unwrap());
-        cmd .creation_flags ((DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW).0);
-
-        if let Ok (proc) = cmd .spawn() {
-            warn! ("Launched a new WinDusky process with pid: {:?}", proc.id());
-        }
-
-        //thread::sleep (Duration::from_millis (100));
-        //std::process::exit(0);
+fn show_effects_check_report (wd: &'static WinDusky) {
+    // we'll list the problems found in the effects (to the log too), in a message box off the tray thread (as it blocks)
+    let issues = wd.effects.lint_issues .iter() .filter (|i| i.level > crate::effects::lint::LintLevel::Note) .collect::<Vec<_>>();
+    let mut report = format! ("Found {} problem(s) in the configured color-effects :\n\n", issues.len());
+    for issue in issues.iter() {
+        info! ("color-effects check : {}", issue);
+        report += &format! ("{issue}\n\n");
+    }
+    report += "(These are also listed by 'WinDusky check-effects', and can be fixed via 'Edit Config')";
 
-        wd.post_req__quit();
+    thread::spawn (move || unsafe {
+        let (text, caption) = (wide_string (&report), wide_string ("WinDusky : Effects Check"));
+        MessageBoxW (None, PCWSTR (text.as_ptr()), PCWSTR (caption.as_ptr()), MB_OK | MB_ICONWARNING);
+    } );
+}
 
-    });
 
+fn handle_restart_request (wd: &'static WinDusky) {
+    warn! ("Attempting to Restart WinDusky !!");
+    wd.post_req__un_register_hotkeys();
+    wd.post_req__restart();
+    // ^^ the dusky thread tears down (gamma restore etc) before launching the new process, then quits
 }
@@ -58,6 +58,10 @@ pub fn check_window_visible (hwnd:Hwnd) -> bool { unsafe {
     IsWindowVisible (hwnd.into()) .as_bool()
 } }
 
+pub fn check_window_minimized (hwnd:Hwnd) -> bool { unsafe {
+    IsIconic (hwnd.into()) .as_bool()
+} }
+
 pub fn check_window_cloaked (hwnd:Hwnd) -> bool { unsafe {
     let mut cloaked_state: isize = 0;
     let out_ptr = &mut cloaked_state as *mut isize as *mut _;
@@ -95,6 +99,11 @@ pub fn get_pid_by_hwnd (hwnd:Hwnd) -> u32 { unsafe {
     let _ = GetWindowThreadProcessId (hwnd.into(), Some(&mut pid));
     pid
 } }
+pub fn get_thread_and_pid_by_hwnd (hwnd:Hwnd) -> (u32, u32) { unsafe {
+    let mut pid = 0u32;
+    let thread_id = GetWindowThreadProcessId (hwnd.into(), Some(&mut pid));
+    (thread_id, pid)
+} }
 pub fn get_exe_by_hwnd (hwnd:Hwnd) -> Option<String> {
     get_exe_by_pid ( get_pid_by_hwnd (hwnd))
 }